@@ -0,0 +1,141 @@
+//! 運用向けの管理API
+//!
+//! 接続レジストリの強制切断・全接続への NOTICE 通知を HTTP から呼び出す。
+//! 環境変数 `RELAY_ADMIN_TOKEN` を設定した場合のみ有効になり、
+//! `Authorization: Bearer <トークン>` が一致しないリクエストは 401 で拒否する。
+//! CloudFront 経由でも到達できるため、トークンは十分に長いランダム値にすること。
+
+use std::env;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::connection_registry::ConnectionRegistry;
+
+/// 管理APIのトークンの環境変数名
+const ENV_ADMIN_TOKEN: &str = "RELAY_ADMIN_TOKEN";
+
+/// 環境変数から管理APIのトークンを読み込む（未設定・空なら `None` で管理APIは無効）
+pub fn token_from_env() -> Option<String> {
+    env::var(ENV_ADMIN_TOKEN)
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+#[derive(Clone)]
+struct AdminState {
+    connections: Arc<ConnectionRegistry>,
+    token: Arc<str>,
+}
+
+/// 管理APIのルーターを作成する
+///
+/// - `POST /admin/connections/{connection_id}/disconnect`: 接続を強制切断（存在しなければ 404）
+/// - `POST /admin/ips/{ip}/disconnect`: 送信元IPが一致する全接続を強制切断
+/// - `POST /admin/notice`: 全接続に NOTICE を送る（本文は `{"message": "..."}`）
+pub fn router(connections: Arc<ConnectionRegistry>, token: String) -> Router {
+    let state = AdminState {
+        connections,
+        token: token.into(),
+    };
+    Router::new()
+        .route(
+            "/admin/connections/{connection_id}/disconnect",
+            post(disconnect),
+        )
+        .route("/admin/ips/{ip}/disconnect", post(disconnect_by_ip))
+        .route("/admin/notice", post(broadcast_notice))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+async fn authorize(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), state.token.as_bytes()));
+    if !authorized {
+        warn!(path = %request.uri().path(), "管理APIの認証に失敗");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+/// トークンの比較（一致した長さから推測されないよう、内容によらず全バイトを比較する）
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn disconnect(
+    State(state): State<AdminState>,
+    Path(connection_id): Path<String>,
+) -> StatusCode {
+    if state.connections.disconnect(&connection_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn disconnect_by_ip(State(state): State<AdminState>, Path(ip): Path<IpAddr>) -> Response {
+    let disconnected = state.connections.disconnect_by_ip(ip);
+    Json(json!({ "disconnected": disconnected })).into_response()
+}
+
+#[derive(Deserialize)]
+struct NoticeRequest {
+    message: String,
+}
+
+async fn broadcast_notice(
+    State(state): State<AdminState>,
+    Json(request): Json<NoticeRequest>,
+) -> Response {
+    let notified = state.connections.broadcast_notice(request.message);
+    Json(json!({ "notified": notified })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret-longer"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_token_from_env() {
+        unsafe {
+            env::set_var(ENV_ADMIN_TOKEN, "s3cr3t");
+        }
+        assert_eq!(token_from_env().as_deref(), Some("s3cr3t"));
+
+        // 空のトークンでは管理APIを有効にしない
+        unsafe {
+            env::set_var(ENV_ADMIN_TOKEN, "  ");
+        }
+        assert_eq!(token_from_env(), None);
+
+        unsafe {
+            env::remove_var(ENV_ADMIN_TOKEN);
+        }
+        assert_eq!(token_from_env(), None);
+    }
+}
@@ -15,6 +15,8 @@ pub const DEFAULT_MAX_SUBSCRIPTIONS: u32 = 20;
 pub const DEFAULT_MAX_FILTERS: u32 = 10;
 /// サブスクリプションIDの最大文字数（NIP-01仕様: 64固定）
 pub const DEFAULT_MAX_SUBID_LENGTH: u32 = 64;
+/// フィルタの limit の上限（これを超える limit はクランプする）
+pub const DEFAULT_MAX_LIMIT: u32 = 5000;
 /// イベントの最大タグ数
 pub const DEFAULT_MAX_EVENT_TAGS: u32 = 2000;
 /// コンテンツの最大文字数（64KB）
@@ -23,17 +25,48 @@ pub const DEFAULT_MAX_CONTENT_LENGTH: u32 = 65536;
 pub const DEFAULT_CREATED_AT_LOWER_LIMIT: u64 = 31536000;
 /// 未来の created_at 許容範囲（秒）（15分）
 pub const DEFAULT_CREATED_AT_UPPER_LIMIT: u64 = 900;
+/// 同一接続で実質同じフィルタの購読を拒否するか（デフォルトは警告ログのみ）
+pub const DEFAULT_REJECT_DUPLICATE_SUBSCRIPTIONS: bool = false;
+/// 保存完了を待たずに配信するか（デフォルトは保存を優先）
+pub const DEFAULT_DISTRIBUTE_BEFORE_PERSIST: bool = false;
+/// created_at が未来のイベントの配信を保留するか（デフォルトは即配信）
+pub const DEFAULT_HOLD_FUTURE_EVENTS: bool = false;
+/// 配信を保留するイベント数の上限
+pub const DEFAULT_MAX_HELD_EVENTS: u32 = 10000;
+/// REQ の until をサーバ現在時刻にクランプするか（デフォルトは NIP-01 どおりそのまま使う）
+pub const DEFAULT_CLAMP_UNTIL_TO_NOW: bool = false;
+/// 署名検証失敗の理由を OK メッセージに含めるか（デフォルトは含める）
+pub const DEFAULT_VERBOSE_ERRORS: bool = true;
+/// 診断用の STATS コマンドを受け付けるか（デフォルトは無効）
+pub const DEFAULT_ENABLE_STATS_COMMAND: bool = false;
+/// EVENT の OK メッセージに処理時間を付記するか（デフォルトは付記しない）
+pub const DEFAULT_INCLUDE_TIMING_IN_OK: bool = false;
+/// 条件のないフィルタの REQ を拒否するか（デフォルトは拒否せず件数を絞る）
+pub const DEFAULT_REQUIRE_FILTER: bool = false;
 
 // 環境変数名
 const ENV_MAX_MESSAGE_LENGTH: &str = "RELAY_MAX_MESSAGE_LENGTH";
 const ENV_MAX_SUBSCRIPTIONS: &str = "RELAY_MAX_SUBSCRIPTIONS";
 const ENV_MAX_FILTERS: &str = "RELAY_MAX_FILTERS";
+const ENV_MAX_LIMIT: &str = "RELAY_MAX_LIMIT";
+const ENV_DEFAULT_LIMIT: &str = "RELAY_DEFAULT_LIMIT";
 const ENV_MAX_EVENT_TAGS: &str = "RELAY_MAX_EVENT_TAGS";
 const ENV_MAX_CONTENT_LENGTH: &str = "RELAY_MAX_CONTENT_LENGTH";
 const ENV_CREATED_AT_LOWER_LIMIT: &str = "RELAY_CREATED_AT_LOWER_LIMIT";
 const ENV_CREATED_AT_UPPER_LIMIT: &str = "RELAY_CREATED_AT_UPPER_LIMIT";
+const ENV_REJECT_DUPLICATE_SUBSCRIPTIONS: &str = "RELAY_REJECT_DUPLICATE_SUBSCRIPTIONS";
+const ENV_DISTRIBUTE_BEFORE_PERSIST: &str = "RELAY_DISTRIBUTE_BEFORE_PERSIST";
+const ENV_HOLD_FUTURE_EVENTS: &str = "RELAY_HOLD_FUTURE_EVENTS";
+const ENV_MAX_HELD_EVENTS: &str = "RELAY_MAX_HELD_EVENTS";
+const ENV_CLAMP_UNTIL_TO_NOW: &str = "RELAY_CLAMP_UNTIL_TO_NOW";
+const ENV_VERBOSE_ERRORS: &str = "RELAY_VERBOSE_ERRORS";
+const ENV_ENABLE_STATS_COMMAND: &str = "RELAY_ENABLE_STATS_COMMAND";
+const ENV_INCLUDE_TIMING_IN_OK: &str = "RELAY_INCLUDE_TIMING_IN_OK";
+const ENV_REQUIRE_FILTER: &str = "RELAY_REQUIRE_FILTER";
 
 /// NIP-11 limitation に対応する制限値設定
+///
+/// NIP-11 に対応しないリレー独自の制限・挙動の設定もここで扱う。
 #[derive(Debug, Clone, PartialEq)]
 pub struct LimitationConfig {
     /// WebSocketメッセージの最大バイト数
@@ -44,6 +77,12 @@ pub struct LimitationConfig {
     pub max_filters: u32,
     /// サブスクリプションIDの最大文字数
     pub max_subid_length: u32,
+    /// フィルタの limit の上限
+    pub max_limit: u32,
+    /// limit 未指定のフィルタに適用する limit（None なら max_limit を適用する）
+    ///
+    /// since と until を両方指定した範囲クエリには適用せず、範囲内の全件を max_limit まで返す。
+    pub default_limit: Option<u32>,
     /// イベントの最大タグ数
     pub max_event_tags: u32,
     /// コンテンツの最大文字数
@@ -52,6 +91,44 @@ pub struct LimitationConfig {
     pub created_at_lower_limit: u64,
     /// 未来の created_at 許容範囲（秒）
     pub created_at_upper_limit: u64,
+    /// 同一接続で実質同じフィルタの購読を拒否するか（false なら警告ログのみ）
+    pub reject_duplicate_subscriptions: bool,
+    /// 保存完了を待たずに購読者へ配信するか
+    ///
+    /// 有効にすると配信のレイテンシは下がるが、保存に失敗したイベントや
+    /// 重複・古い Replaceable も配信されうる（OK は保存結果に従う）。
+    pub distribute_before_persist: bool,
+    /// created_at が現在より未来のイベントの購読者への配信を、created_at 到達まで保留するか
+    ///
+    /// 保存は通常どおり行うので REQ では取得できる。保留の解除は
+    /// `Relay::run_held_events_release` のバックグラウンドタスクが定期的に行う。
+    /// 保留中に削除・置換されたイベントは配信しない。
+    pub hold_future_events: bool,
+    /// 配信を保留するイベント数の上限
+    ///
+    /// 上限に達している間に届いた未来のイベントは保留せずに即配信する。
+    pub max_held_events: u32,
+    /// 初回クエリで、現在時刻より未来の until を現在時刻にクランプするか
+    ///
+    /// 未来の created_at を持つイベントが REQ の結果に混ざらないようにする。
+    /// until 未指定のフィルタには適用しない。
+    pub clamp_until_to_now: bool,
+    /// ID・署名の検証に失敗した EVENT の OK メッセージに理由を含めるか
+    ///
+    /// false なら理由を省いて `invalid:` だけを返す（攻撃者に手がかりを与えない）。
+    pub verbose_errors: bool,
+    /// 診断用の `["STATS"]` コマンド（NIP 標準外）を受け付けるか
+    ///
+    /// 無効なら未知のメッセージタイプとして NOTICE を返す。
+    pub enable_stats_command: bool,
+    /// EVENT の OK メッセージに検証・保存の処理時間を付記するか（性能調査用の診断モード）
+    ///
+    /// 付記は prefix 付きメッセージの末尾に括弧で足すため、NIP-01 の prefix 判定は変わらない。
+    pub include_timing_in_ok: bool,
+    /// 条件のないフィルタ（`{}` や limit だけのフィルタ）を含む REQ を CLOSED で拒否するか
+    ///
+    /// 無効の場合も全件は返さず、limit 未指定なら default_limit（未設定なら max_limit）件に絞る。
+    pub require_filter: bool,
 }
 
 impl Default for LimitationConfig {
@@ -61,10 +138,21 @@ impl Default for LimitationConfig {
             max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
             max_filters: DEFAULT_MAX_FILTERS,
             max_subid_length: DEFAULT_MAX_SUBID_LENGTH,
+            max_limit: DEFAULT_MAX_LIMIT,
+            default_limit: None,
             max_event_tags: DEFAULT_MAX_EVENT_TAGS,
             max_content_length: DEFAULT_MAX_CONTENT_LENGTH,
             created_at_lower_limit: DEFAULT_CREATED_AT_LOWER_LIMIT,
             created_at_upper_limit: DEFAULT_CREATED_AT_UPPER_LIMIT,
+            reject_duplicate_subscriptions: DEFAULT_REJECT_DUPLICATE_SUBSCRIPTIONS,
+            distribute_before_persist: DEFAULT_DISTRIBUTE_BEFORE_PERSIST,
+            hold_future_events: DEFAULT_HOLD_FUTURE_EVENTS,
+            max_held_events: DEFAULT_MAX_HELD_EVENTS,
+            clamp_until_to_now: DEFAULT_CLAMP_UNTIL_TO_NOW,
+            verbose_errors: DEFAULT_VERBOSE_ERRORS,
+            enable_stats_command: DEFAULT_ENABLE_STATS_COMMAND,
+            include_timing_in_ok: DEFAULT_INCLUDE_TIMING_IN_OK,
+            require_filter: DEFAULT_REQUIRE_FILTER,
         }
     }
 }
@@ -79,6 +167,8 @@ impl LimitationConfig {
             max_subscriptions: parse_env_u32(ENV_MAX_SUBSCRIPTIONS, DEFAULT_MAX_SUBSCRIPTIONS),
             max_filters: parse_env_u32(ENV_MAX_FILTERS, DEFAULT_MAX_FILTERS),
             max_subid_length: DEFAULT_MAX_SUBID_LENGTH, // NIP-01仕様固定
+            max_limit: parse_env_u32(ENV_MAX_LIMIT, DEFAULT_MAX_LIMIT),
+            default_limit: parse_env_optional_u32(ENV_DEFAULT_LIMIT),
             max_event_tags: parse_env_u32(ENV_MAX_EVENT_TAGS, DEFAULT_MAX_EVENT_TAGS),
             max_content_length: parse_env_u32(ENV_MAX_CONTENT_LENGTH, DEFAULT_MAX_CONTENT_LENGTH),
             created_at_lower_limit: parse_env_u64(
@@ -89,6 +179,27 @@ impl LimitationConfig {
                 ENV_CREATED_AT_UPPER_LIMIT,
                 DEFAULT_CREATED_AT_UPPER_LIMIT,
             ),
+            reject_duplicate_subscriptions: parse_env_bool(
+                ENV_REJECT_DUPLICATE_SUBSCRIPTIONS,
+                DEFAULT_REJECT_DUPLICATE_SUBSCRIPTIONS,
+            ),
+            distribute_before_persist: parse_env_bool(
+                ENV_DISTRIBUTE_BEFORE_PERSIST,
+                DEFAULT_DISTRIBUTE_BEFORE_PERSIST,
+            ),
+            hold_future_events: parse_env_bool(ENV_HOLD_FUTURE_EVENTS, DEFAULT_HOLD_FUTURE_EVENTS),
+            max_held_events: parse_env_u32(ENV_MAX_HELD_EVENTS, DEFAULT_MAX_HELD_EVENTS),
+            clamp_until_to_now: parse_env_bool(ENV_CLAMP_UNTIL_TO_NOW, DEFAULT_CLAMP_UNTIL_TO_NOW),
+            verbose_errors: parse_env_bool(ENV_VERBOSE_ERRORS, DEFAULT_VERBOSE_ERRORS),
+            enable_stats_command: parse_env_bool(
+                ENV_ENABLE_STATS_COMMAND,
+                DEFAULT_ENABLE_STATS_COMMAND,
+            ),
+            include_timing_in_ok: parse_env_bool(
+                ENV_INCLUDE_TIMING_IN_OK,
+                DEFAULT_INCLUDE_TIMING_IN_OK,
+            ),
+            require_filter: parse_env_bool(ENV_REQUIRE_FILTER, DEFAULT_REQUIRE_FILTER),
         };
 
         info!(
@@ -96,10 +207,21 @@ impl LimitationConfig {
             max_subscriptions = config.max_subscriptions,
             max_filters = config.max_filters,
             max_subid_length = config.max_subid_length,
+            max_limit = config.max_limit,
+            default_limit = ?config.default_limit,
             max_event_tags = config.max_event_tags,
             max_content_length = config.max_content_length,
             created_at_lower_limit = config.created_at_lower_limit,
             created_at_upper_limit = config.created_at_upper_limit,
+            reject_duplicate_subscriptions = config.reject_duplicate_subscriptions,
+            distribute_before_persist = config.distribute_before_persist,
+            hold_future_events = config.hold_future_events,
+            max_held_events = config.max_held_events,
+            clamp_until_to_now = config.clamp_until_to_now,
+            verbose_errors = config.verbose_errors,
+            enable_stats_command = config.enable_stats_command,
+            include_timing_in_ok = config.include_timing_in_ok,
+            require_filter = config.require_filter,
             "制限値設定を読み込みました"
         );
 
@@ -121,6 +243,18 @@ fn parse_env_u32(key: &str, default: u32) -> u32 {
     }
 }
 
+/// 環境変数から省略可能な u32 を読み込む（未設定・パース失敗時は None）
+fn parse_env_optional_u32(key: &str) -> Option<u32> {
+    let v = env::var(key).ok()?;
+    match v.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            warn!(key = key, value = %v, "環境変数の値が不正です。設定しなかったものとして扱います");
+            None
+        }
+    }
+}
+
 /// 環境変数から u64 を読み込む（パース失敗時はデフォルト値）
 fn parse_env_u64(key: &str, default: u64) -> u64 {
     match env::var(key) {
@@ -135,6 +269,20 @@ fn parse_env_u64(key: &str, default: u64) -> u64 {
     }
 }
 
+/// 環境変数から bool を読み込む（"true" / "false"、パース失敗時はデフォルト値）
+fn parse_env_bool(key: &str, default: bool) -> bool {
+    match env::var(key) {
+        Ok(v) => match v.parse() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                warn!(key = key, value = %v, default = default, "環境変数の値が不正です。デフォルト値を使用します");
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,10 +295,21 @@ mod tests {
         assert_eq!(config.max_subscriptions, 20);
         assert_eq!(config.max_filters, 10);
         assert_eq!(config.max_subid_length, 64);
+        assert_eq!(config.max_limit, 5000);
+        assert_eq!(config.default_limit, None);
         assert_eq!(config.max_event_tags, 2000);
         assert_eq!(config.max_content_length, 65536);
         assert_eq!(config.created_at_lower_limit, 31536000);
         assert_eq!(config.created_at_upper_limit, 900);
+        assert!(!config.reject_duplicate_subscriptions);
+        assert!(!config.distribute_before_persist);
+        assert!(!config.hold_future_events);
+        assert_eq!(config.max_held_events, 10000);
+        assert!(!config.clamp_until_to_now);
+        assert!(config.verbose_errors);
+        assert!(!config.enable_stats_command);
+        assert!(!config.include_timing_in_ok);
+        assert!(!config.require_filter);
     }
 
     #[test]
@@ -161,10 +320,21 @@ mod tests {
             ENV_MAX_MESSAGE_LENGTH,
             ENV_MAX_SUBSCRIPTIONS,
             ENV_MAX_FILTERS,
+            ENV_MAX_LIMIT,
+            ENV_DEFAULT_LIMIT,
             ENV_MAX_EVENT_TAGS,
             ENV_MAX_CONTENT_LENGTH,
             ENV_CREATED_AT_LOWER_LIMIT,
             ENV_CREATED_AT_UPPER_LIMIT,
+            ENV_REJECT_DUPLICATE_SUBSCRIPTIONS,
+            ENV_DISTRIBUTE_BEFORE_PERSIST,
+            ENV_HOLD_FUTURE_EVENTS,
+            ENV_MAX_HELD_EVENTS,
+            ENV_CLAMP_UNTIL_TO_NOW,
+            ENV_VERBOSE_ERRORS,
+            ENV_ENABLE_STATS_COMMAND,
+            ENV_INCLUDE_TIMING_IN_OK,
+            ENV_REQUIRE_FILTER,
         ] {
             unsafe {
                 env::remove_var(key);
@@ -182,30 +352,63 @@ mod tests {
             env::set_var(ENV_MAX_MESSAGE_LENGTH, "262144");
             env::set_var(ENV_MAX_SUBSCRIPTIONS, "50");
             env::set_var(ENV_MAX_FILTERS, "20");
+            env::set_var(ENV_MAX_LIMIT, "1000");
+            env::set_var(ENV_DEFAULT_LIMIT, "100");
             env::set_var(ENV_MAX_EVENT_TAGS, "5000");
             env::set_var(ENV_MAX_CONTENT_LENGTH, "131072");
             env::set_var(ENV_CREATED_AT_LOWER_LIMIT, "63072000");
             env::set_var(ENV_CREATED_AT_UPPER_LIMIT, "1800");
+            env::set_var(ENV_REJECT_DUPLICATE_SUBSCRIPTIONS, "true");
+            env::set_var(ENV_DISTRIBUTE_BEFORE_PERSIST, "true");
+            env::set_var(ENV_HOLD_FUTURE_EVENTS, "true");
+            env::set_var(ENV_MAX_HELD_EVENTS, "500");
+            env::set_var(ENV_CLAMP_UNTIL_TO_NOW, "true");
+            env::set_var(ENV_VERBOSE_ERRORS, "false");
+            env::set_var(ENV_ENABLE_STATS_COMMAND, "true");
+            env::set_var(ENV_INCLUDE_TIMING_IN_OK, "true");
+            env::set_var(ENV_REQUIRE_FILTER, "true");
         }
 
         let config = LimitationConfig::from_env();
         assert_eq!(config.max_message_length, 262144);
         assert_eq!(config.max_subscriptions, 50);
         assert_eq!(config.max_filters, 20);
+        assert_eq!(config.max_limit, 1000);
+        assert_eq!(config.default_limit, Some(100));
         assert_eq!(config.max_event_tags, 5000);
         assert_eq!(config.max_content_length, 131072);
         assert_eq!(config.created_at_lower_limit, 63072000);
         assert_eq!(config.created_at_upper_limit, 1800);
+        assert!(config.reject_duplicate_subscriptions);
+        assert!(config.distribute_before_persist);
+        assert!(config.hold_future_events);
+        assert_eq!(config.max_held_events, 500);
+        assert!(config.clamp_until_to_now);
+        assert!(!config.verbose_errors);
+        assert!(config.enable_stats_command);
+        assert!(config.include_timing_in_ok);
+        assert!(config.require_filter);
 
         // クリーンアップ
         for key in [
             ENV_MAX_MESSAGE_LENGTH,
             ENV_MAX_SUBSCRIPTIONS,
             ENV_MAX_FILTERS,
+            ENV_MAX_LIMIT,
+            ENV_DEFAULT_LIMIT,
             ENV_MAX_EVENT_TAGS,
             ENV_MAX_CONTENT_LENGTH,
             ENV_CREATED_AT_LOWER_LIMIT,
             ENV_CREATED_AT_UPPER_LIMIT,
+            ENV_REJECT_DUPLICATE_SUBSCRIPTIONS,
+            ENV_DISTRIBUTE_BEFORE_PERSIST,
+            ENV_HOLD_FUTURE_EVENTS,
+            ENV_MAX_HELD_EVENTS,
+            ENV_CLAMP_UNTIL_TO_NOW,
+            ENV_VERBOSE_ERRORS,
+            ENV_ENABLE_STATS_COMMAND,
+            ENV_INCLUDE_TIMING_IN_OK,
+            ENV_REQUIRE_FILTER,
         ] {
             unsafe {
                 env::remove_var(key);
@@ -219,15 +422,24 @@ mod tests {
         unsafe {
             env::set_var(ENV_MAX_MESSAGE_LENGTH, "not_a_number");
             env::set_var(ENV_MAX_SUBSCRIPTIONS, "-1");
+            env::set_var(ENV_REJECT_DUPLICATE_SUBSCRIPTIONS, "yes");
+            env::set_var(ENV_DEFAULT_LIMIT, "-1");
         }
 
         let config = LimitationConfig::from_env();
         assert_eq!(config.max_message_length, DEFAULT_MAX_MESSAGE_LENGTH);
         assert_eq!(config.max_subscriptions, DEFAULT_MAX_SUBSCRIPTIONS);
+        assert_eq!(
+            config.reject_duplicate_subscriptions,
+            DEFAULT_REJECT_DUPLICATE_SUBSCRIPTIONS
+        );
+        assert_eq!(config.default_limit, None);
 
         unsafe {
             env::remove_var(ENV_MAX_MESSAGE_LENGTH);
             env::remove_var(ENV_MAX_SUBSCRIPTIONS);
+            env::remove_var(ENV_REJECT_DUPLICATE_SUBSCRIPTIONS);
+            env::remove_var(ENV_DEFAULT_LIMIT);
         }
     }
 }
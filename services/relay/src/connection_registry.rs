@@ -0,0 +1,403 @@
+//! 接続レジストリ
+//!
+//! 確立中の WebSocket 接続のメタデータ（User-Agent, 送信元IP）を記録し、
+//! 運用・モデレーションのための検索、強制切断、全接続への NOTICE 通知を提供する。
+//! 強制切断と NOTICE 通知は管理API（`crate::admin`）から呼び出す。
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use axum::http::HeaderMap;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// 全接続向け NOTICE のチャネル容量
+const NOTICE_CHANNEL_CAPACITY: usize = 16;
+
+/// 接続のメタデータ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// 接続ID
+    pub connection_id: String,
+    /// User-Agent ヘッダー
+    pub user_agent: Option<String>,
+    /// 送信元IP
+    pub ip: IpAddr,
+    /// 接続確立時刻（UNIX秒）
+    pub connected_at: u64,
+}
+
+impl ConnectionInfo {
+    /// ハンドシェイク時のヘッダーとピアアドレスからメタデータを作成する
+    pub fn from_request(connection_id: String, headers: &HeaderMap, peer: SocketAddr) -> Self {
+        let user_agent = headers
+            .get("User-Agent")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        Self {
+            connection_id,
+            user_agent,
+            ip: client_ip(headers, peer),
+            connected_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// 送信元IPを決定する
+///
+/// CloudFront 経由の接続ではピアアドレスがプロキシになるため、`X-Forwarded-For` の末尾
+/// （直前のプロキシが追加した値）を使う。先頭側はクライアントが自由に書けるので使わない。
+///
+/// `CloudFront-Viewer-Address` は使わない。オリジンリクエストポリシー
+/// （`Managed-AllViewerExceptHostHeader`）では CloudFront が付与しないため、
+/// クライアントが送った値がそのまま届いてしまう。
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or_else(|| peer.ip())
+}
+
+struct Entry {
+    info: ConnectionInfo,
+    /// 強制切断用のトークン
+    cancel: CancellationToken,
+}
+
+/// 確立中の接続の一覧
+pub struct ConnectionRegistry {
+    connections: Mutex<HashMap<String, Entry>>,
+    /// 全接続向け NOTICE の送信側
+    notices: broadcast::Sender<String>,
+}
+
+impl Default for ConnectionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        let (notices, _) = broadcast::channel(NOTICE_CHANNEL_CAPACITY);
+        Self {
+            connections: Mutex::new(HashMap::new()),
+            notices,
+        }
+    }
+
+    fn connections(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 接続を登録する
+    ///
+    /// 返り値のガードが持つトークンは `shutdown` の子で、シャットダウン時と強制切断時にキャンセルされる。
+    /// ガードを drop すると登録が解除される。
+    pub fn register(
+        self: &Arc<Self>,
+        info: ConnectionInfo,
+        shutdown: &CancellationToken,
+    ) -> ConnectionGuard {
+        let cancel = shutdown.child_token();
+        let connection_id = info.connection_id.clone();
+        self.connections().insert(
+            connection_id.clone(),
+            Entry {
+                info,
+                cancel: cancel.clone(),
+            },
+        );
+        ConnectionGuard {
+            registry: Arc::clone(self),
+            connection_id,
+            cancel,
+            notices: self.notices.subscribe(),
+        }
+    }
+
+    fn unregister(&self, connection_id: &str) {
+        self.connections().remove(connection_id);
+    }
+
+    /// 登録中の接続数
+    pub fn len(&self) -> usize {
+        self.connections().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 接続IDでメタデータを取得する
+    pub fn get(&self, connection_id: &str) -> Option<ConnectionInfo> {
+        self.connections()
+            .get(connection_id)
+            .map(|entry| entry.info.clone())
+    }
+
+    /// 送信元IPが一致する接続を検索する
+    pub fn find_by_ip(&self, ip: IpAddr) -> Vec<ConnectionInfo> {
+        self.connections()
+            .values()
+            .filter(|entry| entry.info.ip == ip)
+            .map(|entry| entry.info.clone())
+            .collect()
+    }
+
+    /// 接続を強制切断する。存在しない接続IDなら何もせず `false` を返す
+    pub fn disconnect(&self, connection_id: &str) -> bool {
+        match self.connections().get(connection_id) {
+            Some(entry) => {
+                info!(connection_id, "接続を強制切断");
+                entry.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 送信元IPが一致する全接続を強制切断し、切断した数を返す
+    pub fn disconnect_by_ip(&self, ip: IpAddr) -> usize {
+        let connections = self.connections();
+        let targets: Vec<&Entry> = connections
+            .values()
+            .filter(|entry| entry.info.ip == ip)
+            .collect();
+        for entry in &targets {
+            entry.cancel.cancel();
+        }
+        info!(ip = %ip, count = targets.len(), "送信元IPの接続を強制切断");
+        targets.len()
+    }
+
+    /// 全接続に NOTICE を送る（メンテナンス告知など）。通知先の接続数を返す
+    pub fn broadcast_notice(&self, message: impl Into<String>) -> usize {
+        let message = message.into();
+        let count = self.notices.send(message.clone()).unwrap_or(0);
+        info!(message = %message, count, "全接続にNOTICEを送信");
+        count
+    }
+}
+
+/// 接続の登録ガード（drop で登録解除）
+pub struct ConnectionGuard {
+    registry: Arc<ConnectionRegistry>,
+    connection_id: String,
+    cancel: CancellationToken,
+    notices: broadcast::Receiver<String>,
+}
+
+impl ConnectionGuard {
+    /// 接続を終了すべきことを通知するトークン
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// 全接続向けの NOTICE を待つ（キャンセル安全）
+    ///
+    /// 受信が遅れて取りこぼした分は破棄して次の NOTICE を待つ。
+    pub async fn recv_notice(&mut self) -> String {
+        loop {
+            match self.notices.recv().await {
+                Ok(message) => return message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "NOTICEの受信が遅延し一部を破棄");
+                }
+                // 送信側はレジストリが保持しており、ガードが生きている間は閉じない
+                Err(RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.registry.unregister(&self.connection_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(connection_id: &str, ip: &str) -> ConnectionInfo {
+        ConnectionInfo {
+            connection_id: connection_id.to_string(),
+            user_agent: Some("test-client/1.0".to_string()),
+            ip: ip.parse().unwrap(),
+            connected_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_register_and_unregister_on_drop() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+
+        let guard = registry.register(info("c1", "192.0.2.1"), &shutdown);
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get("c1"), Some(info("c1", "192.0.2.1")));
+
+        drop(guard);
+        assert!(registry.is_empty());
+        assert_eq!(registry.get("c1"), None);
+    }
+
+    #[test]
+    fn test_find_by_ip() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+        let _g1 = registry.register(info("c1", "192.0.2.1"), &shutdown);
+        let _g2 = registry.register(info("c2", "192.0.2.2"), &shutdown);
+        let _g3 = registry.register(info("c3", "192.0.2.1"), &shutdown);
+
+        let mut ids: Vec<String> = registry
+            .find_by_ip("192.0.2.1".parse().unwrap())
+            .into_iter()
+            .map(|i| i.connection_id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["c1", "c3"]);
+        assert!(
+            registry
+                .find_by_ip("198.51.100.1".parse().unwrap())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_disconnect() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+        let g1 = registry.register(info("c1", "192.0.2.1"), &shutdown);
+        let g2 = registry.register(info("c2", "192.0.2.1"), &shutdown);
+
+        assert!(registry.disconnect("c1"));
+        assert!(g1.cancellation_token().is_cancelled());
+        assert!(!g2.cancellation_token().is_cancelled());
+        assert!(!shutdown.is_cancelled());
+    }
+
+    #[test]
+    fn test_disconnect_unknown_connection_is_noop() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        assert!(!registry.disconnect("unknown"));
+    }
+
+    #[test]
+    fn test_disconnect_by_ip() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+        let g1 = registry.register(info("c1", "192.0.2.1"), &shutdown);
+        let g2 = registry.register(info("c2", "192.0.2.2"), &shutdown);
+        let g3 = registry.register(info("c3", "192.0.2.1"), &shutdown);
+
+        assert_eq!(registry.disconnect_by_ip("192.0.2.1".parse().unwrap()), 2);
+        assert!(g1.cancellation_token().is_cancelled());
+        assert!(!g2.cancellation_token().is_cancelled());
+        assert!(g3.cancellation_token().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_notice() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+        let mut g1 = registry.register(info("c1", "192.0.2.1"), &shutdown);
+        let mut g2 = registry.register(info("c2", "192.0.2.2"), &shutdown);
+
+        assert_eq!(registry.broadcast_notice("メンテナンスを行います"), 2);
+        assert_eq!(g1.recv_notice().await, "メンテナンスを行います");
+        assert_eq!(g2.recv_notice().await, "メンテナンスを行います");
+    }
+
+    #[test]
+    fn test_broadcast_notice_without_connections() {
+        let registry = ConnectionRegistry::new();
+        assert_eq!(registry.broadcast_notice("no one"), 0);
+    }
+
+    #[test]
+    fn test_shutdown_cancels_all_connections() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let shutdown = CancellationToken::new();
+        let g1 = registry.register(info("c1", "192.0.2.1"), &shutdown);
+
+        shutdown.cancel();
+        assert!(g1.cancellation_token().is_cancelled());
+    }
+
+    #[test]
+    fn test_client_ip_uses_last_forwarded_for() {
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            client_ip(&headers, peer),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+
+        headers.insert("X-Forwarded-For", "203.0.113.5".parse().unwrap());
+        assert_eq!(
+            client_ip(&headers, peer),
+            "203.0.113.5".parse::<IpAddr>().unwrap()
+        );
+
+        // クライアントが先頭に偽の値を付けても、プロキシが追加した末尾を使う
+        headers.insert(
+            "X-Forwarded-For",
+            "198.51.100.66, 203.0.113.5".parse().unwrap(),
+        );
+        assert_eq!(
+            client_ip(&headers, peer),
+            "203.0.113.5".parse::<IpAddr>().unwrap()
+        );
+
+        // パースできない値はピアアドレスにフォールバック
+        headers.insert("X-Forwarded-For", "unknown".parse().unwrap());
+        assert_eq!(
+            client_ip(&headers, peer),
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_client_ip_ignores_cloudfront_viewer_address() {
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            "198.51.100.66, 203.0.113.5".parse().unwrap(),
+        );
+        // クライアントが送った値がそのまま届くので信用しない
+        headers.insert(
+            "CloudFront-Viewer-Address",
+            "192.0.2.7:46532".parse().unwrap(),
+        );
+        assert_eq!(
+            client_ip(&headers, peer),
+            "203.0.113.5".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_connection_info_from_request() {
+        let peer: SocketAddr = "10.0.0.1:443".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", "nostr-client/2.0".parse().unwrap());
+
+        let info = ConnectionInfo::from_request("c1".to_string(), &headers, peer);
+        assert_eq!(info.connection_id, "c1");
+        assert_eq!(info.user_agent.as_deref(), Some("nostr-client/2.0"));
+        assert_eq!(info.ip, "10.0.0.1".parse::<IpAddr>().unwrap());
+    }
+}
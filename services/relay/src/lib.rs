@@ -1,9 +1,12 @@
+pub mod admin;
 pub mod config;
+pub mod connection_registry;
 pub mod logging;
 pub mod models;
 pub mod nip11;
 pub mod owner_priority;
 pub mod relay;
+pub mod retention;
 pub mod store;
 #[cfg(test)]
 pub mod test_helpers;
@@ -1,8 +1,10 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use anyhow::Context;
 use axum::{
     Router,
+    extract::ConnectInfo,
     extract::State,
     extract::ws::{WebSocketUpgrade, rejection::WebSocketUpgradeRejection},
     http::HeaderMap,
@@ -12,12 +14,15 @@ use axum::{
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
+use relay::admin;
 use relay::config::LimitationConfig;
+use relay::connection_registry::{ConnectionInfo, ConnectionRegistry};
 use relay::logging;
 use relay::nip11::RelayInformation;
 use relay::owner_priority::OwnerPriority;
-use relay::relay::Relay;
-use relay::store::{AppEventStore, create_event_store};
+use relay::relay::{HELD_EVENTS_RELEASE_INTERVAL, Relay};
+use relay::retention;
+use relay::store::{AppEventStore, EventStore, create_event_store};
 use relay::ws;
 
 /// アプリケーション共有状態
@@ -26,11 +31,13 @@ struct AppState {
     relay: Arc<Relay<AppEventStore>>,
     limitation: Arc<LimitationConfig>,
     owner_priority: Arc<OwnerPriority>,
+    connections: Arc<ConnectionRegistry>,
     shutdown: CancellationToken,
 }
 
 async fn handler(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     ws: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
 ) -> Response {
@@ -42,9 +49,20 @@ async fn handler(
             let relay = state.relay.clone();
             let limitation = state.limitation.clone();
             let owner_priority = state.owner_priority.clone();
+            let connections = state.connections.clone();
             let shutdown = state.shutdown.clone();
+            let connection_info = ConnectionInfo::from_request(conn_id.clone(), &headers, peer);
             ws.on_upgrade(move |socket| {
-                ws::handle_socket(socket, relay, conn_id, limitation, owner_priority, shutdown)
+                // 接続中はレジストリに登録しておき、強制切断・全体通知の対象にする
+                let connection = connections.register(connection_info, &shutdown);
+                ws::handle_socket(
+                    socket,
+                    relay,
+                    conn_id,
+                    limitation,
+                    owner_priority,
+                    connection,
+                )
             })
         }
         Err(_) => {
@@ -118,12 +136,20 @@ async fn main() -> anyhow::Result<()> {
     // 制限値設定を読み込み
     let limitation = Arc::new(LimitationConfig::from_env());
 
+    // 保持期間設定（ストアの TTL 付与と定期削除の両方で使うため、ここで一度だけ読み込む）
+    let retention_policies = retention::policies_from_env();
+
     // EventStore の実装を選択（feature flagに基づいてDynamoDB/InMemory切り替え）
-    let (store, owner_priority) = create_event_store().await?;
-    let relay = Arc::new(Relay::new(store));
+    let (store, owner_priority) = create_event_store(retention_policies.clone()).await?;
+    let relay = Arc::new(
+        Relay::new(store)
+            .with_hold_future_events(limitation.hold_future_events)
+            .with_max_held_events(limitation.max_held_events as usize),
+    );
 
     // DynamoDB使用時: バックグラウンドで既存イベントをロード
     // ロード完了前のREQは不完全な結果を返すが、サーバーはすぐにリッスン開始する
+    // （ロード中にキャッシュした結果も、TTL が切れるまでは不完全なまま返る）
     #[cfg(feature = "dynamo")]
     {
         let relay_clone = Arc::clone(&relay);
@@ -133,6 +159,7 @@ async fn main() -> anyhow::Result<()> {
             let created_at_lower_limit = limitation_clone.created_at_lower_limit;
             match relay_clone
                 .store()
+                .inner()
                 .load_recent_events(created_at_lower_limit)
                 .await
             {
@@ -143,14 +170,66 @@ async fn main() -> anyhow::Result<()> {
     }
 
     let shutdown = CancellationToken::new();
+
+    // 未来の created_at の配信保留が有効なら、created_at に到達したものを定期的に配信する
+    if limitation.hold_future_events {
+        let relay_clone = Arc::clone(&relay);
+        let shutdown_clone = shutdown.clone();
+        tokio::spawn(async move {
+            relay_clone
+                .run_held_events_release(HELD_EVENTS_RELEASE_INTERVAL, &shutdown_clone)
+                .await;
+        });
+    }
+
+    // 保持期間設定があれば、期限切れイベントを定期的に削除する
+    // 初回は起動直後に実行せず 1 周期待つ（DynamoDB からのロード中に削除が走らないように）
+    if !retention_policies.is_empty() {
+        let relay_clone = Arc::clone(&relay);
+        let shutdown_clone = shutdown.clone();
+        let period = retention::purge_interval_from_env();
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown_clone.cancelled() => return,
+                    _ = interval.tick() => {}
+                }
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                match relay_clone
+                    .store()
+                    .purge_expired(&retention_policies, now)
+                    .await
+                {
+                    Ok(purged_count) => info!(purged_count, "期限切れイベントを削除"),
+                    Err(e) => error!(error = %e, "期限切れイベントの削除に失敗"),
+                }
+            }
+        });
+    }
+
+    let connections = Arc::new(ConnectionRegistry::new());
     let state = AppState {
         relay,
         limitation,
         owner_priority,
+        connections: Arc::clone(&connections),
         shutdown: shutdown.clone(),
     };
 
-    let app = Router::new().route("/", get(handler)).with_state(state);
+    let mut app = Router::new().route("/", get(handler)).with_state(state);
+
+    // 管理APIはトークンが設定されている場合のみ公開する
+    match admin::token_from_env() {
+        Some(token) => {
+            app = app.merge(admin::router(connections, token));
+            info!("管理APIを有効化");
+        }
+        None => info!("RELAY_ADMIN_TOKEN が未設定のため管理APIは無効"),
+    }
 
     let bind_addr = "0.0.0.0:3000";
     info!(
@@ -166,10 +245,13 @@ async fn main() -> anyhow::Result<()> {
     // 1. SIGTERM/SIGINTを受信
     // 2. CancellationTokenをキャンセル → 各WebSocket接続にCloseフレーム送信を通知
     // 3. 新規接続の受付を停止し、既存接続のクローズ完了を待機
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(shutdown))
-        .await
-        .context("サーバー起動に失敗")?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(shutdown))
+    .await
+    .context("サーバー起動に失敗")?;
 
     info!("サーバーをシャットダウンしました");
     Ok(())
@@ -8,25 +8,25 @@ mod sig;
 pub use sig::Sig;
 
 mod subscription_id;
-pub use subscription_id::SubscriptionId;
+pub use subscription_id::{SubscriptionId, SubscriptionIdParseError};
 
 mod timestamp;
 pub use timestamp::Timestamp;
 
 mod kind;
-pub use kind::Kind;
+pub use kind::{Kind, KindClass};
 
 mod tag;
 pub use tag::Tag;
 
 mod event;
-pub use event::{Event, VerifiedEvent};
+pub use event::{Event, VerificationError, VerifiedEvent};
 
 mod filter;
-pub use filter::Filter;
+pub use filter::{Filter, FilterMismatch, MatchExplanation};
 
 mod client_message;
-pub use client_message::ClientMessage;
+pub use client_message::{ClientMessage, ClientMessageParseError};
 
 mod relay_message;
-pub use relay_message::RelayMessage;
+pub use relay_message::{ConnectionStats, MachineReadablePrefix, RelayMessage};
@@ -39,6 +39,10 @@ pub enum ClientMessageParseError {
     /// CLOSEメッセージに余分な要素がある
     #[error("CLOSEメッセージに余分な要素があります")]
     CloseExtraElements,
+
+    /// STATSメッセージに余分な要素がある
+    #[error("STATSメッセージに余分な要素があります")]
+    StatsExtraElements,
 }
 
 /// NIP-01 クライアントからリレーへのメッセージ
@@ -55,6 +59,9 @@ pub enum ClientMessage {
 
     /// 購読終了: ["CLOSE", <subscription_id>]
     Close(super::SubscriptionId),
+
+    /// 接続の統計情報の要求（NIP 標準外の拡張）: ["STATS"]
+    Stats,
 }
 
 impl Serialize for ClientMessage {
@@ -87,6 +94,11 @@ impl Serialize for ClientMessage {
                 seq.serialize_element(subscription_id)?;
                 seq.end()
             }
+            ClientMessage::Stats => {
+                let mut seq = serializer.serialize_seq(Some(1))?;
+                seq.serialize_element("STATS")?;
+                seq.end()
+            }
         }
     }
 }
@@ -173,6 +185,14 @@ impl<'de> Deserialize<'de> for ClientMessage {
 
                         Ok(ClientMessage::Close(subscription_id))
                     }
+                    "STATS" => {
+                        if seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+                            return Err(de::Error::custom(
+                                ClientMessageParseError::StatsExtraElements,
+                            ));
+                        }
+                        Ok(ClientMessage::Stats)
+                    }
                     _ => Err(de::Error::custom(
                         ClientMessageParseError::UnknownMessageType(message_type),
                     )),
@@ -411,6 +431,22 @@ mod tests {
         assert_eq!(original, restored);
     }
 
+    // ========== STATS ==========
+
+    #[test]
+    fn test_stats_roundtrip() {
+        let message: ClientMessage = serde_json::from_str(r#"["STATS"]"#).unwrap();
+        assert_eq!(message, ClientMessage::Stats);
+        assert_eq!(serde_json::to_string(&message).unwrap(), r#"["STATS"]"#);
+    }
+
+    #[test]
+    fn test_stats_extra_elements_error() {
+        let result: Result<ClientMessage, _> = serde_json::from_str(r#"["STATS", "extra"]"#);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("STATSメッセージに余分な要素があります"));
+    }
+
     // ========== 異常系テスト ==========
 
     #[test]
@@ -8,6 +8,15 @@ static SECP: LazyLock<Secp256k1<secp256k1::VerifyOnly>> =
     LazyLock::new(Secp256k1::verification_only);
 
 /// Nostrイベント（NIP-01準拠）
+///
+/// NIP-01 で定義されたフィールド以外（未知フィールド）はデシリアライズ時に破棄する。
+/// 未知フィールドはイベントIDの計算対象外で署名に守られておらず、
+/// 第三者が任意に付け足せるため、リレーとして保存・再配信しない方針とする。
+/// 保存（DynamoDB の event_json 含む）と配信はこの構造体を再シリアライズしたものを使う。
+///
+/// `content` は `String` なので常に正しい UTF-8 になる。対になっていないサロゲートの
+/// エスケープ（`\ud83d` 単体など）はデシリアライズ時点でパースエラーとなり、保存・配信されない。
+/// 改行やタブなどの制御文字は正当な本文で使われるため許容する（再シリアライズ時にエスケープされる）。
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Event {
     pub id: super::EventId,
@@ -72,6 +81,9 @@ impl std::ops::Deref for VerifiedEvent {
 impl Event {
     /// Addressable event 用の "d" タグ値を取得
     /// "d" タグが無い場合は空文字列を返す（NIP-01 仕様）
+    ///
+    /// "d" タグが複数ある異常なイベントでも、タグの並び順で先頭の "d" タグだけを使うので
+    /// アドレスは決定的に決まる。先頭が値を持たない `["d"]` の場合も、後続を見ずに空文字列とする。
     pub fn d_tag_value(&self) -> &str {
         self.tags
             .iter()
@@ -94,6 +106,18 @@ impl Event {
             .collect()
     }
 
+    /// 単一文字タグの (タグ名, 最初の値) を抽出（NIP-01 のタグフィルタの対象）
+    /// 同じタグ名のタグが複数あれば、それぞれの値を返す
+    pub fn single_letter_tags(&self) -> impl Iterator<Item = (char, &str)> {
+        self.tags.iter().filter_map(|tag| {
+            let mut name_chars = tag.name().chars();
+            match (name_chars.next(), name_chars.next(), tag.value()) {
+                (Some(c), None, Some(value)) if tag.name().len() == 1 => Some((c, value)),
+                _ => None,
+            }
+        })
+    }
+
     /// "a" タグの値を (kind, pubkey, d-identifier) として抽出
     /// フォーマット: "<kind>:<pubkey>:<d-identifier>"
     pub fn a_tag_values(&self) -> Vec<(&str, &str, &str)> {
@@ -112,9 +136,11 @@ impl Event {
             .collect()
     }
 
-    /// NIP-01準拠でイベントIDを計算（プライベート）
-    fn compute_id(&self) -> [u8; 32] {
-        // [0, pubkey, created_at, kind, tags, content] をシリアライズ
+    /// NIP-01 のID計算用シリアライズ `[0, pubkey, created_at, kind, tags, content]`
+    ///
+    /// 空白なしの UTF-8 JSON で、content 中の非ASCII文字はエスケープせずそのまま出力する。
+    /// 外部の Nostr ライブラリには依存せず、この形式はテストベクターで固定している。
+    fn serialize_for_id(&self) -> String {
         let serializable = serde_json::json!([
             0,
             self.pubkey.to_hex(),
@@ -123,11 +149,13 @@ impl Event {
             self.tags.iter().map(|t| t.as_slice()).collect::<Vec<_>>(),
             &self.content,
         ]);
+        serde_json::to_string(&serializable).expect("JSONシリアライズは常に成功する")
+    }
 
-        let json = serde_json::to_string(&serializable).expect("JSONシリアライズは常に成功する");
-
+    /// NIP-01準拠でイベントIDを計算（プライベート）
+    fn compute_id(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
-        hasher.update(json.as_bytes());
+        hasher.update(self.serialize_for_id().as_bytes());
         hasher.finalize().into()
     }
 
@@ -136,6 +164,9 @@ impl Event {
     /// 1. イベントIDが正しいか検証
     /// 2. Schnorr署名が正しいか検証
     ///
+    /// ID は pubkey を含めて計算し、署名は `pubkey` の鍵で検証する。
+    /// そのため pubkey を差し替えたイベントは ID 不一致か署名検証失敗のいずれかで弾かれる。
+    ///
     /// # エラー
     ///
     /// - `VerificationError::IdMismatch`: 計算されたIDとイベントのIDが一致しない
@@ -262,6 +293,95 @@ mod tests {
         ));
     }
 
+    /// 別の鍵（秘密鍵 = 1）の x-only 公開鍵
+    const OTHER_PUBKEY: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    /// JSON 上でフィールドを差し替えたイベントを作る（id と sig は元のまま）
+    fn with_field(event: &Event, field: &str, value: serde_json::Value) -> Event {
+        let mut json = serde_json::to_value(event).unwrap();
+        json[field] = value;
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_verify_pubkey_swapped_keeps_original_id() {
+        // pubkey だけ差し替えると id の再計算結果が変わる
+        let event = create_actually_valid_event();
+        let tampered = with_field(&event, "pubkey", serde_json::json!(OTHER_PUBKEY));
+        assert!(matches!(
+            tampered.verify(),
+            Err(VerificationError::IdMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_verify_pubkey_swapped_with_recomputed_id() {
+        // id を再計算しても、sig は元の鍵の署名なので差し替えた pubkey では検証できない
+        let event = create_actually_valid_event();
+        let mut tampered = with_field(&event, "pubkey", serde_json::json!(OTHER_PUBKEY));
+        tampered.id = super::super::EventId::from_bytes(tampered.compute_id());
+        assert!(matches!(
+            tampered.verify(),
+            Err(VerificationError::SignatureVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_compute_id_covers_all_signed_fields() {
+        // pubkey / created_at / kind / tags / content のどれを変えても id が変わる
+        let event = create_actually_valid_event();
+        let original_id = event.compute_id();
+        let cases = [
+            ("pubkey", serde_json::json!(OTHER_PUBKEY)),
+            ("created_at", serde_json::json!(1234567891)),
+            ("kind", serde_json::json!(2)),
+            ("tags", serde_json::json!([["t", "nostr"]])),
+            ("content", serde_json::json!("Hello, Nostr?")),
+        ];
+        for (field, value) in cases {
+            let tampered = with_field(&event, field, value);
+            assert_ne!(
+                tampered.compute_id(),
+                original_id,
+                "{field} が id に含まれていない"
+            );
+            assert!(
+                matches!(tampered.verify(), Err(VerificationError::IdMismatch { .. })),
+                "{field} の改ざんが検出されない"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compute_id_matches_known_vector() {
+        // 期待値は Python の json.dumps(separators=(",", ":"), ensure_ascii=False) と
+        // hashlib.sha256 で独立に計算したもの
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "id": "0".repeat(64),
+            "pubkey": OTHER_PUBKEY,
+            "created_at": 1700000000,
+            "kind": 1,
+            "tags": [["e", "abc"], ["p", OTHER_PUBKEY, "wss://relay.example"]],
+            "content": "hello\n\"nostr\"\\ 日本語 🎉\t",
+            "sig": "0".repeat(128),
+        }))
+        .unwrap();
+
+        assert_eq!(
+            event.serialize_for_id(),
+            concat!(
+                r#"[0,"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","#,
+                r#"1700000000,1,[["e","abc"],["p","#,
+                r#""79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798","#,
+                r#""wss://relay.example"]],"hello\n\"nostr\"\\ 日本語 🎉\t"]"#,
+            )
+        );
+        assert_eq!(
+            hex::encode(event.compute_id()),
+            "800f449ae6601d6c8cccfee591f553563034f1b6d778d37d32e191f9e2c02a9d"
+        );
+    }
+
     #[test]
     fn test_verify_special_characters_in_content() {
         use secp256k1::{Keypair, SecretKey};
@@ -389,6 +509,40 @@ mod tests {
         serde_json::from_value(event_json).unwrap()
     }
 
+    #[test]
+    fn test_single_letter_tags_returns_every_value() {
+        let event = create_valid_event_with_tags(
+            vec![
+                vec!["e", "first"],
+                vec!["e", "second", "wss://relay.example.com"],
+                vec!["e"],
+                vec!["client", "app"],
+                vec!["p", "pubkey"],
+            ],
+            "reply",
+        );
+        let tags: Vec<(char, &str)> = event.single_letter_tags().collect();
+        assert_eq!(tags, vec![('e', "first"), ('e', "second"), ('p', "pubkey")]);
+    }
+
+    #[test]
+    fn test_d_tag_value_uses_first_d_tag() {
+        let event = create_valid_event_with_tags(
+            vec![vec!["t", "x"], vec!["d", "first"], vec!["d", "second"]],
+            "",
+        );
+        assert_eq!(event.d_tag_value(), "first");
+
+        // 先頭の d タグが値を持たなければ、後続の d タグがあっても空文字列
+        let event = create_valid_event_with_tags(vec![vec!["d"], vec!["d", "second"]], "");
+        assert_eq!(event.d_tag_value(), "");
+
+        let event = create_valid_event_with_tags(vec![vec!["d", ""]], "");
+        assert_eq!(event.d_tag_value(), "");
+        let event = create_valid_event_with_tags(vec![], "");
+        assert_eq!(event.d_tag_value(), "");
+    }
+
     #[test]
     fn test_is_protected_with_dash_tag() {
         let event = create_valid_event_with_tags(vec![vec!["-"]], "protected event");
@@ -451,4 +605,119 @@ mod tests {
         let inner = verified.into_inner();
         assert_eq!(inner.content, original_content);
     }
+
+    // ========== 未知フィールドの扱い ==========
+
+    /// 未知フィールドを付け足したイベントJSON
+    fn event_json_with_unknown_fields() -> serde_json::Value {
+        let mut json = serde_json::to_value(create_actually_valid_event()).unwrap();
+        let obj = json.as_object_mut().unwrap();
+        obj.insert("future_field".to_string(), serde_json::json!("value"));
+        obj.insert("nested".to_string(), serde_json::json!({"a": [1, 2]}));
+        json
+    }
+
+    #[test]
+    fn test_unknown_fields_are_accepted_on_deserialize() {
+        // 未知フィールドがあってもパースは成功する
+        let event: Event = serde_json::from_value(event_json_with_unknown_fields()).unwrap();
+        assert_eq!(event, create_actually_valid_event());
+    }
+
+    #[test]
+    fn test_unknown_fields_do_not_affect_verification() {
+        // 未知フィールドはID計算の対象外なので検証に影響しない
+        let event: Event = serde_json::from_value(event_json_with_unknown_fields()).unwrap();
+        assert!(event.verify().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_dropped_on_roundtrip() {
+        // 再シリアライズ（保存・配信に使う形）では未知フィールドが破棄される
+        let event: Event = serde_json::from_value(event_json_with_unknown_fields()).unwrap();
+        let json = serde_json::to_value(&event).unwrap();
+        let obj = json.as_object().unwrap();
+
+        assert!(!obj.contains_key("future_field"));
+        assert!(!obj.contains_key("nested"));
+        let mut keys: Vec<&str> = obj.keys().map(|k| k.as_str()).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            vec![
+                "content",
+                "created_at",
+                "id",
+                "kind",
+                "pubkey",
+                "sig",
+                "tags"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_roundtrip_keeps_tag_order_and_id() {
+        // 保存（event_json）・配信で再シリアライズしてもタグの順序は変わらず、id も一致する
+        let event = crate::test_helpers::create_custom_event(
+            1,
+            1000,
+            "",
+            vec![
+                vec!["p", "zzz"],
+                vec!["e", "bbb", "wss://relay.example.com"],
+                vec!["e", "aaa"],
+                vec!["p", "zzz"],
+            ],
+        );
+        let json = serde_json::to_string(&event).unwrap();
+        let restored: Event = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.tags, event.tags);
+        assert_eq!(restored.compute_id(), *event.id.as_bytes());
+        assert_eq!(restored.serialize_for_id(), event.serialize_for_id());
+    }
+
+    // ========== content の Unicode ==========
+
+    #[test]
+    fn test_lone_surrogate_in_content_is_rejected_on_deserialize() {
+        let event = crate::test_helpers::create_custom_event(1, 1000, "PLACEHOLDER", vec![]);
+        let json = serde_json::to_string(&event).unwrap();
+        for escaped in ["\\ud83d", "\\ude00", "\\ude00\\ud83d"] {
+            let malformed = json.replace("PLACEHOLDER", escaped);
+            assert!(
+                serde_json::from_str::<Event>(&malformed).is_err(),
+                "{escaped} はパースエラーになるべき"
+            );
+        }
+    }
+
+    #[test]
+    fn test_surrogate_pair_escape_in_content_is_decoded() {
+        // サロゲートペアのエスケープは1文字に復号され、生の UTF-8 と同じイベントになる
+        let event = crate::test_helpers::create_custom_event(1, 1000, "smile 😀", vec![]);
+        let json = serde_json::to_string(&event)
+            .unwrap()
+            .replace("😀", "\\ud83d\\ude00");
+
+        let parsed: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, event);
+        assert!(parsed.verify().is_ok());
+    }
+
+    #[test]
+    fn test_unicode_and_control_characters_in_content_are_accepted() {
+        for content in [
+            "絵文字👍🏽と漢字",
+            "line1\nline2\ttab",
+            "nul\u{0}esc\u{1b}del\u{7f}",
+        ] {
+            let event = crate::test_helpers::create_custom_event(1, 1000, content, vec![]);
+            let json = serde_json::to_string(&event).unwrap();
+            let parsed: Event = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.content, content);
+            assert!(parsed.verify().is_ok(), "{content:?} は検証に通るべき");
+        }
+    }
 }
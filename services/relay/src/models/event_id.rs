@@ -2,12 +2,16 @@ use std::fmt;
 use std::str::FromStr;
 
 /// Nostrイベントの一意識別子（32バイト）
+///
+/// 順序はバイト列の辞書順で、lowercase hex 文字列の辞書順と一致する。
 #[derive(
     Debug,
     Clone,
     Copy,
     PartialEq,
     Eq,
+    PartialOrd,
+    Ord,
     Hash,
     serde_with::SerializeDisplay,
     serde_with::DeserializeFromStr,
@@ -36,7 +40,9 @@ impl fmt::Display for EventId {
 impl FromStr for EventId {
     type Err = EventIdParseError;
 
-    /// lowercase hex-encoded 文字列からパース
+    /// hex-encoded 文字列からパース
+    ///
+    /// 大文字の hex も受け付ける。バイト列として保持するので、表示は常に小文字になる。
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let bytes = hex::decode(s)?;
         let len = bytes.len();
@@ -54,7 +60,6 @@ impl EventId {
     }
 
     /// バイト配列からEventIdを生成
-    #[allow(dead_code)]
     pub fn from_bytes(bytes: [u8; 32]) -> Self {
         EventId(bytes)
     }
@@ -171,4 +176,21 @@ mod tests {
         let result: Result<EventId, _> = "abcd1234".parse();
         assert!(matches!(result, Err(EventIdParseError::InvalidLength(4))));
     }
+
+    #[test]
+    fn test_event_id_ord_matches_hex_order() {
+        // バイト順序とhex文字列の辞書順が一致する
+        let ids = [
+            EventId([0x00; 32]),
+            EventId([0x0f; 32]),
+            EventId([0x10; 32]),
+            EventId([0xa0; 32]),
+            EventId([0xff; 32]),
+        ];
+        for a in &ids {
+            for b in &ids {
+                assert_eq!(a.cmp(b), a.to_string().cmp(&b.to_string()));
+            }
+        }
+    }
 }
@@ -45,6 +45,23 @@ impl TagFilters {
     pub fn iter(&self) -> impl Iterator<Item = (&char, &Vec<String>)> {
         self.0.iter()
     }
+
+    /// 各タグの値をソート・重複排除したTagFiltersを返す
+    fn normalized(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|(name, values)| (*name, sorted_dedup(values.clone())))
+                .collect(),
+        )
+    }
+}
+
+/// ソートして重複を取り除く
+fn sorted_dedup<T: Ord>(mut values: Vec<T>) -> Vec<T> {
+    values.sort_unstable();
+    values.dedup();
+    values
 }
 
 impl Serialize for TagFilters {
@@ -110,6 +127,32 @@ impl<'de> Deserialize<'de> for TagFilters {
     }
 }
 
+/// フィルタ条件のうちイベントが満たさなかったもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMismatch {
+    Ids,
+    Authors,
+    Kinds,
+    /// タグフィルタ（タグ名）
+    Tag(char),
+    Since,
+    Until,
+}
+
+/// `Filter::explain_match` の結果（デバッグ・テスト用）
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MatchExplanation {
+    /// 満たさなかった条件（ids, authors, kinds, タグ名順, since, until の順）
+    pub mismatches: Vec<FilterMismatch>,
+}
+
+impl MatchExplanation {
+    /// 全条件を満たしたか
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
 /// NIP-01 で定義されたフィルタ
 /// イベントの購読やクエリに使用する
 #[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -131,6 +174,8 @@ pub struct Filter {
 
     /// タグフィルタ（#e, #p など）
     /// キーはタグ名（"e", "p" など、#は含まない）
+    /// 空文字の値も通常の値として扱い、値が空文字のタグ（`["d", ""]` など）にだけ完全一致する。
+    /// タグを持たないイベントや、他の値のタグにはマッチしない
     #[serde(flatten, default)]
     pub tags: TagFilters,
 
@@ -148,6 +193,50 @@ pub struct Filter {
 }
 
 impl Filter {
+    /// 正規化したフィルタを返す
+    ///
+    /// ids / authors / kinds / タグ値をソート・重複排除する。マッチ結果は変わらないため、
+    /// 正規化後のフィルタ同士を `==` で比較すれば実質的に同じ条件かどうかを判定できる。
+    /// 空リスト（何もマッチしない）と未指定（条件なし）は区別したまま保持する。
+    pub fn normalized(&self) -> Self {
+        Self {
+            ids: self.ids.clone().map(sorted_dedup),
+            authors: self.authors.clone().map(sorted_dedup),
+            kinds: self.kinds.clone().map(sorted_dedup),
+            tags: self.tags.normalized(),
+            since: self.since,
+            until: self.until,
+            limit: self.limit,
+        }
+    }
+
+    /// 絞り込み条件を何も持たないか（limit 以外が未指定で、全イベントにマッチする）
+    pub fn is_unconditional(&self) -> bool {
+        self.ids.is_none()
+            && self.authors.is_none()
+            && self.kinds.is_none()
+            && self.tags.is_empty()
+            && self.since.is_none()
+            && self.until.is_none()
+    }
+
+    /// クエリで返す最大件数を usize で返す
+    ///
+    /// limit は全経路で u64 のまま扱い、件数として使う箇所ではこのヘルパーで変換する。
+    /// 未指定は上限なし（`usize::MAX`）、usize に収まらない値も `usize::MAX` に飽和させるため、
+    /// 32bit 環境でも切り捨てで小さな limit に化けることはない。
+    pub fn result_limit(&self) -> usize {
+        self.limit
+            .map_or(usize::MAX, |l| usize::try_from(l).unwrap_or(usize::MAX))
+    }
+
+    /// 2つのフィルタ列が実質的に同じ条件か（フィルタの順序・重複は問わない）
+    pub fn equivalent_sets(a: &[Filter], b: &[Filter]) -> bool {
+        let a: Vec<Filter> = a.iter().map(Filter::normalized).collect();
+        let b: Vec<Filter> = b.iter().map(Filter::normalized).collect();
+        a.iter().all(|f| b.contains(f)) && b.iter().all(|f| a.contains(f))
+    }
+
     /// イベントがこのフィルタにマッチするかを判定
     pub fn matches(&self, event: &super::Event) -> bool {
         self.matches_ids(event)
@@ -158,6 +247,37 @@ impl Filter {
             && self.matches_until(event)
     }
 
+    /// イベントがどの条件で不一致になったかを返す（デバッグ・テスト用）
+    ///
+    /// `matches` と異なり最初の不一致で打ち切らず、全条件を評価する。
+    pub fn explain_match(&self, event: &super::Event) -> MatchExplanation {
+        let mut mismatches = Vec::new();
+        if !self.matches_ids(event) {
+            mismatches.push(FilterMismatch::Ids);
+        }
+        if !self.matches_authors(event) {
+            mismatches.push(FilterMismatch::Authors);
+        }
+        if !self.matches_kinds(event) {
+            mismatches.push(FilterMismatch::Kinds);
+        }
+        let mut tag_names: Vec<char> = self
+            .tags
+            .iter()
+            .filter(|(name, values)| !Self::matches_tag(**name, values, event))
+            .map(|(name, _)| *name)
+            .collect();
+        tag_names.sort_unstable();
+        mismatches.extend(tag_names.into_iter().map(FilterMismatch::Tag));
+        if !self.matches_since(event) {
+            mismatches.push(FilterMismatch::Since);
+        }
+        if !self.matches_until(event) {
+            mismatches.push(FilterMismatch::Until);
+        }
+        MatchExplanation { mismatches }
+    }
+
     /// IDフィルタのマッチング
     fn matches_ids(&self, event: &super::Event) -> bool {
         match &self.ids {
@@ -185,36 +305,36 @@ impl Filter {
         }
     }
 
-    /// タグフィルタのマッチング
-    /// イベントのタグの最初の値（tags[1]）とフィルタの値を完全一致で比較
+    /// タグフィルタのマッチング（全タグ名の条件を満たすこと）
     fn matches_tags(&self, event: &super::Event) -> bool {
-        for (tag_name, filter_values) in self.tags.iter() {
-            // 空のフィルタ値リストは何もマッチしない
-            if filter_values.is_empty() {
-                return false;
-            }
+        self.tags
+            .iter()
+            .all(|(tag_name, filter_values)| Self::matches_tag(*tag_name, filter_values, event))
+    }
 
-            // イベントのタグから該当するタグ名のものを探す
-            let has_match = event.tags.iter().any(|tag| {
-                let tag_slice = tag.as_slice();
-                // タグ名が一致し、かつ値が存在する場合
-                if tag_slice.len() >= 2 {
-                    let event_tag_name = &tag_slice[0];
-                    let event_tag_value = &tag_slice[1];
-                    // タグ名が一致（単一文字）
-                    if event_tag_name.len() == 1 && event_tag_name.starts_with(*tag_name) {
-                        // フィルタ値のいずれかと完全一致
-                        return filter_values.iter().any(|v| v == event_tag_value);
-                    }
-                }
-                false
-            });
+    /// 1つのタグ名についてのマッチング
+    /// イベントのタグの最初の値（tags[1]）とフィルタの値を完全一致で比較
+    fn matches_tag(tag_name: char, filter_values: &[String], event: &super::Event) -> bool {
+        // 空のフィルタ値リストは何もマッチしない
+        if filter_values.is_empty() {
+            return false;
+        }
 
-            if !has_match {
-                return false;
+        // イベントのタグから該当するタグ名のものを探す
+        event.tags.iter().any(|tag| {
+            let tag_slice = tag.as_slice();
+            // タグ名が一致し、かつ値が存在する場合
+            if tag_slice.len() >= 2 {
+                let event_tag_name = &tag_slice[0];
+                let event_tag_value = &tag_slice[1];
+                // タグ名が一致（単一文字）
+                if event_tag_name.len() == 1 && event_tag_name.starts_with(tag_name) {
+                    // フィルタ値のいずれかと完全一致
+                    return filter_values.iter().any(|v| v == event_tag_value);
+                }
             }
-        }
-        true
+            false
+        })
     }
 
     /// sinceフィルタのマッチング（created_at >= since）
@@ -466,6 +586,59 @@ mod tests {
         assert!(filter.matches(&event));
     }
 
+    #[test]
+    fn test_authors_and_p_tag_are_independent_conditions() {
+        use crate::test_helpers::create_custom_event_with_keypair;
+
+        let (x, y) = ([0x02; 32], [0x03; 32]);
+        let pubkey_of = |secret| create_custom_event_with_keypair(1, 0, "", vec![], secret).pubkey;
+        let (x_hex, y_hex) = (pubkey_of(x).to_hex(), pubkey_of(y).to_hex());
+        let mention = |author, target: &str| {
+            create_custom_event_with_keypair(1, 1000, "", vec![vec!["p", target]], author)
+        };
+
+        let x_mentions_y = mention(x, &y_hex);
+        let x_mentions_x = mention(x, &x_hex);
+        let y_mentions_x = mention(y, &x_hex);
+        let y_mentions_y = mention(y, &y_hex);
+        let x_no_mention = create_custom_event_with_keypair(1, 1000, "", vec![], x);
+
+        // authors は event.pubkey、#p は p タグ値に対して AND で評価される
+        let filter: Filter = serde_json::from_value(serde_json::json!({
+            "authors": [x_hex],
+            "#p": [y_hex],
+        }))
+        .unwrap();
+        assert!(filter.matches(&x_mentions_y));
+        for event in [&x_mentions_x, &y_mentions_x, &y_mentions_y, &x_no_mention] {
+            assert!(!filter.matches(event), "{event:?}");
+        }
+
+        // #p だけなら作成者は問わず、p タグ値だけを見る
+        let filter: Filter = serde_json::from_value(serde_json::json!({"#p": [x_hex]})).unwrap();
+        assert!(filter.matches(&x_mentions_x));
+        assert!(filter.matches(&y_mentions_x));
+        assert!(!filter.matches(&x_mentions_y));
+        assert!(!filter.matches(&x_no_mention));
+    }
+
+    #[test]
+    fn test_empty_string_tag_value_matches_only_empty_value_tags() {
+        use crate::test_helpers::create_custom_event;
+
+        let filter: Filter = serde_json::from_str(r##"{"#d": [""]}"##).unwrap();
+        assert!(filter.matches(&create_custom_event(30000, 1000, "", vec![vec!["d", ""]])));
+        // 値の異なるタグ・タグなし・値のないタグにはマッチしない（全マッチにならない）
+        assert!(!filter.matches(&create_custom_event(30000, 1000, "", vec![vec!["d", "a"]])));
+        assert!(!filter.matches(&create_custom_event(30000, 1000, "", vec![])));
+        assert!(!filter.matches(&create_custom_event(30000, 1000, "", vec![vec!["d"]])));
+
+        // 他の値と混ざっていても OR の1候補として扱う
+        let filter: Filter = serde_json::from_str(r##"{"#d": ["", "a"]}"##).unwrap();
+        assert!(filter.matches(&create_custom_event(30000, 1000, "", vec![vec!["d", "a"]])));
+        assert!(!filter.matches(&create_custom_event(30000, 1000, "", vec![vec!["d", "b"]])));
+    }
+
     #[test]
     fn test_kinds_filter_match() {
         let event = create_test_event();
@@ -681,4 +854,397 @@ mod tests {
         let event = create_test_event();
         assert!(filter.matches(&event));
     }
+
+    #[test]
+    fn test_result_limit_clamps_huge_values() {
+        let parse = |json: &str| serde_json::from_str::<Filter>(json).unwrap();
+        assert_eq!(parse(r#"{}"#).result_limit(), usize::MAX);
+        assert_eq!(parse(r#"{"limit": 0}"#).result_limit(), 0);
+        assert_eq!(parse(r#"{"limit": 500}"#).result_limit(), 500);
+        // u64 の最大値も切り捨てずに上限なしとして扱う
+        assert_eq!(
+            parse(r#"{"limit": 18446744073709551615}"#).result_limit(),
+            usize::MAX
+        );
+        // u64 に収まらない limit はパースエラー
+        assert!(serde_json::from_str::<Filter>(r#"{"limit": 18446744073709551616}"#).is_err());
+    }
+
+    // ========== 正規化テスト ==========
+
+    fn parse(json: &str) -> Filter {
+        serde_json::from_str(json).unwrap()
+    }
+
+    const PUBKEY_A: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const PUBKEY_B: &str = "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+
+    #[test]
+    fn test_normalized_ignores_order_and_duplicates() {
+        let a = parse(&format!(
+            r#"{{"kinds": [1, 1], "authors": ["{PUBKEY_A}"]}}"#
+        ));
+        let b = parse(&format!(r#"{{"authors": ["{PUBKEY_A}"], "kinds": [1]}}"#));
+        assert_ne!(a, b);
+        assert_eq!(a.normalized(), b.normalized());
+    }
+
+    #[test]
+    fn test_normalized_shrinks_duplicated_values_without_changing_matches() {
+        use crate::test_helpers::create_custom_event;
+
+        let ids = vec![PUBKEY_A; 500];
+        let filter: Filter = serde_json::from_value(serde_json::json!({
+            "kinds": vec![1; 1000],
+            "authors": vec![PUBKEY_A; 500],
+            "#e": ids,
+        }))
+        .unwrap();
+        let normalized = filter.normalized();
+        assert_eq!(normalized.kinds.as_ref().unwrap().len(), 1);
+        assert_eq!(normalized.authors.as_ref().unwrap().len(), 1);
+        assert_eq!(normalized.tags.get('e').unwrap().len(), 1);
+
+        for event in [
+            create_custom_event(1, 1000, "", vec![vec!["e", PUBKEY_A]]),
+            create_custom_event(1, 1000, "", vec![]),
+            create_custom_event(7, 1000, "", vec![vec!["e", PUBKEY_A]]),
+        ] {
+            assert_eq!(filter.matches(&event), normalized.matches(&event));
+        }
+    }
+
+    #[test]
+    fn test_normalized_sorts_values() {
+        let filter = parse(&format!(
+            r##"{{"kinds": [7, 1, 7], "authors": ["{PUBKEY_B}", "{PUBKEY_A}"], "#t": ["b", "a", "b"]}}"##
+        ));
+        let normalized = filter.normalized();
+        let kinds: Vec<u16> = normalized
+            .kinds
+            .unwrap()
+            .iter()
+            .map(|k| k.as_u16())
+            .collect();
+        assert_eq!(kinds, vec![1, 7]);
+        assert_eq!(normalized.authors.unwrap().len(), 2);
+        assert_eq!(
+            normalized.tags.get('t'),
+            Some(&vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_deserialize_empty_list_distinct_from_missing_key() {
+        // 明示的な空配列は Some(空)、キー自体が無ければ None としてパースされる
+        let empty = parse(r##"{"ids": [], "authors": [], "kinds": [], "#t": []}"##);
+        assert_eq!(empty.ids, Some(vec![]));
+        assert_eq!(empty.authors, Some(vec![]));
+        assert_eq!(empty.kinds, Some(vec![]));
+        assert_eq!(empty.tags.get('t'), Some(&vec![]));
+
+        let missing = parse("{}");
+        assert_eq!(missing.ids, None);
+        assert_eq!(missing.authors, None);
+        assert_eq!(missing.kinds, None);
+        assert_eq!(missing.tags.get('t'), None);
+    }
+
+    #[test]
+    fn test_explicit_empty_lists_match_none() {
+        // kinds/ids/authors/タグ値のいずれかが空配列なら、他の条件によらず何もマッチしない
+        let event =
+            crate::test_helpers::create_custom_event(1, 1000, "tagged", vec![vec!["t", "nostr"]]);
+        for json in [
+            r#"{"ids": []}"#,
+            r#"{"authors": []}"#,
+            r#"{"kinds": []}"#,
+            r##"{"#t": []}"##,
+            r##"{"kinds": [], "#t": ["nostr"]}"##,
+        ] {
+            assert!(!parse(json).matches(&event), "{json} はマッチしないべき");
+        }
+        // キーが無い場合は制約なし
+        assert!(parse("{}").matches(&event));
+        assert!(parse(r##"{"#t": ["nostr"]}"##).matches(&event));
+    }
+
+    #[test]
+    fn test_normalized_keeps_empty_list_distinct_from_none() {
+        let empty = parse(r#"{"kinds": []}"#);
+        let none = parse("{}");
+        assert_ne!(empty.normalized(), none.normalized());
+    }
+
+    #[test]
+    fn test_normalized_distinguishes_other_fields() {
+        let a = parse(r#"{"kinds": [1], "limit": 10}"#);
+        let b = parse(r#"{"kinds": [1], "limit": 20}"#);
+        assert_ne!(a.normalized(), b.normalized());
+    }
+
+    #[test]
+    fn test_normalized_preserves_matching() {
+        let event = create_test_event();
+        let filter = parse(r##"{"kinds": [1, 1, 7], "#e": ["x", "event123", "x"]}"##);
+        assert_eq!(filter.matches(&event), filter.normalized().matches(&event));
+    }
+
+    #[test]
+    fn test_is_unconditional_ignores_limit_only() {
+        assert!(parse("{}").is_unconditional());
+        assert!(parse(r#"{"limit": 10}"#).is_unconditional());
+        // 空リストは「何もマッチしない」条件なので、条件なしではない
+        assert!(!parse(r#"{"kinds": []}"#).is_unconditional());
+        assert!(!parse(r##"{"#t": ["nostr"]}"##).is_unconditional());
+        assert!(!parse(r#"{"since": 0}"#).is_unconditional());
+    }
+
+    #[test]
+    fn test_equivalent_sets_ignores_filter_order() {
+        let a = vec![parse(r#"{"kinds": [1]}"#), parse(r#"{"kinds": [7, 6]}"#)];
+        let b = vec![parse(r#"{"kinds": [6, 7]}"#), parse(r#"{"kinds": [1, 1]}"#)];
+        assert!(Filter::equivalent_sets(&a, &b));
+
+        let c = vec![parse(r#"{"kinds": [1]}"#)];
+        assert!(!Filter::equivalent_sets(&a, &c));
+    }
+
+    // ========== ids / authors の hex 妥当性 ==========
+
+    #[test]
+    fn test_ids_and_authors_reject_invalid_hex() {
+        // NIP-01 は完全一致のみなので prefix（64文字未満）も不正として扱う
+        let invalid_values = [
+            "",
+            "abc",
+            "abcd",
+            &PUBKEY_A[..63],
+            &format!("{}zz", &PUBKEY_A[..62]),
+            &format!(" {}", &PUBKEY_A[1..]),
+            &format!("{} ", &PUBKEY_A[..63]),
+        ];
+        for value in invalid_values {
+            for field in ["ids", "authors"] {
+                let json = format!(r#"{{"{field}": ["{value}"]}}"#);
+                assert!(
+                    serde_json::from_str::<Filter>(&json).is_err(),
+                    "{field} に {value:?} が通ってしまった"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ids_and_authors_accept_full_hex() {
+        let filter = parse(&format!(
+            r#"{{"ids": ["{PUBKEY_A}"], "authors": ["{PUBKEY_A}", "{PUBKEY_B}"]}}"#
+        ));
+        assert_eq!(filter.ids.unwrap().len(), 1);
+        assert_eq!(filter.authors.unwrap().len(), 2);
+    }
+
+    // ========== explain_match ==========
+
+    #[test]
+    fn test_explain_match_all_pass() {
+        let event =
+            crate::test_helpers::create_custom_event(1, 1000, "note", vec![vec!["e", "event123"]]);
+        let filter = parse(r##"{"kinds": [1], "#e": ["event123"]}"##);
+        let explanation = filter.explain_match(&event);
+        assert!(explanation.is_match());
+        assert!(explanation.mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_explain_match_reports_every_mismatch() {
+        let event = create_test_event();
+        let created_at = event.created_at.as_i64();
+        let filter = parse(&format!(
+            r##"{{"authors": ["{PUBKEY_B}"], "kinds": [7], "#t": ["x"], "#e": ["other"], "since": {}}}"##,
+            created_at + 1
+        ));
+        let explanation = filter.explain_match(&event);
+        assert!(!explanation.is_match());
+        assert_eq!(
+            explanation.mismatches,
+            vec![
+                FilterMismatch::Authors,
+                FilterMismatch::Kinds,
+                FilterMismatch::Tag('e'),
+                FilterMismatch::Tag('t'),
+                FilterMismatch::Since,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_match_consistent_with_matches() {
+        let event = create_test_event();
+        let created_at = event.created_at.as_i64();
+        let filters = [
+            "{}".to_string(),
+            r#"{"ids": []}"#.to_string(),
+            r#"{"kinds": [1, 2]}"#.to_string(),
+            r##"{"#e": []}"##.to_string(),
+            format!(r#"{{"until": {}}}"#, created_at - 1),
+            format!(r#"{{"since": {created_at}, "until": {created_at}}}"#),
+        ];
+        for json in filters {
+            let filter = parse(&json);
+            assert_eq!(
+                filter.explain_match(&event).is_match(),
+                filter.matches(&event),
+                "{json}"
+            );
+        }
+    }
+
+    // ========== 不正・極端な入力への堅牢性 ==========
+
+    /// パースできた場合はマッチ判定・正規化まで通し、パニックしないことを確認する
+    fn parse_and_match_without_panic(json: &str, event: &super::super::Event) {
+        if let Ok(filters) = serde_json::from_str::<Vec<Filter>>(json) {
+            for filter in &filters {
+                let normalized = filter.normalized();
+                assert_eq!(normalized.matches(event), filter.matches(event), "{json}");
+                assert_eq!(
+                    filter.explain_match(event).is_match(),
+                    filter.matches(event),
+                    "{json}"
+                );
+                let _ = filter.result_limit();
+            }
+            assert!(Filter::equivalent_sets(&filters, &filters), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_extreme_inputs_do_not_panic() {
+        let huge_kinds = format!(r#"[{{"kinds": [{}]}}]"#, vec!["1"; 10_000].join(","));
+        let huge_tag_values = format!(r##"[{{"#e": [{}]}}]"##, vec![r#""x""#; 10_000].join(","));
+        let huge_filters = format!("[{}]", vec!["{}"; 1_000].join(","));
+        let deep_nesting = format!(
+            r#"[{{"kinds": {}1{}}}]"#,
+            "[".repeat(10_000),
+            "]".repeat(10_000)
+        );
+        let long_string = format!(r##"[{{"#e": ["{}"]}}]"##, "a".repeat(100_000));
+        let inputs = [
+            huge_kinds.as_str(),
+            huge_tag_values.as_str(),
+            huge_filters.as_str(),
+            deep_nesting.as_str(),
+            long_string.as_str(),
+            r#"[{"since": 9223372036854775807, "until": -9223372036854775808}]"#,
+            r#"[{"since": 9223372036854775808}]"#,
+            r#"[{"until": 1e308}]"#,
+            r#"[{"limit": 18446744073709551615}]"#,
+            r#"[{"limit": 18446744073709551616}]"#,
+            r#"[{"limit": -1}]"#,
+            r#"[{"kinds": [65535, 65536, -1, 4294967296]}]"#,
+            r#"[{"kinds": [1.5]}]"#,
+            r#"[{"ids": [null]}]"#,
+            r##"[{"#": ["x"]}]"##,
+            r##"[{"#ee": ["x"]}]"##,
+            r##"[{"#あ": ["x"]}]"##,
+            r##"[{"#e": "x"}]"##,
+            r##"[{"#e": [1, {}, []]}]"##,
+            r##"[{"#e": ["\ud800"]}]"##,
+            r##"[{"#e": ["\u0000"]}]"##,
+            r#"[{"kinds": [1], "kinds": [2]}]"#,
+            r#"[{"unknown": {"nested": [[[]]]}}]"#,
+            r#"[null]"#,
+            r#"[[]]"#,
+            "[{",
+            "",
+        ];
+        let event = create_test_event();
+        for json in inputs {
+            parse_and_match_without_panic(json, &event);
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8_input_does_not_panic() {
+        let bytes: &[u8] = b"[{\"#e\": [\"\xff\xfe\"], \"kinds\": [1]}]";
+        assert!(serde_json::from_slice::<Vec<Filter>>(bytes).is_err());
+    }
+
+    /// 既知の断片をランダムに組み合わせた JSON を大量に流す（乱数は固定シードで再現可能）
+    #[test]
+    fn test_random_filter_json_does_not_panic() {
+        const FRAGMENTS: &[&str] = &[
+            "{",
+            "}",
+            "[",
+            "]",
+            ",",
+            ":",
+            "null",
+            "true",
+            "0",
+            "-1",
+            "1e400",
+            "18446744073709551616",
+            "-9223372036854775809",
+            "0.5",
+            r#""""#,
+            r#""x""#,
+            r#""ids""#,
+            r#""authors""#,
+            r#""kinds""#,
+            r#""since""#,
+            r#""until""#,
+            r#""limit""#,
+            r##""#e""##,
+            r##""#""##,
+            r##""#ab""##,
+            r#""\ud800""#,
+            r#""\u0000""#,
+        ];
+        const VALID_SHAPES: &[&str] = &[
+            r#"{"kinds": [$]}"#,
+            r#"{"since": $, "until": $}"#,
+            r#"{"limit": $}"#,
+            r##"{"#e": [$]}"##,
+            r#"{"ids": [$]}"#,
+        ];
+
+        let event = create_test_event();
+        // xorshift64: 依存を増やさずに再現性のある擬似乱数を得る
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let mut json = String::from("[");
+            if next() % 2 == 0 {
+                // 構造は正しく値だけがデタラメなフィルタ
+                let shape = VALID_SHAPES[next() as usize % VALID_SHAPES.len()];
+                let mut filter = String::new();
+                for part in shape.split_inclusive('$') {
+                    match part.strip_suffix('$') {
+                        Some(prefix) => {
+                            filter.push_str(prefix);
+                            filter.push_str(FRAGMENTS[next() as usize % FRAGMENTS.len()]);
+                        }
+                        None => filter.push_str(part),
+                    }
+                }
+                json.push_str(&filter);
+            } else {
+                // 断片を無作為に並べただけの JSON
+                for _ in 0..next() % 32 {
+                    json.push_str(FRAGMENTS[next() as usize % FRAGMENTS.len()]);
+                }
+            }
+            json.push(']');
+            parse_and_match_without_panic(&json, &event);
+        }
+    }
 }
@@ -1,7 +1,22 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 #[serde(transparent)]
 pub struct Kind(u16);
 
+/// kind 範囲による保存方法の分類（NIP-01）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KindClass {
+    /// 通常のイベント（保存・配信される）
+    Regular,
+    /// 同一 pubkey + kind で最新のみ保持
+    Replaceable,
+    /// 保存せず配信のみ
+    Ephemeral,
+    /// 同一 pubkey + kind + d タグで最新のみ保持
+    Addressable,
+}
+
 impl Kind {
     /// 内部のu16値を返す
     pub fn as_u16(&self) -> u16 {
@@ -39,6 +54,22 @@ impl Kind {
     pub fn is_addressable(&self) -> bool {
         (30000..40000).contains(&self.0)
     }
+
+    /// 保存方法の分類を返す
+    ///
+    /// NIP-01 で範囲が定義されていない kind（45-999, 40000 以上）は Regular として扱う。
+    /// 将来の NIP で定義される kind を拒否・破棄するより、通常イベントとして保存する方が安全なため。
+    pub fn classify(&self) -> KindClass {
+        if self.is_replaceable() {
+            KindClass::Replaceable
+        } else if self.is_ephemeral() {
+            KindClass::Ephemeral
+        } else if self.is_addressable() {
+            KindClass::Addressable
+        } else {
+            KindClass::Regular
+        }
+    }
 }
 
 #[cfg(test)]
@@ -99,4 +130,56 @@ mod tests {
         assert!(!Kind(29999).is_addressable());
         assert!(!Kind(40000).is_addressable());
     }
+
+    #[test]
+    fn test_classify_boundaries() {
+        let cases = [
+            (0, KindClass::Replaceable),
+            (1, KindClass::Regular),
+            (2, KindClass::Regular),
+            (3, KindClass::Replaceable),
+            (4, KindClass::Regular),
+            (44, KindClass::Regular),
+            (45, KindClass::Regular),  // 未定義
+            (999, KindClass::Regular), // 未定義
+            (1000, KindClass::Regular),
+            (9999, KindClass::Regular),
+            (10000, KindClass::Replaceable),
+            (19999, KindClass::Replaceable),
+            (20000, KindClass::Ephemeral),
+            (29999, KindClass::Ephemeral),
+            (30000, KindClass::Addressable),
+            (39999, KindClass::Addressable),
+            (40000, KindClass::Regular),    // 未定義
+            (u16::MAX, KindClass::Regular), // 未定義
+        ];
+        for (k, expected) in cases {
+            assert_eq!(Kind(k).classify(), expected, "kind {k}");
+        }
+    }
+
+    #[test]
+    fn test_classify_all_kinds() {
+        // 全 kind が各判定メソッドと矛盾なく、ちょうど1つに分類される
+        for k in 0..=u16::MAX {
+            let kind = Kind(k);
+            let expected = match (
+                kind.is_replaceable(),
+                kind.is_ephemeral(),
+                kind.is_addressable(),
+            ) {
+                (true, false, false) => KindClass::Replaceable,
+                (false, true, false) => KindClass::Ephemeral,
+                (false, false, true) => KindClass::Addressable,
+                (false, false, false) => KindClass::Regular,
+                _ => panic!("kind {k} が複数の分類に該当する"),
+            };
+            assert_eq!(kind.classify(), expected, "kind {k}");
+
+            // NIP-01 の Regular 範囲は必ず Regular
+            if kind.is_regular() {
+                assert_eq!(kind.classify(), KindClass::Regular, "kind {k}");
+            }
+        }
+    }
 }
@@ -1,7 +1,9 @@
 /// Nostrの公開鍵（NIP-01準拠）
 ///
 /// BIP-340に従い、x座標のみの32バイト（64文字のhex）で表現される。
-#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 #[serde(transparent)]
 pub struct Pubkey(secp256k1::XOnlyPublicKey);
 
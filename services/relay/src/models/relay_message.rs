@@ -28,6 +28,300 @@ pub enum RelayMessage {
 
     /// 通知メッセージ: ["NOTICE", <message>]
     Notice(String),
+
+    /// 接続の統計情報（NIP 標準外の拡張）: ["STATS-RESULT", <stats>]
+    StatsResult(ConnectionStats),
+}
+
+/// 1接続の統計情報（STATS コマンドの応答）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ConnectionStats {
+    /// クライアントから受け取った EVENT メッセージ数（拒否したものを含む）
+    pub events_received: u64,
+    /// クライアントへ送った EVENT メッセージ数（保存済み・リアルタイムの合計）
+    pub events_sent: u64,
+    /// 有効なサブスクリプション数
+    pub subscriptions_active: usize,
+}
+
+/// NIP-01 で定義された OK / CLOSED メッセージの machine-readable prefix
+///
+/// クライアントはメッセージ先頭の `<prefix>:` で拒否理由を機械的に判別する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineReadablePrefix {
+    /// 既に保持しているイベント・サブスクリプション
+    Duplicate,
+    /// PoW 不足
+    Pow,
+    /// 公開鍵・IP 等によるブロック
+    Blocked,
+    /// レート制限
+    RateLimited,
+    /// メッセージやイベントの内容が不正
+    Invalid,
+    /// 認証済みだが権限がない
+    Restricted,
+    /// ミュート
+    Mute,
+    /// その他のエラー（内部エラー等）
+    Error,
+    /// NIP-42 認証が必要
+    AuthRequired,
+}
+
+impl MachineReadablePrefix {
+    /// prefix 文字列（末尾の `:` を含まない）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MachineReadablePrefix::Duplicate => "duplicate",
+            MachineReadablePrefix::Pow => "pow",
+            MachineReadablePrefix::Blocked => "blocked",
+            MachineReadablePrefix::RateLimited => "rate-limited",
+            MachineReadablePrefix::Invalid => "invalid",
+            MachineReadablePrefix::Restricted => "restricted",
+            MachineReadablePrefix::Mute => "mute",
+            MachineReadablePrefix::Error => "error",
+            MachineReadablePrefix::AuthRequired => "auth-required",
+        }
+    }
+
+    /// `<prefix>: <reason>` 形式のメッセージを組み立てる
+    pub fn message(&self, reason: &str) -> String {
+        format!("{}: {}", self.as_str(), reason)
+    }
+}
+
+impl std::fmt::Display for MachineReadablePrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl RelayMessage {
+    /// 受理を示す OK メッセージ（理由なし）
+    pub fn ok_accepted(event_id: super::EventId) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: true,
+            message: String::new(),
+        }
+    }
+
+    /// 拒否を示す prefix 付きの OK メッセージ
+    pub fn ok_rejected(
+        event_id: super::EventId,
+        prefix: MachineReadablePrefix,
+        reason: &str,
+    ) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: false,
+            message: prefix.message(reason),
+        }
+    }
+
+    /// 既に保持しているイベント（NIP-01: 受理扱い）
+    pub fn ok_duplicate(event_id: super::EventId) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: true,
+            message: MachineReadablePrefix::Duplicate.message("already have this event"),
+        }
+    }
+
+    /// Replaceable/Addressable の既存イベントを置換した
+    pub fn ok_replaced(event_id: super::EventId) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: true,
+            message: "replaced: updated existing event".to_string(),
+        }
+    }
+
+    /// Replaceable/Addressable でより新しいイベントを保持しているため無視した
+    pub fn ok_ignored(event_id: super::EventId) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: true,
+            message: "ignored: newer event exists".to_string(),
+        }
+    }
+
+    /// タグ数超過
+    pub fn ok_too_many_tags(event_id: super::EventId, got: usize, max: u32) -> Self {
+        Self::ok_rejected(
+            event_id,
+            MachineReadablePrefix::Invalid,
+            &format!("too many tags (got {got}, max {max})"),
+        )
+    }
+
+    /// コンテンツ長超過
+    pub fn ok_content_too_long(event_id: super::EventId, got: usize, max: u32) -> Self {
+        Self::ok_rejected(
+            event_id,
+            MachineReadablePrefix::Invalid,
+            &format!("content too long (got {got} chars, max {max})"),
+        )
+    }
+
+    /// created_at が負の値
+    pub fn ok_created_at_negative(event_id: super::EventId, got: i64) -> Self {
+        Self::ok_rejected(
+            event_id,
+            MachineReadablePrefix::Invalid,
+            &format!("created_at must not be negative (got {got})"),
+        )
+    }
+
+    /// created_at が過去すぎる
+    pub fn ok_created_at_too_old(event_id: super::EventId, got: i64, min: i64) -> Self {
+        Self::ok_rejected(
+            event_id,
+            MachineReadablePrefix::Invalid,
+            &format!("event is too old (created_at: got {got}, min {min})"),
+        )
+    }
+
+    /// created_at が未来すぎる
+    pub fn ok_created_at_too_far_in_future(event_id: super::EventId, got: i64, max: i64) -> Self {
+        Self::ok_rejected(
+            event_id,
+            MachineReadablePrefix::Invalid,
+            &format!("event is too far in the future (created_at: got {got}, max {max})"),
+        )
+    }
+
+    /// ID・署名の検証失敗
+    pub fn ok_verification_failed(
+        event_id: super::EventId,
+        error: &super::VerificationError,
+    ) -> Self {
+        Self::ok_rejected(event_id, MachineReadablePrefix::Invalid, &error.to_string())
+    }
+
+    /// ID・署名の検証失敗（理由を伏せて `invalid:` だけを返す）
+    pub fn ok_verification_failed_without_reason(event_id: super::EventId) -> Self {
+        RelayMessage::Ok {
+            event_id,
+            success: false,
+            message: format!("{}:", MachineReadablePrefix::Invalid),
+        }
+    }
+
+    /// 書き込み権限がないクライアントからの EVENT の拒否（会員制リレー等）
+    pub fn ok_restricted(event_id: super::EventId, reason: &str) -> Self {
+        Self::ok_rejected(event_id, MachineReadablePrefix::Restricted, reason)
+    }
+
+    /// 保存時の内部エラー
+    pub fn ok_store_error(event_id: super::EventId, error: impl std::fmt::Display) -> Self {
+        Self::ok_rejected(event_id, MachineReadablePrefix::Error, &error.to_string())
+    }
+
+    /// OK メッセージの末尾に検証・保存の処理時間を括弧で付記する（診断用）
+    ///
+    /// prefix の後ろに足すだけなので、prefix による判定には影響しない。OK 以外はそのまま返す。
+    pub fn with_timing(self, verify: std::time::Duration, save: std::time::Duration) -> Self {
+        match self {
+            RelayMessage::Ok {
+                event_id,
+                success,
+                message,
+            } => {
+                let timing = format!(
+                    "(verify {}ms, save {}ms)",
+                    verify.as_millis(),
+                    save.as_millis()
+                );
+                let message = if message.is_empty() {
+                    timing
+                } else {
+                    format!("{message} {timing}")
+                };
+                RelayMessage::Ok {
+                    event_id,
+                    success,
+                    message,
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// prefix 付きの CLOSED メッセージを作成
+    pub fn closed(
+        subscription_id: super::SubscriptionId,
+        prefix: MachineReadablePrefix,
+        reason: &str,
+    ) -> Self {
+        RelayMessage::Closed {
+            subscription_id,
+            message: prefix.message(reason),
+        }
+    }
+
+    /// クライアントの CLOSE に対する応答（理由なし）
+    pub fn closed_by_client(subscription_id: super::SubscriptionId) -> Self {
+        RelayMessage::Closed {
+            subscription_id,
+            message: String::new(),
+        }
+    }
+
+    /// フィルタ数超過による CLOSED
+    pub fn closed_too_many_filters(
+        subscription_id: super::SubscriptionId,
+        count: usize,
+        max: u32,
+    ) -> Self {
+        Self::closed(
+            subscription_id,
+            MachineReadablePrefix::Invalid,
+            &format!("too many filters ({count}, max {max})"),
+        )
+    }
+
+    /// 条件のないフィルタを受け付けない設定での CLOSED
+    pub fn closed_unconditional_filter(subscription_id: super::SubscriptionId) -> Self {
+        Self::closed(
+            subscription_id,
+            MachineReadablePrefix::Invalid,
+            "filter must have at least one condition",
+        )
+    }
+
+    /// サブスクリプション数超過による CLOSED
+    ///
+    /// 既存の購読を閉じれば再試行できるため、NIP-01 の `rate-limited:` を使う。
+    pub fn closed_too_many_subscriptions(
+        subscription_id: super::SubscriptionId,
+        current: usize,
+        max: u32,
+    ) -> Self {
+        Self::closed(
+            subscription_id,
+            MachineReadablePrefix::RateLimited,
+            &format!("too many subscriptions ({current}, max {max})"),
+        )
+    }
+
+    /// 読み取り権限がないクライアントの REQ の拒否（会員制リレー等）
+    pub fn closed_restricted(subscription_id: super::SubscriptionId, reason: &str) -> Self {
+        Self::closed(subscription_id, MachineReadablePrefix::Restricted, reason)
+    }
+
+    /// クエリ失敗等の内部エラーによる CLOSED
+    pub fn closed_subscription_error(
+        subscription_id: super::SubscriptionId,
+        error: impl std::fmt::Display,
+    ) -> Self {
+        Self::closed(
+            subscription_id,
+            MachineReadablePrefix::Error,
+            &error.to_string(),
+        )
+    }
 }
 
 impl Serialize for RelayMessage {
@@ -80,6 +374,12 @@ impl Serialize for RelayMessage {
                 seq.serialize_element(message)?;
                 seq.end()
             }
+            RelayMessage::StatsResult(stats) => {
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element("STATS-RESULT")?;
+                seq.serialize_element(stats)?;
+                seq.end()
+            }
         }
     }
 }
@@ -247,4 +547,265 @@ mod tests {
         assert_eq!(arr[0], "NOTICE");
         assert_eq!(arr[1], "This is a notice message");
     }
+
+    #[test]
+    fn test_stats_result_serialize() {
+        let message = RelayMessage::StatsResult(ConnectionStats {
+            events_received: 3,
+            events_sent: 5,
+            subscriptions_active: 1,
+        });
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!([
+                "STATS-RESULT",
+                {"events_received": 3, "events_sent": 5, "subscriptions_active": 1}
+            ])
+        );
+    }
+
+    // ========== machine-readable prefix ==========
+
+    /// CLOSED メッセージの message 部分を取り出す
+    fn closed_message(message: &RelayMessage) -> &str {
+        match message {
+            RelayMessage::Closed { message, .. } => message,
+            other => panic!("CLOSED ではない: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_machine_readable_prefix_strings() {
+        let cases = [
+            (MachineReadablePrefix::Duplicate, "duplicate"),
+            (MachineReadablePrefix::Pow, "pow"),
+            (MachineReadablePrefix::Blocked, "blocked"),
+            (MachineReadablePrefix::RateLimited, "rate-limited"),
+            (MachineReadablePrefix::Invalid, "invalid"),
+            (MachineReadablePrefix::Restricted, "restricted"),
+            (MachineReadablePrefix::Mute, "mute"),
+            (MachineReadablePrefix::Error, "error"),
+            (MachineReadablePrefix::AuthRequired, "auth-required"),
+        ];
+        for (prefix, expected) in cases {
+            assert_eq!(prefix.as_str(), expected);
+            assert_eq!(prefix.to_string(), expected);
+            assert_eq!(prefix.message("reason"), format!("{expected}: reason"));
+        }
+    }
+
+    #[test]
+    fn test_closed_with_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message =
+            RelayMessage::closed(sub_id, MachineReadablePrefix::Restricted, "not allowed");
+        assert_eq!(closed_message(&message), "restricted: not allowed");
+    }
+
+    #[test]
+    fn test_closed_by_client_has_empty_message() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_by_client(sub_id);
+        assert_eq!(closed_message(&message), "");
+    }
+
+    #[test]
+    fn test_closed_too_many_filters_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_too_many_filters(sub_id, 11, 10);
+        assert_eq!(
+            closed_message(&message),
+            "invalid: too many filters (11, max 10)"
+        );
+    }
+
+    #[test]
+    fn test_closed_unconditional_filter_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_unconditional_filter(sub_id);
+        assert_eq!(
+            closed_message(&message),
+            "invalid: filter must have at least one condition"
+        );
+    }
+
+    #[test]
+    fn test_closed_too_many_subscriptions_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_too_many_subscriptions(sub_id, 20, 20);
+        assert_eq!(
+            closed_message(&message),
+            "rate-limited: too many subscriptions (20, max 20)"
+        );
+    }
+
+    #[test]
+    fn test_closed_subscription_error_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_subscription_error(sub_id, "database unavailable");
+        assert_eq!(closed_message(&message), "error: database unavailable");
+    }
+
+    #[test]
+    fn test_closed_restricted_prefix() {
+        let sub_id: super::super::SubscriptionId = "sub1".parse().unwrap();
+        let message = RelayMessage::closed_restricted(sub_id, "members only");
+        assert_eq!(closed_message(&message), "restricted: members only");
+    }
+
+    // ========== OK メッセージ ==========
+
+    fn test_event_id() -> super::super::EventId {
+        "0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20"
+            .parse()
+            .unwrap()
+    }
+
+    /// OK メッセージの (success, message) を取り出す
+    fn ok_parts(message: &RelayMessage) -> (bool, &str) {
+        match message {
+            RelayMessage::Ok {
+                success, message, ..
+            } => (*success, message),
+            other => panic!("OK ではない: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_ok_accepted() {
+        let message = RelayMessage::ok_accepted(test_event_id());
+        assert_eq!(ok_parts(&message), (true, ""));
+    }
+
+    #[test]
+    fn test_ok_rejected_with_prefix() {
+        let message = RelayMessage::ok_rejected(
+            test_event_id(),
+            MachineReadablePrefix::RateLimited,
+            "slow down",
+        );
+        assert_eq!(ok_parts(&message), (false, "rate-limited: slow down"));
+    }
+
+    #[test]
+    fn test_ok_accepted_variants() {
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_duplicate(test_event_id())),
+            (true, "duplicate: already have this event")
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_replaced(test_event_id())),
+            (true, "replaced: updated existing event")
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_ignored(test_event_id())),
+            (true, "ignored: newer event exists")
+        );
+    }
+
+    #[test]
+    fn test_ok_limitation_messages_include_values() {
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_too_many_tags(test_event_id(), 150, 100)),
+            (false, "invalid: too many tags (got 150, max 100)")
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_content_too_long(
+                test_event_id(),
+                70000,
+                65536
+            )),
+            (
+                false,
+                "invalid: content too long (got 70000 chars, max 65536)"
+            )
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_created_at_too_old(
+                test_event_id(),
+                1000,
+                2000
+            )),
+            (
+                false,
+                "invalid: event is too old (created_at: got 1000, min 2000)"
+            )
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_created_at_too_far_in_future(
+                test_event_id(),
+                3000,
+                2000
+            )),
+            (
+                false,
+                "invalid: event is too far in the future (created_at: got 3000, max 2000)"
+            )
+        );
+        assert_eq!(
+            ok_parts(&RelayMessage::ok_created_at_negative(test_event_id(), -1)),
+            (false, "invalid: created_at must not be negative (got -1)")
+        );
+    }
+
+    #[test]
+    fn test_ok_verification_failed_has_invalid_prefix() {
+        let error = super::super::VerificationError::SignatureVerificationFailed;
+        let message = RelayMessage::ok_verification_failed(test_event_id(), &error);
+        let (success, text) = ok_parts(&message);
+        assert!(!success);
+        assert_eq!(text, format!("invalid: {error}"));
+    }
+
+    #[test]
+    fn test_ok_verification_failed_without_reason_has_prefix_only() {
+        let message = RelayMessage::ok_verification_failed_without_reason(test_event_id());
+        assert_eq!(ok_parts(&message), (false, "invalid:"));
+    }
+
+    #[test]
+    fn test_with_timing_appends_after_prefixed_message() {
+        use std::time::Duration;
+
+        let message = RelayMessage::ok_duplicate(test_event_id())
+            .with_timing(Duration::from_micros(1_500), Duration::from_millis(12));
+        assert_eq!(
+            ok_parts(&message),
+            (
+                true,
+                "duplicate: already have this event (verify 1ms, save 12ms)"
+            )
+        );
+
+        let message = RelayMessage::ok_accepted(test_event_id())
+            .with_timing(Duration::ZERO, Duration::from_millis(3));
+        assert_eq!(ok_parts(&message), (true, "(verify 0ms, save 3ms)"));
+
+        let eose = RelayMessage::Eose("sub1".parse().unwrap());
+        assert_eq!(
+            eose.clone().with_timing(Duration::ZERO, Duration::ZERO),
+            eose
+        );
+    }
+
+    #[test]
+    fn test_ok_store_error_has_error_prefix() {
+        let message = RelayMessage::ok_store_error(test_event_id(), "write failed");
+        assert_eq!(ok_parts(&message), (false, "error: write failed"));
+    }
+
+    #[test]
+    fn test_ok_restricted_prefix() {
+        let message = RelayMessage::ok_restricted(test_event_id(), "members only");
+        assert_eq!(ok_parts(&message), (false, "restricted: members only"));
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!([
+                "OK",
+                test_event_id().to_string(),
+                false,
+                "restricted: members only"
+            ])
+        );
+    }
 }
@@ -20,6 +20,10 @@ pub enum SubscriptionIdParseError {
     /// 文字数が64文字を超えている
     #[error("サブスクリプションIDが長すぎます: {0}文字（最大{1}文字）")]
     TooLong(usize, usize),
+
+    /// 制御文字（改行・null など）を含む
+    #[error("サブスクリプションIDに制御文字を含められません: {0:?}")]
+    ControlCharacter(char),
 }
 
 impl fmt::Display for SubscriptionId {
@@ -44,11 +48,25 @@ impl FromStr for SubscriptionId {
             ));
         }
 
+        // ログインジェクションや表示崩れを防ぐため、制御文字は許可しない
+        // （NIP-01 は文字種を規定していないので、日本語や絵文字などの印字可能な文字は許可する）
+        if let Some(c) = s.chars().find(|c| c.is_control()) {
+            return Err(SubscriptionIdParseError::ControlCharacter(c));
+        }
+
         Ok(SubscriptionId(s.to_string()))
     }
 }
 
 impl SubscriptionId {
+    /// 検証せずに作成する
+    ///
+    /// 文字種が不正な REQ に、クライアントが送ったIDのまま CLOSED で応答する用途に限る。
+    /// JSON にシリアライズされる際に制御文字はエスケープされる。
+    pub(crate) fn new_unchecked(s: &str) -> Self {
+        SubscriptionId(s.to_string())
+    }
+
     /// 内部文字列への参照を返す
     #[allow(dead_code)]
     pub fn as_str(&self) -> &str {
@@ -156,10 +174,47 @@ mod tests {
 
     #[test]
     fn test_subscription_id_with_control_chars() {
-        // 改行・制御文字を含む文字列（許可される）
-        let with_newline = "sub\nid";
-        let result: Result<SubscriptionId, _> = with_newline.parse();
-        assert!(result.is_ok());
+        // 改行・null・DEL・C1制御文字などを含む文字列はエラー
+        for (input, c) in [
+            ("sub\nid", '\n'),
+            ("sub\rid", '\r'),
+            ("sub\tid", '\t'),
+            ("sub\0id", '\0'),
+            ("sub\u{1b}[31m", '\u{1b}'),
+            ("sub\u{7f}", '\u{7f}'),
+            ("sub\u{85}", '\u{85}'),
+        ] {
+            let result: Result<SubscriptionId, _> = input.parse();
+            assert_eq!(
+                result,
+                Err(SubscriptionIdParseError::ControlCharacter(c)),
+                "{input:?} は拒否されるべき"
+            );
+        }
+    }
+
+    #[test]
+    fn test_subscription_id_with_printable_chars() {
+        // 空白・ASCII記号は許可される
+        for input in [
+            "sub id",
+            "sub:1/feed#home",
+            "~!@$%^&*()_+-=[]{}|;',.<>?\"\\",
+        ] {
+            let result: Result<SubscriptionId, _> = input.parse();
+            assert!(result.is_ok(), "{input:?} は許可されるべき");
+        }
+    }
+
+    #[test]
+    fn test_subscription_id_too_long_checked_before_control_chars() {
+        // 長さ超過と制御文字の両方に該当する場合は長さのエラーを返す
+        let input = format!("{}\n", "a".repeat(64));
+        let result: Result<SubscriptionId, _> = input.parse();
+        assert!(matches!(
+            result,
+            Err(SubscriptionIdParseError::TooLong(65, 64))
+        ));
     }
 
     #[test]
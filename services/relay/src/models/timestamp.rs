@@ -3,8 +3,35 @@
 pub struct Timestamp(i64);
 
 impl Timestamp {
+    /// UNIX秒から作成する
+    pub fn from_i64(secs: i64) -> Self {
+        Self(secs)
+    }
+
     /// 内部のi64値を返す
     pub fn as_i64(&self) -> i64 {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_i64_boundaries() {
+        let max: Timestamp = serde_json::from_str(&i64::MAX.to_string()).unwrap();
+        assert_eq!(max.as_i64(), i64::MAX);
+        let min: Timestamp = serde_json::from_str(&i64::MIN.to_string()).unwrap();
+        assert_eq!(min.as_i64(), i64::MIN);
+    }
+
+    #[test]
+    fn test_deserialize_out_of_i64_range_fails() {
+        // u64 の上位ビットを使う値は i64 に収まらないのでパースエラーにする（負数に化けない）
+        let over = (i64::MAX as u64 + 1).to_string();
+        assert!(serde_json::from_str::<Timestamp>(&over).is_err());
+        assert!(serde_json::from_str::<Timestamp>(&u64::MAX.to_string()).is_err());
+        assert!(serde_json::from_str::<Timestamp>("1.5").is_err());
+    }
+}
@@ -60,6 +60,9 @@ pub struct Limitation {
     pub max_subscriptions: u32,
     pub max_filters: u32,
     pub max_subid_length: u32,
+    pub max_limit: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_limit: Option<u32>,
     pub max_event_tags: u32,
     pub max_content_length: u32,
     pub created_at_lower_limit: u64,
@@ -73,6 +76,8 @@ impl From<&LimitationConfig> for Limitation {
             max_subscriptions: config.max_subscriptions,
             max_filters: config.max_filters,
             max_subid_length: config.max_subid_length,
+            max_limit: config.max_limit,
+            default_limit: config.default_limit,
             max_event_tags: config.max_event_tags,
             max_content_length: config.max_content_length,
             created_at_lower_limit: config.created_at_lower_limit,
@@ -154,6 +159,19 @@ mod tests {
     use super::*;
     use serial_test::serial;
 
+    #[test]
+    fn test_limitation_serializes_default_limit_only_when_set() {
+        let config = LimitationConfig {
+            default_limit: Some(100),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(Limitation::from(&config)).unwrap();
+        assert_eq!(json["default_limit"], 100);
+
+        let json = serde_json::to_value(Limitation::from(&LimitationConfig::default())).unwrap();
+        assert!(json.get("default_limit").is_none());
+    }
+
     #[test]
     fn test_supported_nips_contains_expected() {
         // 実装済みNIPが含まれていることを確認
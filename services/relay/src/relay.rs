@@ -1,16 +1,43 @@
 //! Relay構造体（EventStore + broadcast sender）
 
-use std::time::Instant;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use tokio::sync::broadcast;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, instrument, warn};
 
-use crate::models::{Event, Filter, VerifiedEvent};
+use crate::config::DEFAULT_MAX_HELD_EVENTS;
+use crate::models::{Event, EventId, Filter, VerifiedEvent};
 use crate::store::{EventStore, SaveResult, StoreError};
 
+mod held_events;
+mod in_flight;
+mod recent_ids;
+use held_events::HeldEvents;
+use in_flight::{InFlight, InFlightEvents, InFlightGuard};
+pub use recent_ids::DedupConfig;
+use recent_ids::RecentEventIds;
+
 /// broadcast チャネルのキャパシティ
 const BROADCAST_CAPACITY: usize = 1024;
 
+/// 保留中のイベントを解除する間隔（created_at は秒単位なので 1 秒ごとで足りる）
+pub const HELD_EVENTS_RELEASE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// publish の各フェーズの所要時間
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PublishTiming {
+    /// 保存フェーズ（NIP-09 の削除処理を含む。Ephemeral はゼロ）
+    pub save: Duration,
+    /// 配信フェーズ（broadcast チャネルへの送信。配信しない結果ではゼロ）
+    ///
+    /// 各接続への送信は接続ごとのタスクが非同期に行うため、ここには含まれない。
+    /// broadcast チャネルへの送信は受信側を待たないので、購読者数に依存しない。
+    pub distribute: Duration,
+}
+
 /// Nostr Relay のコア構造体
 ///
 /// イベントの永続化と配信を担う
@@ -19,6 +46,14 @@ pub struct Relay<S: EventStore> {
     store: S,
     /// イベント配信用 broadcast sender
     event_tx: broadcast::Sender<Event>,
+    /// 直近に保存済みと判定したイベントID（重複受信でストアを呼ばないため）
+    recent_ids: RecentEventIds,
+    /// 保存処理中のイベントID（同一イベントの並行処理で二重に保存・配信しないため）
+    in_flight: InFlightEvents,
+    /// created_at が未来のイベントの配信を保留するか
+    hold_future_events: bool,
+    /// 配信を保留しているイベント
+    held_events: HeldEvents,
 }
 
 impl<S: EventStore> Relay<S> {
@@ -28,8 +63,35 @@ impl<S: EventStore> Relay<S> {
     ///
     /// * `store` - イベントストレージの実装
     pub fn new(store: S) -> Self {
+        Self::with_dedup(store, DedupConfig::default())
+    }
+
+    /// 重複受信の短期記録の設定を指定して Relay を作成
+    pub fn with_dedup(store: S, dedup: DedupConfig) -> Self {
         let (event_tx, _) = broadcast::channel(BROADCAST_CAPACITY);
-        Self { store, event_tx }
+        Self {
+            store,
+            event_tx,
+            recent_ids: RecentEventIds::new(dedup),
+            in_flight: InFlightEvents::default(),
+            hold_future_events: false,
+            held_events: HeldEvents::new(DEFAULT_MAX_HELD_EVENTS as usize),
+        }
+    }
+
+    /// created_at が未来のイベントの配信を保留するかを設定する
+    ///
+    /// 有効にした場合、保留したイベントは `release_held_events` を呼ぶまで配信されない。
+    /// 定期的な解除は `run_held_events_release` で行う。
+    pub fn with_hold_future_events(mut self, enabled: bool) -> Self {
+        self.hold_future_events = enabled;
+        self
+    }
+
+    /// 配信を保留するイベント数の上限を設定する（上限に達したら保留せずに即配信する）
+    pub fn with_max_held_events(mut self, max: usize) -> Self {
+        self.held_events = HeldEvents::new(max);
+        self
     }
 
     /// イベントを保存し、成功したら broadcast で配信
@@ -38,43 +100,243 @@ impl<S: EventStore> Relay<S> {
     ///
     /// * `Ok(SaveResult::Saved)` - 新規イベントとして保存・配信完了
     /// * `Ok(SaveResult::Replaced)` - 既存イベントを置換・配信完了
-    /// * `Ok(SaveResult::Duplicate)` - 既存イベント（配信なし）
+    /// * `Ok(SaveResult::Duplicate)` - 既存イベント、または並行して届いた同じイベントを別の処理が保存済み（配信なし）
     /// * `Ok(SaveResult::Ignored)` - 古いイベント（配信なし）
     /// * `Err(StoreError)` - ストレージエラー
     ///
     /// # Ephemeral イベント
     ///
     /// Ephemeral イベント (kind 20000-29999) は保存せず配信のみ行う
-    #[instrument(skip(self, event), fields(event_id = %event.inner().id, kind = event.inner().kind.as_u16()))]
     pub async fn publish(&self, event: VerifiedEvent) -> Result<SaveResult, StoreError> {
-        let start = Instant::now();
+        self.publish_timed(event)
+            .await
+            .map(|(result, _timing)| result)
+    }
+
+    /// `publish` と同じ処理を行い、保存・配信の各フェーズの所要時間も返す
+    #[instrument(skip(self, event), fields(event_id = %event.inner().id, kind = event.inner().kind.as_u16()))]
+    pub async fn publish_timed(
+        &self,
+        event: VerifiedEvent,
+    ) -> Result<(SaveResult, PublishTiming), StoreError> {
+        let mut timing = PublishTiming::default();
 
         // Ephemeral イベント: 保存せず配信のみ
         if event.kind.is_ephemeral() {
-            let _ = self.event_tx.send(event.into_inner());
+            let distribute_start = Instant::now();
+            self.distribute(event.into_inner());
+            timing.distribute = distribute_start.elapsed();
             debug!(
-                elapsed_ms = start.elapsed().as_millis(),
+                elapsed_ms = timing.distribute.as_millis(),
                 "publish完了（ephemeral）"
             );
-            return Ok(SaveResult::Ephemeral);
+            return Ok((SaveResult::Ephemeral, timing));
         }
 
-        let result = self.store.save(&event).await?;
+        // 直近に保存済みと判定したイベントの再送はストアを呼ばずに重複とする。
+        // TTL 内に NIP-09 で削除されたイベントの再送も重複になる
+        if self.recent_ids.contains(&event.id) {
+            debug!("publish完了（直近に受信済みのため重複）");
+            return Ok((SaveResult::Duplicate, timing));
+        }
+
+        // 別の接続から届いた同じイベントを処理中なら、そちらの保存結果を待つ
+        let in_flight = match self.acquire_in_flight(event.id).await {
+            Ok(guard) => guard,
+            Err(result) => return Ok((result, timing)),
+        };
+
+        let save_start = Instant::now();
+        let result = self.persist(&event).await?;
+        timing.save = save_start.elapsed();
+        in_flight.finish(&result);
 
         // Saved または Replaced の場合のみ配信
-        if result == SaveResult::Saved || result == SaveResult::Replaced {
-            // NIP-09: kind 5（削除リクエスト）の場合、参照されたイベントを削除
-            // TODO: 削除済みイベントの再投稿防止（NIP-09 SHOULD級）は未実装。
-            // 削除リクエストを記録し、以降の同一イベントのEVENTメッセージをrejectする仕組みが望ましい。
-            if event.kind.is_deletion_request()
-                && let Err(e) = self.store.delete(&event).await
-            {
-                warn!(error = %e, event_id = %event.inner().id, "削除リクエストの処理に失敗");
+        if matches!(result, SaveResult::Saved | SaveResult::Replaced { .. }) {
+            let distribute_start = Instant::now();
+            self.distribute(event.into_inner());
+            timing.distribute = distribute_start.elapsed();
+        }
+
+        debug!(
+            save_ms = timing.save.as_millis(),
+            distribute_ms = timing.distribute.as_millis(),
+            result = ?result,
+            "publish完了"
+        );
+        Ok((result, timing))
+    }
+
+    /// 保存を待たずに先に配信し、その後で保存する（低レイテンシモード）
+    ///
+    /// 配信は保存結果が分かる前に行うため、ストアで重複・古い Replaceable と判定される
+    /// イベントも配信されうる（Ephemeral と直近に受信済みのイベントは `publish_timed` と同じ扱い）。
+    /// 保存に失敗した場合も配信は取り消せないため、warn ログを出してエラーを返す。
+    #[instrument(skip(self, event), fields(event_id = %event.inner().id, kind = event.inner().kind.as_u16()))]
+    pub async fn publish_distribute_first(
+        &self,
+        event: VerifiedEvent,
+    ) -> Result<(SaveResult, PublishTiming), StoreError> {
+        if event.kind.is_ephemeral() || self.recent_ids.contains(&event.id) {
+            return self.publish_timed(event).await;
+        }
+
+        let mut timing = PublishTiming::default();
+        let in_flight = match self.acquire_in_flight(event.id).await {
+            Ok(guard) => guard,
+            Err(result) => return Ok((result, timing)),
+        };
+        let distribute_start = Instant::now();
+        self.distribute(event.inner().clone());
+        timing.distribute = distribute_start.elapsed();
+
+        let save_start = Instant::now();
+        let result = self.persist(&event).await.inspect_err(|e| {
+            warn!(error = %e, "配信済みイベントの保存に失敗");
+        })?;
+        timing.save = save_start.elapsed();
+        in_flight.finish(&result);
+
+        debug!(
+            save_ms = timing.save.as_millis(),
+            distribute_ms = timing.distribute.as_millis(),
+            result = ?result,
+            "publish完了（配信先行）"
+        );
+        Ok((result, timing))
+    }
+
+    /// 同じイベントの処理を開始する
+    ///
+    /// 別の接続から届いた同じイベントを処理中なら、その保存結果を待つ。先行の処理が
+    /// 保存を終えていれば、この処理の結果を `Err` で返す（保存・配信は先行の処理が済ませている）。
+    /// 先行の処理が保存に失敗した、または接続切断で中断された場合は、代わりに処理を開始する。
+    async fn acquire_in_flight(&self, id: EventId) -> Result<InFlightGuard<'_>, SaveResult> {
+        loop {
+            let waiter = match self.in_flight.acquire(id) {
+                InFlight::Acquired(guard) => return Ok(guard),
+                InFlight::Busy(waiter) => waiter,
+            };
+            match waiter.wait().await {
+                Some(SaveResult::Ignored) => {
+                    debug!("publish完了（同じイベントの処理結果が古いイベントのため無視）");
+                    return Err(SaveResult::Ignored);
+                }
+                Some(_) => {
+                    debug!("publish完了（同じイベントを別の処理が保存済みのため重複）");
+                    return Err(SaveResult::Duplicate);
+                }
+                None => debug!("同じイベントの先行処理が保存できなかったため処理を引き継ぐ"),
+            }
+        }
+    }
+
+    /// 購読者へ配信する（保留が有効で created_at が未来なら保留する）
+    fn distribute(&self, event: Event) {
+        if self.hold_future_events && HeldEvents::should_hold(&event, unix_now()) {
+            let event_id = event.id;
+            let created_at = event.created_at.as_i64();
+            let Some(event) = self.held_events.hold(event) else {
+                debug!(%event_id, created_at, "created_at が未来のため配信を保留");
+                return;
+            };
+            warn!(%event_id, created_at, "保留中のイベントが上限に達したため即配信");
+            let _ = self.event_tx.send(event);
+            return;
+        }
+        let _ = self.event_tx.send(event);
+    }
+
+    /// created_at が `now`（UNIX秒）に到達した保留中のイベントを配信する
+    ///
+    /// 保留中に削除・置換されてストアに残っていないイベントは配信せずに破棄する。
+    /// ストアの確認に失敗した場合は保留に戻し、次回の解除で再確認する。
+    ///
+    /// # 戻り値
+    ///
+    /// 配信したイベント数
+    pub async fn release_held_events(&self, now: u64) -> usize {
+        let due = self.held_events.take_due(now);
+        if due.is_empty() {
+            return 0;
+        }
+
+        let filter = Filter {
+            ids: Some(due.iter().map(|event| event.id).collect()),
+            ..Default::default()
+        };
+        let existing: HashSet<EventId> = match self.store.query(&[filter]).await {
+            Ok(events) => events.into_iter().map(|event| event.id).collect(),
+            Err(e) => {
+                warn!(error = %e, "保留中のイベントの存在確認に失敗したため保留に戻す");
+                for event in due {
+                    // 確認中に上限まで埋まった場合は、確認できないまま配信する
+                    if let Some(event) = self.held_events.hold(event) {
+                        let _ = self.event_tx.send(event);
+                    }
+                }
+                return 0;
+            }
+        };
+
+        let mut released = 0;
+        let mut dropped = 0;
+        for event in due {
+            if existing.contains(&event.id) {
+                let _ = self.event_tx.send(event);
+                released += 1;
+            } else {
+                dropped += 1;
+            }
+        }
+        debug!(
+            released,
+            dropped,
+            remaining = self.held_events.len(),
+            "保留中のイベントを配信"
+        );
+        released
+    }
+
+    /// `shutdown` がキャンセルされるまで、`period` ごとに保留中のイベントを解除する
+    ///
+    /// 保留を有効にした場合はバックグラウンドタスクとして起動しておく。
+    pub async fn run_held_events_release(&self, period: Duration, shutdown: &CancellationToken) {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = interval.tick() => {}
             }
-            let _ = self.event_tx.send(event.into_inner());
+            self.release_held_events(unix_now()).await;
+        }
+    }
+
+    /// 配信を保留しているイベント数
+    pub fn held_event_count(&self) -> usize {
+        self.held_events.len()
+    }
+
+    /// イベントを保存し、保存できた削除リクエストは参照先の削除まで行う
+    async fn persist(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+        let result = self.store.save(event).await?;
+        if matches!(
+            result,
+            SaveResult::Saved | SaveResult::Replaced { .. } | SaveResult::Duplicate
+        ) {
+            self.recent_ids.insert(event.id);
         }
 
-        debug!(elapsed_ms = start.elapsed().as_millis(), result = ?result, "publish完了");
+        // NIP-09: kind 5（削除リクエスト）の場合、参照されたイベントを削除
+        // TODO: 削除済みイベントの再投稿防止（NIP-09 SHOULD級）は未実装。
+        // 削除リクエストを記録し、以降の同一イベントのEVENTメッセージをrejectする仕組みが望ましい。
+        if matches!(result, SaveResult::Saved | SaveResult::Replaced { .. })
+            && event.kind.is_deletion_request()
+            && let Err(e) = self.store.delete(event).await
+        {
+            warn!(error = %e, event_id = %event.inner().id, "削除リクエストの処理に失敗");
+        }
         Ok(result)
     }
 
@@ -102,8 +364,17 @@ impl<S: EventStore> Relay<S> {
     }
 }
 
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
     use crate::store::InMemoryEventStore;
     use crate::test_helpers::{
@@ -123,6 +394,510 @@ mod tests {
         assert_eq!(result, SaveResult::Saved);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_timed_measures_save_and_distribute() {
+        let relay = Relay::new(SlowSaveStore::new(Duration::from_millis(100)));
+        let _rx = relay.subscribe();
+
+        // 時刻を止めているので、保存フェーズはストアの待ち時間ちょうどになる
+        let event = create_test_event();
+        let (result, timing) = relay
+            .publish_timed(event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+        assert_eq!(timing.save, Duration::from_millis(100));
+        assert_eq!(timing.distribute, Duration::ZERO);
+
+        // 配信しない結果では配信フェーズはゼロ
+        let (result, timing) = relay.publish_timed(event.verify().unwrap()).await.unwrap();
+        assert_eq!(result, SaveResult::Duplicate);
+        // 直近に保存したイベントの再送はストアを呼ばないので保存フェーズもゼロ
+        assert_eq!(timing.save, Duration::ZERO);
+        assert_eq!(timing.distribute, Duration::ZERO);
+
+        // Ephemeral は保存フェーズがゼロ
+        let ephemeral = create_custom_event(20000, 1000, "ephemeral", vec![]);
+        let (result, timing) = relay
+            .publish_timed(ephemeral.verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Ephemeral);
+        assert_eq!(timing.save, Duration::ZERO);
+        assert_eq!(timing.distribute, Duration::ZERO);
+    }
+
+    /// 保存に一定時間かかるストア
+    struct SlowSaveStore {
+        inner: InMemoryEventStore,
+        delay: Duration,
+    }
+
+    impl SlowSaveStore {
+        fn new(delay: Duration) -> Self {
+            Self {
+                inner: InMemoryEventStore::new(),
+                delay,
+            }
+        }
+    }
+
+    impl EventStore for SlowSaveStore {
+        async fn save(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            tokio::time::sleep(self.delay).await;
+            self.inner.save(event).await
+        }
+
+        async fn query(&self, filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            self.inner.query(filters).await
+        }
+
+        async fn delete(
+            &self,
+            event: &VerifiedEvent,
+        ) -> Result<crate::store::DeleteResult, StoreError> {
+            self.inner.delete(event).await
+        }
+
+        async fn purge_expired(
+            &self,
+            policies: &[crate::retention::RetentionPolicy],
+            now: u64,
+        ) -> Result<u64, StoreError> {
+            self.inner.purge_expired(policies, now).await
+        }
+    }
+
+    /// save の呼び出し回数を数えるストア
+    struct CountingStore {
+        inner: InMemoryEventStore,
+        save_calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CountingStore {
+        fn new() -> Self {
+            Self {
+                inner: InMemoryEventStore::new(),
+                save_calls: Default::default(),
+            }
+        }
+
+        fn save_calls(&self) -> usize {
+            self.save_calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl EventStore for CountingStore {
+        async fn save(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            self.save_calls
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            // 並行に publish するテストで、保存中に他のタスクへ処理を譲る
+            tokio::task::yield_now().await;
+            self.inner.save(event).await
+        }
+
+        async fn query(&self, filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            self.inner.query(filters).await
+        }
+
+        async fn delete(
+            &self,
+            event: &VerifiedEvent,
+        ) -> Result<crate::store::DeleteResult, StoreError> {
+            self.inner.delete(event).await
+        }
+
+        async fn purge_expired(
+            &self,
+            policies: &[crate::retention::RetentionPolicy],
+            now: u64,
+        ) -> Result<u64, StoreError> {
+            self.inner.purge_expired(policies, now).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recent_duplicate_skips_store_save() {
+        let relay = Relay::with_dedup(
+            CountingStore::new(),
+            DedupConfig {
+                ttl: Duration::from_secs(60),
+                capacity: 100,
+            },
+        );
+        let event = create_test_event();
+
+        let result = relay
+            .publish(event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+        assert_eq!(relay.store.save_calls(), 1);
+
+        // TTL 内の再送はストアを呼ばずに Duplicate
+        let result = relay
+            .publish(event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Duplicate);
+        assert_eq!(relay.store.save_calls(), 1);
+
+        // TTL 切れ後は通常どおりストアで判定する
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let result = relay.publish(event.verify().unwrap()).await.unwrap();
+        assert_eq!(result, SaveResult::Duplicate);
+        assert_eq!(relay.store.save_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_publish_of_same_event_saves_and_distributes_once() {
+        let relay = Relay::new(CountingStore::new());
+        let mut rx = relay.subscribe();
+        let event = create_test_event();
+
+        let (first, second) = tokio::join!(
+            relay.publish(event.clone().verify().unwrap()),
+            relay.publish(event.clone().verify().unwrap()),
+        );
+        assert_eq!(first.unwrap(), SaveResult::Saved);
+        assert_eq!(second.unwrap(), SaveResult::Duplicate);
+        assert_eq!(relay.store.save_calls(), 1);
+        assert_eq!(rx.try_recv().unwrap().id, event.id);
+        assert!(rx.try_recv().is_err());
+
+        // 処理が終われば処理中の記録は外れ、以降は通常の重複判定になる
+        assert_eq!(
+            relay.publish(event.verify().unwrap()).await.unwrap(),
+            SaveResult::Duplicate
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_publish_takes_over_after_first_save_fails() {
+        let relay = Relay::new(FailFirstSaveStore::default());
+        let mut rx = relay.subscribe();
+        let event = create_test_event();
+
+        let (first, second) = tokio::join!(
+            relay.publish(event.clone().verify().unwrap()),
+            relay.publish(event.clone().verify().unwrap()),
+        );
+        // 先行の保存が失敗したら、待っていた側は重複（OK true）とせずに自分で保存する
+        assert!(first.is_err());
+        assert_eq!(second.unwrap(), SaveResult::Saved);
+        assert_eq!(
+            relay.query(&[Filter::default()]).await.unwrap(),
+            vec![event.clone()]
+        );
+        assert_eq!(rx.try_recv().unwrap().id, event.id);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_is_released_after_save_failure() {
+        let relay = Relay::new(FailingSaveStore);
+        let event = create_test_event();
+
+        // 保存に失敗しても処理中の記録は残らず、再送で再び保存を試みる
+        for _ in 0..2 {
+            assert!(
+                relay
+                    .publish(event.clone().verify().unwrap())
+                    .await
+                    .is_err()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ignored_event_is_not_recorded_as_recent() {
+        let relay = Relay::new(CountingStore::new());
+        let newer = create_custom_event(0, 2000, "newer", vec![]);
+        let older = create_custom_event(0, 1000, "older", vec![]);
+        relay.publish(newer.verify().unwrap()).await.unwrap();
+
+        // 古い Replaceable は記録しないので、再送しても Ignored のまま
+        for _ in 0..2 {
+            let result = relay
+                .publish(older.clone().verify().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(result, SaveResult::Ignored);
+        }
+        assert_eq!(relay.store.save_calls(), 3);
+    }
+
+    /// 最初の保存だけ失敗するストア
+    #[derive(Default)]
+    struct FailFirstSaveStore {
+        inner: InMemoryEventStore,
+        failed: std::sync::atomic::AtomicBool,
+    }
+
+    impl EventStore for FailFirstSaveStore {
+        async fn save(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            // 並行に publish するテストで、保存中に他のタスクへ処理を譲る
+            tokio::task::yield_now().await;
+            if !self.failed.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                return Err(StoreError::Internal("save failed".to_string()));
+            }
+            self.inner.save(event).await
+        }
+
+        async fn query(&self, filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            self.inner.query(filters).await
+        }
+
+        async fn delete(
+            &self,
+            event: &VerifiedEvent,
+        ) -> Result<crate::store::DeleteResult, StoreError> {
+            self.inner.delete(event).await
+        }
+
+        async fn purge_expired(
+            &self,
+            policies: &[crate::retention::RetentionPolicy],
+            now: u64,
+        ) -> Result<u64, StoreError> {
+            self.inner.purge_expired(policies, now).await
+        }
+    }
+
+    /// 保存が常に失敗するストア
+    struct FailingSaveStore;
+
+    impl EventStore for FailingSaveStore {
+        async fn save(&self, _event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            Err(StoreError::Internal("save failed".to_string()))
+        }
+
+        async fn query(&self, _filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            Ok(vec![])
+        }
+
+        async fn delete(
+            &self,
+            _event: &VerifiedEvent,
+        ) -> Result<crate::store::DeleteResult, StoreError> {
+            Ok(crate::store::DeleteResult { deleted_count: 0 })
+        }
+
+        async fn purge_expired(
+            &self,
+            _policies: &[crate::retention::RetentionPolicy],
+            _now: u64,
+        ) -> Result<u64, StoreError> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_failure_is_distributed_only_in_distribute_first_mode() {
+        let relay = Relay::new(FailingSaveStore);
+        let mut rx = relay.subscribe();
+        let event = create_test_event();
+
+        // 保存優先: 保存に失敗したイベントは配信しない
+        assert!(
+            relay
+                .publish(event.clone().verify().unwrap())
+                .await
+                .is_err()
+        );
+        assert!(rx.try_recv().is_err());
+
+        // 配信先行: 保存に失敗してもエラーは返るが、配信は済んでいる
+        assert!(
+            relay
+                .publish_distribute_first(event.clone().verify().unwrap())
+                .await
+                .is_err()
+        );
+        assert_eq!(rx.try_recv().unwrap().id, event.id);
+    }
+
+    #[tokio::test]
+    async fn test_distribute_first_returns_store_result() {
+        let relay = Relay::new(InMemoryEventStore::new());
+        let mut rx = relay.subscribe();
+
+        let newer = create_custom_event(0, 2000, "newer", vec![]);
+        let (result, _) = relay
+            .publish_distribute_first(newer.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+        assert_eq!(rx.try_recv().unwrap().id, newer.id);
+
+        // 古い Replaceable は保存結果を知る前に配信されるが、結果は Ignored
+        let older = create_custom_event(0, 1000, "older", vec![]);
+        let (result, _) = relay
+            .publish_distribute_first(older.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Ignored);
+        assert_eq!(rx.try_recv().unwrap().id, older.id);
+
+        // 直近に保存済みの再送は保存優先と同じく配信しない
+        let (result, _) = relay
+            .publish_distribute_first(newer.verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Duplicate);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(relay.query(&[Filter::default()]).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hold_future_events_until_released() {
+        let relay = Relay::new(InMemoryEventStore::new()).with_hold_future_events(true);
+        let mut rx = relay.subscribe();
+        let now = unix_now();
+        let future_at = i64::try_from(now + 3600).unwrap();
+
+        let future_event = create_custom_event(1, future_at, "future", vec![]);
+        let result = relay
+            .publish(future_event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+
+        // 保存はされるので REQ では取得できるが、配信は保留される
+        assert_eq!(
+            relay.query(&[Filter::default()]).await.unwrap(),
+            vec![future_event.clone()]
+        );
+        assert!(rx.try_recv().is_err());
+        assert_eq!(relay.held_event_count(), 1);
+
+        // 現在以前のイベントは即配信
+        let current_event = create_custom_event(1, i64::try_from(now).unwrap(), "now", vec![]);
+        relay
+            .publish(current_event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap(), current_event);
+
+        // created_at 到達前は解除されない
+        assert_eq!(relay.release_held_events(now).await, 0);
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(relay.release_held_events(now + 3600).await, 1);
+        assert_eq!(rx.try_recv().unwrap(), future_event);
+        assert_eq!(relay.held_event_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_held_events_replaced_or_deleted_are_not_released() {
+        let relay = Relay::new(InMemoryEventStore::new()).with_hold_future_events(true);
+        let mut rx = relay.subscribe();
+        let now = unix_now();
+        let future_at = i64::try_from(now + 3600).unwrap();
+
+        // 保留中に新しい Replaceable で置換される
+        let replaced = create_custom_event(0, future_at, "old", vec![]);
+        let replacing = create_custom_event(0, future_at + 1, "new", vec![]);
+        // 保留中に削除リクエストで削除される
+        let deleted = create_custom_event(1, future_at, "deleted", vec![]);
+        for event in [&replaced, &replacing, &deleted] {
+            relay
+                .publish(event.clone().verify().unwrap())
+                .await
+                .unwrap();
+        }
+        let deletion = create_custom_event(
+            5,
+            i64::try_from(now).unwrap(),
+            "",
+            vec![vec!["e", &deleted.id.to_string()]],
+        );
+        relay.publish(deletion.verify().unwrap()).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().kind.as_u16(), 5);
+        assert_eq!(relay.held_event_count(), 3);
+
+        // ストアに残っているイベントだけが配信される
+        assert_eq!(relay.release_held_events(now + 7200).await, 1);
+        assert_eq!(rx.try_recv().unwrap(), replacing);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(relay.held_event_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_held_events_release_distributes_due_events() {
+        let relay = Arc::new(Relay::new(InMemoryEventStore::new()).with_hold_future_events(true));
+        let mut rx = relay.subscribe();
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn({
+            let relay = Arc::clone(&relay);
+            let shutdown = shutdown.clone();
+            async move {
+                relay
+                    .run_held_events_release(Duration::from_millis(50), &shutdown)
+                    .await;
+            }
+        });
+
+        let future_at = i64::try_from(unix_now() + 1).unwrap();
+        let event = create_custom_event(1, future_at, "soon", vec![]);
+        relay
+            .publish(event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(relay.held_event_count(), 1);
+
+        // created_at に到達すると、明示的に release_held_events を呼ばなくても配信される
+        let received = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("保留中のイベントが解除されない")
+            .unwrap();
+        assert_eq!(received, event);
+        assert_eq!(relay.held_event_count(), 0);
+
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("shutdown 後も解除タスクが終了しない")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_future_events_are_distributed_immediately_when_held_events_full() {
+        let relay = Relay::new(InMemoryEventStore::new())
+            .with_hold_future_events(true)
+            .with_max_held_events(1);
+        let mut rx = relay.subscribe();
+        let future_at = i64::try_from(unix_now() + 3600).unwrap();
+
+        let held = create_custom_event(1, future_at, "held", vec![]);
+        relay.publish(held.verify().unwrap()).await.unwrap();
+        assert!(rx.try_recv().is_err());
+
+        let overflow = create_custom_event(1, future_at, "overflow", vec![]);
+        relay
+            .publish(overflow.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap(), overflow);
+        assert_eq!(relay.held_event_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_future_events_are_distributed_immediately_by_default() {
+        let relay = Relay::new(InMemoryEventStore::new());
+        let mut rx = relay.subscribe();
+        let future_at = i64::try_from(unix_now() + 3600).unwrap();
+
+        let event = create_custom_event(1, future_at, "future", vec![]);
+        relay
+            .publish(event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap(), event);
+        assert_eq!(relay.held_event_count(), 0);
+    }
+
     #[tokio::test]
     async fn test_publish_duplicate_event() {
         let store = InMemoryEventStore::new();
@@ -161,6 +936,27 @@ mod tests {
         assert_eq!(received.id, event_id);
     }
 
+    #[tokio::test]
+    async fn test_publish_does_not_wait_for_subscribers() {
+        // 読み出さない購読者が多数いても publish はすぐ返り、全員に届く
+        let relay = Relay::new(InMemoryEventStore::new());
+        let mut receivers: Vec<_> = (0..1000).map(|_| relay.subscribe()).collect();
+
+        let event = create_test_event();
+        let result = tokio::time::timeout(
+            Duration::from_secs(1),
+            relay.publish(event.clone().verify().unwrap()),
+        )
+        .await
+        .expect("publish が購読者を待ってはいけない")
+        .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+
+        for rx in &mut receivers {
+            assert_eq!(rx.try_recv().unwrap(), event);
+        }
+    }
+
     #[tokio::test]
     async fn test_no_broadcast_on_duplicate() {
         let store = InMemoryEventStore::new();
@@ -236,6 +1032,7 @@ mod tests {
 
         // 最初の replaceable イベント
         let old_event = create_custom_event(0, 1000, "old profile", vec![]);
+        let old_event_id = old_event.id;
         relay.publish(old_event.verify().unwrap()).await.unwrap();
 
         // subscriber を作成（最初のイベント後）
@@ -247,7 +1044,12 @@ mod tests {
         let verified_new = new_event.verify().unwrap();
 
         let result = relay.publish(verified_new).await.unwrap();
-        assert_eq!(result, SaveResult::Replaced);
+        assert_eq!(
+            result,
+            SaveResult::Replaced {
+                old_id: old_event_id
+            }
+        );
 
         // broadcast で受信できる
         let received = rx.recv().await.unwrap();
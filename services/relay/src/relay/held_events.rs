@@ -0,0 +1,132 @@
+//! created_at が未来のため配信を保留しているイベント
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::models::{Event, EventId};
+
+/// 配信保留中のイベントの集合（created_at の昇順）
+pub(super) struct HeldEvents {
+    events: Mutex<BTreeMap<(i64, EventId), Event>>,
+    /// 保留するイベント数の上限
+    capacity: usize,
+}
+
+impl HeldEvents {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(BTreeMap::new()),
+            capacity,
+        }
+    }
+
+    fn events(&self) -> MutexGuard<'_, BTreeMap<(i64, EventId), Event>> {
+        // 保留中のイベントは BTreeMap への追加・取り出しだけなので、poison されても中身は壊れない
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 配信を保留するか判定する（created_at が `now` より未来なら保留）
+    pub(super) fn should_hold(event: &Event, now: u64) -> bool {
+        u64::try_from(event.created_at.as_i64()).is_ok_and(|created_at| created_at > now)
+    }
+
+    /// イベントを保留する。上限に達していれば保留せずにそのイベントを返す
+    pub(super) fn hold(&self, event: Event) -> Option<Event> {
+        let mut events = self.events();
+        if events.len() >= self.capacity {
+            return Some(event);
+        }
+        events.insert((event.created_at.as_i64(), event.id), event);
+        None
+    }
+
+    /// created_at が `now` 以下になったイベントを取り出す（created_at の昇順）
+    pub(super) fn take_due(&self, now: u64) -> Vec<Event> {
+        let mut events = self.events();
+        let mut due = Vec::new();
+        while let Some(entry) = events.first_entry()
+            && !Self::should_hold(entry.get(), now)
+        {
+            due.push(entry.remove());
+        }
+        due
+    }
+
+    /// 保留中のイベント数
+    pub(super) fn len(&self) -> usize {
+        self.events().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::create_custom_event;
+
+    #[test]
+    fn test_should_hold_only_future_events() {
+        let now = 1000;
+        assert!(HeldEvents::should_hold(
+            &create_custom_event(1, 1001, "", vec![]),
+            now
+        ));
+        // created_at ちょうどは保留しない
+        assert!(!HeldEvents::should_hold(
+            &create_custom_event(1, 1000, "", vec![]),
+            now
+        ));
+        assert!(!HeldEvents::should_hold(
+            &create_custom_event(1, 999, "", vec![]),
+            now
+        ));
+        assert!(!HeldEvents::should_hold(
+            &create_custom_event(1, -1, "", vec![]),
+            now
+        ));
+    }
+
+    #[test]
+    fn test_take_due_returns_reached_events_in_created_at_order() {
+        let held = HeldEvents::new(10);
+        for created_at in [1300, 1100, 1200] {
+            assert!(
+                held.hold(create_custom_event(1, created_at, "", vec![]))
+                    .is_none()
+            );
+        }
+
+        assert!(held.take_due(1000).is_empty());
+        let due: Vec<i64> = held
+            .take_due(1200)
+            .iter()
+            .map(|event| event.created_at.as_i64())
+            .collect();
+        assert_eq!(due, vec![1100, 1200]);
+        assert_eq!(held.len(), 1);
+
+        assert_eq!(held.take_due(2000).len(), 1);
+        assert_eq!(held.len(), 0);
+    }
+
+    #[test]
+    fn test_hold_rejects_when_full() {
+        let held = HeldEvents::new(2);
+        for created_at in [1100, 1200] {
+            assert!(
+                held.hold(create_custom_event(1, created_at, "", vec![]))
+                    .is_none()
+            );
+        }
+
+        let overflow = create_custom_event(1, 1300, "", vec![]);
+        assert_eq!(held.hold(overflow.clone()), Some(overflow));
+        assert_eq!(held.len(), 2);
+
+        // 解除して空きができれば再び保留できる
+        assert_eq!(held.take_due(1100).len(), 1);
+        assert!(
+            held.hold(create_custom_event(1, 1300, "", vec![]))
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! 保存処理中のイベントID（同一イベントの並行処理の排除用）
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use tokio::sync::watch;
+
+use crate::models::EventId;
+use crate::store::SaveResult;
+
+/// 保存処理中のイベントIDと、その処理結果の通知先
+#[derive(Default)]
+pub(super) struct InFlightEvents {
+    ids: Mutex<HashMap<EventId, watch::Receiver<Option<SaveResult>>>>,
+}
+
+/// `InFlightEvents::acquire` の結果
+pub(super) enum InFlight<'a> {
+    /// 処理を開始した（保存できたら `InFlightGuard::finish` で結果を知らせる）
+    Acquired(InFlightGuard<'a>),
+    /// 同じイベントを別の処理が処理中（`InFlightWaiter::wait` で結果を待つ）
+    Busy(InFlightWaiter),
+}
+
+impl InFlightEvents {
+    fn ids(&self) -> MutexGuard<'_, HashMap<EventId, watch::Receiver<Option<SaveResult>>>> {
+        // HashMap への追加・削除だけなので、poison されても中身は壊れない
+        self.ids.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// イベントIDの処理を開始する。既に処理中なら、その結果を待つための `Busy`
+    ///
+    /// 返したガードを drop すると処理中の記録が外れるため、保存エラーや
+    /// 接続切断による future の破棄でも記録が残り続けることはない。
+    pub(super) fn acquire(&self, id: EventId) -> InFlight<'_> {
+        let mut ids = self.ids();
+        if let Some(rx) = ids.get(&id) {
+            return InFlight::Busy(InFlightWaiter { rx: rx.clone() });
+        }
+        let (tx, rx) = watch::channel(None);
+        ids.insert(id, rx);
+        drop(ids);
+        InFlight::Acquired(InFlightGuard {
+            in_flight: self,
+            id,
+            tx,
+        })
+    }
+
+    /// 処理中のイベント数
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.ids().len()
+    }
+}
+
+/// 処理中の記録を保持するガード（drop で解放）
+pub(super) struct InFlightGuard<'a> {
+    in_flight: &'a InFlightEvents,
+    id: EventId,
+    tx: watch::Sender<Option<SaveResult>>,
+}
+
+impl InFlightGuard<'_> {
+    /// 保存の結果を待機中の処理に知らせて、処理中の記録を外す
+    pub(super) fn finish(self, result: &SaveResult) {
+        self.tx.send_replace(Some(result.clone()));
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.ids().remove(&self.id);
+    }
+}
+
+/// 処理中の同じイベントの結果を待つ
+pub(super) struct InFlightWaiter {
+    rx: watch::Receiver<Option<SaveResult>>,
+}
+
+impl InFlightWaiter {
+    /// 先行の処理の保存結果を待つ
+    ///
+    /// 先行の処理が保存に失敗した、または future ごと破棄された場合は `None`。
+    pub(super) async fn wait(mut self) -> Option<SaveResult> {
+        self.rx
+            .wait_for(Option::is_some)
+            .await
+            .ok()
+            .and_then(|result| result.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_is_exclusive_until_guard_dropped() {
+        let in_flight = InFlightEvents::default();
+        let id = EventId::from_bytes([0x01; 32]);
+
+        let InFlight::Acquired(guard) = in_flight.acquire(id) else {
+            panic!("最初の処理は開始できる");
+        };
+        let InFlight::Busy(waiter) = in_flight.acquire(id) else {
+            panic!("処理中のイベントは開始できない");
+        };
+        // 別のイベントは並行して処理できる
+        assert!(matches!(
+            in_flight.acquire(EventId::from_bytes([0x02; 32])),
+            InFlight::Acquired(_)
+        ));
+
+        // 結果を知らせずに解放した場合（保存エラー・future の破棄）は結果なし
+        drop(guard);
+        assert_eq!(waiter.wait().await, None);
+        assert_eq!(in_flight.len(), 0);
+        assert!(matches!(in_flight.acquire(id), InFlight::Acquired(_)));
+    }
+
+    #[tokio::test]
+    async fn test_waiter_receives_result() {
+        let in_flight = InFlightEvents::default();
+        let id = EventId::from_bytes([0x01; 32]);
+
+        let InFlight::Acquired(guard) = in_flight.acquire(id) else {
+            panic!("最初の処理は開始できる");
+        };
+        let InFlight::Busy(waiter) = in_flight.acquire(id) else {
+            panic!("処理中のイベントは開始できない");
+        };
+
+        guard.finish(&SaveResult::Saved);
+        assert_eq!(waiter.wait().await, Some(SaveResult::Saved));
+        assert_eq!(in_flight.len(), 0);
+    }
+}
@@ -0,0 +1,163 @@
+//! 直近に処理したイベントIDの短期記録（重複受信のショートサーキット用）
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::models::EventId;
+
+/// 重複受信の短期記録の設定
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    /// 記録を保持する期間。過ぎたら通常どおりストアで判定する
+    pub ttl: Duration,
+    /// 記録するイベントIDの最大数。超えたら古いものから捨てる（0 で無効）
+    pub capacity: usize,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(60),
+            capacity: 10_000,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    /// イベントID -> 記録時刻
+    recorded_at: HashMap<EventId, Instant>,
+    /// 記録順（期限切れ・容量超過時に古いものから捨てる）
+    order: VecDeque<(Instant, EventId)>,
+}
+
+/// 直近に保存済みと判定したイベントIDの集合
+pub(super) struct RecentEventIds {
+    config: DedupConfig,
+    entries: Mutex<Entries>,
+}
+
+impl RecentEventIds {
+    pub(super) fn new(config: DedupConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        // 記録は捨てても困らないので、poison されても中身をそのまま使う
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// TTL 内に記録されたイベントIDか
+    pub(super) fn contains(&self, id: &EventId) -> bool {
+        self.entries()
+            .recorded_at
+            .get(id)
+            .is_some_and(|at| at.elapsed() < self.config.ttl)
+    }
+
+    /// イベントIDを記録する
+    pub(super) fn insert(&self, id: EventId) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries();
+        let Entries { recorded_at, order } = &mut *entries;
+
+        // 期限切れと容量超過分を古い順に捨てる
+        while let Some((at, oldest)) = order.front().copied() {
+            let expired = now.duration_since(at) >= self.config.ttl;
+            if !expired && recorded_at.len() < self.config.capacity {
+                break;
+            }
+            order.pop_front();
+            // 再記録で時刻が更新されている場合は、新しい方の記録を残す
+            if recorded_at.get(&oldest) == Some(&at) {
+                recorded_at.remove(&oldest);
+            }
+        }
+
+        recorded_at.insert(id, now);
+        order.push_back((now, id));
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries().recorded_at.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_id(n: u8) -> EventId {
+        EventId::from_bytes([n; 32])
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_contains_until_ttl_expires() {
+        let recent = RecentEventIds::new(DedupConfig {
+            ttl: Duration::from_secs(10),
+            capacity: 100,
+        });
+        recent.insert(event_id(1));
+        assert!(recent.contains(&event_id(1)));
+        assert!(!recent.contains(&event_id(2)));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(!recent.contains(&event_id(1)));
+
+        // 期限切れの記録は次の記録時に捨てられる
+        recent.insert(event_id(2));
+        assert_eq!(recent.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_capacity_evicts_oldest() {
+        let recent = RecentEventIds::new(DedupConfig {
+            ttl: Duration::from_secs(60),
+            capacity: 2,
+        });
+        for n in 1..=3 {
+            recent.insert(event_id(n));
+            tokio::time::advance(Duration::from_millis(10)).await;
+        }
+        assert_eq!(recent.len(), 2);
+        assert!(!recent.contains(&event_id(1)));
+        assert!(recent.contains(&event_id(2)));
+        assert!(recent.contains(&event_id(3)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reinsert_refreshes_timestamp() {
+        let recent = RecentEventIds::new(DedupConfig {
+            ttl: Duration::from_secs(10),
+            capacity: 100,
+        });
+        recent.insert(event_id(1));
+        tokio::time::advance(Duration::from_secs(6)).await;
+        recent.insert(event_id(1));
+        tokio::time::advance(Duration::from_secs(6)).await;
+
+        // 古い方の記録が期限切れになっても、再記録した分は残る
+        recent.insert(event_id(2));
+        assert!(recent.contains(&event_id(1)));
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_recording() {
+        let recent = RecentEventIds::new(DedupConfig {
+            ttl: Duration::from_secs(60),
+            capacity: 0,
+        });
+        recent.insert(event_id(1));
+        assert!(!recent.contains(&event_id(1)));
+    }
+}
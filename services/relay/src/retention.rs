@@ -0,0 +1,246 @@
+//! kind ごとのイベント保持期間（retention policy）
+//!
+//! 環境変数 `RELAY_RETENTION_POLICIES` で設定する。
+//! 形式は `<kind または kind範囲>:<保持秒数>` のカンマ区切り
+//! （例: `1:2592000,30000-39999:86400`）。未設定の場合は無期限に保持する。
+
+use std::env;
+use std::ops::RangeInclusive;
+
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::models::{Event, Kind};
+
+/// 保持期間の環境変数名
+const ENV_RETENTION_POLICIES: &str = "RELAY_RETENTION_POLICIES";
+/// 期限切れイベント削除の実行間隔の環境変数名
+const ENV_RETENTION_PURGE_INTERVAL_SECS: &str = "RELAY_RETENTION_PURGE_INTERVAL_SECS";
+/// 期限切れイベント削除の実行間隔（秒）（1時間）
+pub const DEFAULT_RETENTION_PURGE_INTERVAL_SECS: u64 = 3600;
+
+/// kind 範囲ごとの保持期間
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// 対象の kind 範囲
+    pub kind_range: RangeInclusive<u16>,
+    /// created_at からの保持秒数
+    pub max_age_secs: u64,
+}
+
+/// retention policy のパースエラー
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RetentionPolicyParseError {
+    /// `<kind>:<秒数>` 形式でない
+    #[error("保持期間の形式が不正です: {0}（<kind>:<秒数> 形式）")]
+    InvalidFormat(String),
+    /// kind が数値でない、または範囲が逆転している
+    #[error("kind の指定が不正です: {0}")]
+    InvalidKind(String),
+    /// 秒数が数値でない
+    #[error("保持秒数が不正です: {0}")]
+    InvalidMaxAge(String),
+}
+
+impl RetentionPolicy {
+    /// kind がこのポリシーの対象か
+    pub fn applies_to(&self, kind: Kind) -> bool {
+        self.kind_range.contains(&kind.as_u16())
+    }
+
+    /// このポリシーでのイベントの有効期限（UNIX秒）。対象外なら `None`
+    pub fn expires_at(&self, event: &Event) -> Option<i64> {
+        if !self.applies_to(event.kind) {
+            return None;
+        }
+        let max_age = i64::try_from(self.max_age_secs).unwrap_or(i64::MAX);
+        Some(event.created_at.as_i64().saturating_add(max_age))
+    }
+}
+
+/// 該当するポリシーのうち最も早い有効期限（UNIX秒）。対象ポリシーがなければ `None`
+pub fn expires_at(policies: &[RetentionPolicy], event: &Event) -> Option<i64> {
+    policies.iter().filter_map(|p| p.expires_at(event)).min()
+}
+
+/// イベントが `now` 時点で保持期間を過ぎているか
+pub fn is_expired(policies: &[RetentionPolicy], event: &Event, now: u64) -> bool {
+    let now = i64::try_from(now).unwrap_or(i64::MAX);
+    expires_at(policies, event).is_some_and(|expires_at| expires_at < now)
+}
+
+/// `1:2592000,30000-39999:86400` 形式の設定をパースする
+pub fn parse_policies(value: &str) -> Result<Vec<RetentionPolicy>, RetentionPolicyParseError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(parse_policy)
+        .collect()
+}
+
+fn parse_policy(entry: &str) -> Result<RetentionPolicy, RetentionPolicyParseError> {
+    let (kinds, max_age) = entry
+        .split_once(':')
+        .ok_or_else(|| RetentionPolicyParseError::InvalidFormat(entry.to_string()))?;
+
+    let parse_kind = |s: &str| {
+        s.trim()
+            .parse::<u16>()
+            .map_err(|_| RetentionPolicyParseError::InvalidKind(kinds.to_string()))
+    };
+    let kind_range = match kinds.split_once('-') {
+        Some((start, end)) => parse_kind(start)?..=parse_kind(end)?,
+        None => {
+            let kind = parse_kind(kinds)?;
+            kind..=kind
+        }
+    };
+    if kind_range.is_empty() {
+        return Err(RetentionPolicyParseError::InvalidKind(kinds.to_string()));
+    }
+
+    let max_age_secs = max_age
+        .trim()
+        .parse()
+        .map_err(|_| RetentionPolicyParseError::InvalidMaxAge(max_age.to_string()))?;
+
+    Ok(RetentionPolicy {
+        kind_range,
+        max_age_secs,
+    })
+}
+
+/// 環境変数から retention policy を読み込む
+///
+/// 未設定なら空（無期限保持）。不正な値の場合も誤削除を避けるため空とする。
+pub fn policies_from_env() -> Vec<RetentionPolicy> {
+    let Ok(value) = env::var(ENV_RETENTION_POLICIES) else {
+        return Vec::new();
+    };
+    match parse_policies(&value) {
+        Ok(policies) => {
+            info!(policies = ?policies, "保持期間設定を読み込みました");
+            policies
+        }
+        Err(e) => {
+            warn!(key = ENV_RETENTION_POLICIES, value = %value, error = %e, "保持期間設定が不正です。保持期間は適用しません");
+            Vec::new()
+        }
+    }
+}
+
+/// 期限切れイベント削除の実行間隔を環境変数から読み込む
+pub fn purge_interval_from_env() -> std::time::Duration {
+    let secs = env::var(ENV_RETENTION_PURGE_INTERVAL_SECS)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_RETENTION_PURGE_INTERVAL_SECS);
+    std::time::Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::create_custom_event;
+    use serial_test::serial;
+
+    fn policy(kind_range: RangeInclusive<u16>, max_age_secs: u64) -> RetentionPolicy {
+        RetentionPolicy {
+            kind_range,
+            max_age_secs,
+        }
+    }
+
+    // ========== パース ==========
+
+    #[test]
+    fn test_parse_single_kind_and_range() {
+        let policies = parse_policies("1:2592000, 30000-39999:86400").unwrap();
+        assert_eq!(
+            policies,
+            vec![policy(1..=1, 2592000), policy(30000..=39999, 86400)]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_string() {
+        assert_eq!(parse_policies("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_invalid_entries() {
+        assert!(matches!(
+            parse_policies("1"),
+            Err(RetentionPolicyParseError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            parse_policies("abc:10"),
+            Err(RetentionPolicyParseError::InvalidKind(_))
+        ));
+        assert!(matches!(
+            parse_policies("100-1:10"),
+            Err(RetentionPolicyParseError::InvalidKind(_))
+        ));
+        assert!(matches!(
+            parse_policies("1:-10"),
+            Err(RetentionPolicyParseError::InvalidMaxAge(_))
+        ));
+    }
+
+    // ========== 期限判定 ==========
+
+    #[test]
+    fn test_is_expired() {
+        let policies = vec![policy(1..=1, 100)];
+        let event = create_custom_event(1, 1000, "note", vec![]);
+
+        // 期限ちょうどはまだ保持
+        assert!(!is_expired(&policies, &event, 1100));
+        assert!(is_expired(&policies, &event, 1101));
+    }
+
+    #[test]
+    fn test_is_expired_ignores_other_kinds() {
+        let policies = vec![policy(1..=1, 100)];
+        let event = create_custom_event(7, 1000, "reaction", vec![]);
+        assert!(!is_expired(&policies, &event, u64::MAX));
+    }
+
+    #[test]
+    fn test_expires_at_uses_shortest_policy() {
+        let policies = vec![policy(0..=10, 1000), policy(1..=1, 100)];
+        let event = create_custom_event(1, 1000, "note", vec![]);
+        assert_eq!(expires_at(&policies, &event), Some(1100));
+    }
+
+    #[test]
+    fn test_expires_at_saturates() {
+        let policies = vec![policy(1..=1, u64::MAX)];
+        let event = create_custom_event(1, 1000, "note", vec![]);
+        assert_eq!(expires_at(&policies, &event), Some(i64::MAX));
+    }
+
+    // ========== 環境変数 ==========
+
+    #[test]
+    #[serial]
+    fn test_policies_from_env() {
+        unsafe {
+            env::set_var(ENV_RETENTION_POLICIES, "1:60");
+        }
+        assert_eq!(policies_from_env(), vec![policy(1..=1, 60)]);
+
+        // 不正な値は適用しない
+        unsafe {
+            env::set_var(ENV_RETENTION_POLICIES, "1:60,invalid");
+        }
+        assert!(policies_from_env().is_empty());
+
+        unsafe {
+            env::remove_var(ENV_RETENTION_POLICIES);
+        }
+        assert!(policies_from_env().is_empty());
+    }
+}
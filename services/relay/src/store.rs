@@ -3,20 +3,24 @@
 //! - `EventStore` trait: ストレージの抽象インターフェース
 //! - `InMemoryEventStore`: インメモリ実装（開発・テスト用）
 //! - `DynamoEventStore`: DynamoDB永続化実装（本番用、`dynamo` feature有効時のみ）
+//! - `CachedEventStore`: クエリ結果を短時間キャッシュするデコレータ
 
+mod cached;
 #[cfg(feature = "dynamo")]
 mod dynamo;
 mod in_memory;
 
 // Re-exports
+pub use cached::{CacheInvalidation, CachedEventStore, QueryCacheConfig};
 #[cfg(feature = "dynamo")]
 pub use dynamo::DynamoEventStore;
 pub use in_memory::InMemoryEventStore;
 
 use std::sync::Arc;
 
-use crate::models::{Event, Filter, VerifiedEvent};
+use crate::models::{Event, EventId, Filter, VerifiedEvent};
 use crate::owner_priority::OwnerPriority;
+use crate::retention::RetentionPolicy;
 
 #[cfg(feature = "dynamo")]
 use tracing::debug;
@@ -35,7 +39,10 @@ pub enum SaveResult {
     /// Ephemeral イベント（保存せず配信のみ）
     Ephemeral,
     /// 置換（既存イベントを上書き）
-    Replaced,
+    Replaced {
+        /// 置換されて削除された旧イベントのID
+        old_id: EventId,
+    },
 }
 
 /// 削除処理の結果
@@ -52,6 +59,15 @@ pub enum StoreError {
     #[allow(dead_code)]
     #[error("内部エラー: {0}")]
     Internal(String),
+
+    /// イベントがストレージの1件あたりのサイズ上限を超える（DynamoDB のアイテム上限など）
+    #[error("event too large for storage ({size} bytes, max {max})")]
+    EventTooLarge {
+        /// 保存しようとしたデータの見積もりサイズ（バイト）
+        size: usize,
+        /// ストレージの上限（バイト）
+        max: usize,
+    },
 }
 
 /// イベントストレージの抽象インターフェース
@@ -68,34 +84,54 @@ pub trait EventStore: Send + Sync {
 
     /// 削除リクエスト(kind 5)を処理し、参照されたイベントを削除
     async fn delete(&self, event: &VerifiedEvent) -> Result<DeleteResult, StoreError>;
+
+    /// 保持期間（retention policy）を過ぎたイベントを削除し、削除件数を返す
+    async fn purge_expired(
+        &self,
+        policies: &[RetentionPolicy],
+        now: u64,
+    ) -> Result<u64, StoreError>;
 }
 
 /// feature flagによるEventStore型の切り替え（静的ディスパッチ）
+///
+/// いずれもクエリキャッシュで包む。
 #[cfg(feature = "dynamo")]
-pub type AppEventStore = DynamoEventStore;
+pub type AppEventStore = CachedEventStore<DynamoEventStore>;
 #[cfg(not(feature = "dynamo"))]
-pub type AppEventStore = InMemoryEventStore;
+pub type AppEventStore = CachedEventStore<InMemoryEventStore>;
 
 /// EventStoreのファクトリ関数（feature flagによる切り替え）
 ///
-/// ストアとオーナー優先度のペアを返す。
+/// ストアとオーナー優先度のペアを返す。キャッシュ設定は `QueryCacheConfig::from_env` で読み込む。
 /// オーナー優先度はWebSocketハンドラでcreated_atバリデーションの免除判定に使用する。
-pub async fn create_event_store() -> Result<(AppEventStore, Arc<OwnerPriority>), StoreError> {
+/// `retention_policies` は DynamoDB 使用時に TTL 属性の付与に使う
+/// （InMemory は `purge_expired` の呼び出しごとにポリシーを受け取るため不要）。
+pub async fn create_event_store(
+    retention_policies: Vec<RetentionPolicy>,
+) -> Result<(AppEventStore, Arc<OwnerPriority>), StoreError> {
     #[cfg(feature = "dynamo")]
     {
         let table_name = std::env::var("DYNAMODB_TABLE_NAME")
             .unwrap_or_else(|_| "nostr_relay_events".to_string());
 
         debug!("DynamoEventStoreを初期化中 (table: {})", table_name);
-        let store = DynamoEventStore::new(table_name).await?;
+        let store = DynamoEventStore::new(table_name, retention_policies).await?;
         let owner_priority = store.owner_priority();
-        Ok((store, owner_priority))
+        Ok((
+            CachedEventStore::new(store, QueryCacheConfig::from_env()),
+            owner_priority,
+        ))
     }
 
     #[cfg(not(feature = "dynamo"))]
     {
         debug!("InMemoryEventStoreを初期化中");
+        let _ = retention_policies;
         let owner_priority = Arc::new(OwnerPriority::new(std::env::var("RELAY_PUBKEY").ok()));
-        Ok((InMemoryEventStore::new(), owner_priority))
+        Ok((
+            CachedEventStore::new(InMemoryEventStore::new(), QueryCacheConfig::from_env()),
+            owner_priority,
+        ))
     }
 }
@@ -0,0 +1,732 @@
+//! クエリ結果を短時間キャッシュする EventStore デコレータ
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::{instrument, trace, warn};
+
+use super::{DeleteResult, EventStore, SaveResult, StoreError};
+use crate::models::{Event, EventId, Filter, VerifiedEvent};
+use crate::retention::RetentionPolicy;
+
+/// キャッシュの有効期間（ミリ秒）の環境変数名
+const ENV_QUERY_CACHE_TTL_MS: &str = "RELAY_QUERY_CACHE_TTL_MS";
+/// 保持するフィルタ（キー）の最大数の環境変数名（0 でキャッシュ無効）
+const ENV_QUERY_CACHE_MAX_ENTRIES: &str = "RELAY_QUERY_CACHE_MAX_ENTRIES";
+/// 全エントリで保持するイベント数の上限の環境変数名
+const ENV_QUERY_CACHE_MAX_TOTAL_EVENTS: &str = "RELAY_QUERY_CACHE_MAX_TOTAL_EVENTS";
+/// 1エントリ（1回のクエリ結果）としてキャッシュするイベント数の上限の環境変数名
+const ENV_QUERY_CACHE_MAX_ENTRY_EVENTS: &str = "RELAY_QUERY_CACHE_MAX_ENTRY_EVENTS";
+/// 無効化方式の環境変数名（`on_write` / `ttl_only`）
+const ENV_QUERY_CACHE_INVALIDATION: &str = "RELAY_QUERY_CACHE_INVALIDATION";
+
+/// 新規イベント保存時のキャッシュ無効化方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheInvalidation {
+    /// 保存したイベントにマッチするフィルタのキャッシュを破棄する
+    OnWrite,
+    /// 保存では破棄せず、TTL が切れるまで古い結果を返すことを許容する
+    TtlOnly,
+}
+
+/// クエリキャッシュの設定
+#[derive(Debug, Clone)]
+pub struct QueryCacheConfig {
+    /// キャッシュの有効期間
+    pub ttl: Duration,
+    /// 保持するフィルタ（キー）の最大数。超えたら最も古いエントリから捨てる
+    pub max_entries: usize,
+    /// 全エントリで保持するイベント数の合計の上限（メモリ使用量の上限）
+    ///
+    /// 超える場合は最も古いエントリから捨てる。
+    pub max_total_events: usize,
+    /// 1エントリとしてキャッシュするイベント数の上限。これより大きい結果はキャッシュしない
+    pub max_entry_events: usize,
+    /// 新規イベント保存時の無効化方式
+    pub invalidation: CacheInvalidation,
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(3),
+            max_entries: 1024,
+            max_total_events: 50_000,
+            max_entry_events: 1000,
+            invalidation: CacheInvalidation::OnWrite,
+        }
+    }
+}
+
+impl QueryCacheConfig {
+    /// 環境変数から設定を読み込む（未設定・不正な値はデフォルト）
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let ttl = parse_env(ENV_QUERY_CACHE_TTL_MS, |v| v.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(default.ttl);
+        let max_entries = parse_env(ENV_QUERY_CACHE_MAX_ENTRIES, |v| v.parse().ok())
+            .unwrap_or(default.max_entries);
+        let max_total_events = parse_env(ENV_QUERY_CACHE_MAX_TOTAL_EVENTS, |v| v.parse().ok())
+            .unwrap_or(default.max_total_events);
+        let max_entry_events = parse_env(ENV_QUERY_CACHE_MAX_ENTRY_EVENTS, |v| v.parse().ok())
+            .unwrap_or(default.max_entry_events);
+        let invalidation = parse_env(ENV_QUERY_CACHE_INVALIDATION, |v| match v {
+            "on_write" => Some(CacheInvalidation::OnWrite),
+            "ttl_only" => Some(CacheInvalidation::TtlOnly),
+            _ => None,
+        })
+        .unwrap_or(default.invalidation);
+        Self {
+            ttl,
+            max_entries,
+            max_total_events,
+            max_entry_events,
+            invalidation,
+        }
+    }
+}
+
+/// 環境変数を読み込む。不正な値は警告して `None`
+fn parse_env<T>(key: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    let value = env::var(key).ok()?;
+    let parsed = parse(value.trim());
+    if parsed.is_none() {
+        warn!(key, value = %value, "クエリキャッシュの設定が不正です。デフォルト値を使用します");
+    }
+    parsed
+}
+
+struct CacheEntry {
+    /// 無効化判定用に元のフィルタを保持する
+    filters: Vec<Filter>,
+    events: Vec<Event>,
+    /// `CacheState::by_age` のキー（挿入時刻と挿入順の通し番号）
+    age_key: (Instant, u64),
+}
+
+impl CacheEntry {
+    fn inserted_at(&self) -> Instant {
+        self.age_key.0
+    }
+}
+
+/// キャッシュの中身（エントリと、古い順に並べたキー）
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// 古い順のキー。期限切れの掃除と上限超過時の追い出しを先頭から行う
+    by_age: BTreeMap<(Instant, u64), String>,
+    /// 全エントリのイベント数の合計
+    total_events: usize,
+    /// 挿入順の通し番号（同じ時刻に挿入したエントリを区別する）
+    next_seq: u64,
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.by_age.remove(&entry.age_key);
+            self.total_events -= entry.events.len();
+        }
+    }
+
+    /// `keep` が偽のエントリを破棄する
+    fn retain(&mut self, mut keep: impl FnMut(&CacheEntry) -> bool) {
+        let removed: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| !keep(entry))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in removed {
+            self.remove(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.by_age.clear();
+        self.total_events = 0;
+    }
+
+    /// 最も古いエントリを破棄する。空なら `false`
+    fn remove_oldest(&mut self) -> bool {
+        let Some((_, key)) = self.by_age.pop_first() else {
+            return false;
+        };
+        if let Some(entry) = self.entries.remove(&key) {
+            self.total_events -= entry.events.len();
+        }
+        true
+    }
+
+    /// 期限切れのエントリを古い順に破棄する（期限内のエントリに当たったら止める）
+    fn remove_expired(&mut self, now: Instant, ttl: Duration) {
+        while let Some((&(inserted_at, _), _)) = self.by_age.first_key_value()
+            && now.duration_since(inserted_at) >= ttl
+        {
+            self.remove_oldest();
+        }
+    }
+
+    fn insert(&mut self, key: String, filters: &[Filter], events: &[Event], now: Instant) {
+        self.remove(&key);
+        let age_key = (now, self.next_seq);
+        self.next_seq += 1;
+        self.total_events += events.len();
+        self.by_age.insert(age_key, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                filters: filters.to_vec(),
+                events: events.to_vec(),
+                age_key,
+            },
+        );
+    }
+}
+
+/// クエリ結果キャッシュ付きの EventStore
+///
+/// 同じタイムラインを多数のクライアントが同時に購読する場合に、
+/// 正規化したフィルタ（limit を含む）が同じ REQ の結果を TTL の間使い回す。
+pub struct CachedEventStore<S: EventStore> {
+    inner: S,
+    config: QueryCacheConfig,
+    state: Mutex<CacheState>,
+    /// 無効化のたびに進める世代番号
+    ///
+    /// 内部ストアへのクエリ中に無効化が起きた場合、その結果は古い可能性があるため
+    /// キャッシュに入れない。
+    generation: AtomicU64,
+}
+
+impl<S: EventStore> CachedEventStore<S> {
+    /// 既存のストアをキャッシュで包む
+    pub fn new(inner: S, config: QueryCacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(CacheState::default()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// 内部のEventStoreへの参照を返す
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// 現在キャッシュしているフィルタ（キー）の数
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    /// キャッシュが空かどうか
+    pub fn is_empty(&self) -> bool {
+        self.state().entries.is_empty()
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        // キャッシュは捨てても困らないので、poison されても中身をそのまま使う
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 無効化用にロックを取り、世代番号を進める
+    ///
+    /// 世代番号はロック中に進めるので、`insert` の世代確認との間に無効化が割り込むことはない。
+    fn state_for_invalidation(&self) -> MutexGuard<'_, CacheState> {
+        let state = self.state();
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        state
+    }
+
+    /// 全エントリを破棄する
+    fn clear(&self) {
+        self.state_for_invalidation().clear();
+    }
+
+    /// 保存したイベントにマッチするフィルタのエントリを破棄する
+    fn invalidate_matching(&self, event: &Event) {
+        self.state_for_invalidation()
+            .retain(|entry| !entry.filters.iter().any(|f| f.matches(event)));
+    }
+
+    /// 置換で保存したイベントにマッチするか、置換された旧イベントを含むエントリを破棄する
+    fn invalidate_replaced(&self, event: &Event, old_id: &EventId) {
+        self.state_for_invalidation().retain(|entry| {
+            !entry.filters.iter().any(|f| f.matches(event))
+                && !entry.events.iter().any(|e| e.id == *old_id)
+        });
+    }
+
+    /// クエリ結果をキャッシュに入れる
+    ///
+    /// `generation` はクエリ開始前に読んだ世代番号。その後に無効化が起きていれば入れない。
+    /// エントリ数・イベント数の上限を超える分は古いエントリから捨てる。
+    fn insert(&self, key: String, filters: &[Filter], events: &[Event], generation: u64) {
+        if self.config.max_entries == 0
+            || events.len() > self.config.max_entry_events
+            || events.len() > self.config.max_total_events
+        {
+            trace!(
+                result_count = events.len(),
+                "結果が大きいためキャッシュしない"
+            );
+            return;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state();
+        if self.generation.load(Ordering::Relaxed) != generation {
+            trace!("クエリ中に無効化されたため結果をキャッシュしない");
+            return;
+        }
+        state.remove_expired(now, self.config.ttl);
+        state.remove(&key);
+        while state.entries.len() >= self.config.max_entries
+            || state.total_events + events.len() > self.config.max_total_events
+        {
+            if !state.remove_oldest() {
+                break;
+            }
+        }
+        state.insert(key, filters, events, now);
+    }
+}
+
+/// フィルタ列からキャッシュキーを作る（フィルタの順序・重複と各条件の並びは問わない）
+fn cache_key(filters: &[Filter]) -> String {
+    let mut keys: Vec<String> = filters
+        .iter()
+        .map(|f| serde_json::to_string(&f.normalized()).unwrap_or_default())
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys.join("\n")
+}
+
+impl<S: EventStore> EventStore for CachedEventStore<S> {
+    async fn save(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+        let result = self.inner.save(event).await?;
+        if self.config.invalidation == CacheInvalidation::OnWrite {
+            match result {
+                SaveResult::Saved => self.invalidate_matching(event),
+                // 新イベントにマッチするエントリに加え、置換された旧イベントを含むエントリも破棄
+                SaveResult::Replaced { old_id } => self.invalidate_replaced(event, &old_id),
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+
+    #[instrument(skip(self, filters), fields(filter_count = filters.len()))]
+    async fn query(&self, filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+        let key = cache_key(filters);
+        if let Some(entry) = self.state().entries.get(&key)
+            && entry.inserted_at().elapsed() < self.config.ttl
+        {
+            trace!(result_count = entry.events.len(), "クエリキャッシュヒット");
+            return Ok(entry.events.clone());
+        }
+
+        trace!("クエリキャッシュミス");
+        let generation = self.generation.load(Ordering::Relaxed);
+        let events = self.inner.query(filters).await?;
+        self.insert(key, filters, &events, generation);
+        Ok(events)
+    }
+
+    async fn delete(&self, event: &VerifiedEvent) -> Result<DeleteResult, StoreError> {
+        let result = self.inner.delete(event).await?;
+        // 削除はTTLを待たずに反映する（無効化方式によらない）
+        if result.deleted_count > 0 {
+            self.clear();
+        }
+        Ok(result)
+    }
+
+    async fn purge_expired(
+        &self,
+        policies: &[RetentionPolicy],
+        now: u64,
+    ) -> Result<u64, StoreError> {
+        let purged = self.inner.purge_expired(policies, now).await?;
+        if purged > 0 {
+            self.clear();
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::store::InMemoryEventStore;
+    use crate::test_helpers::{create_custom_event, create_test_event};
+
+    /// クエリを外から再開させるまで止めておくストア（クエリ中の保存を再現する）
+    #[derive(Default)]
+    struct PausedQueryStore {
+        inner: InMemoryEventStore,
+        query_started: Notify,
+        resume_query: Notify,
+    }
+
+    impl EventStore for PausedQueryStore {
+        async fn save(&self, event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            self.inner.save(event).await
+        }
+
+        async fn query(&self, filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            // 保存前の状態で結果を確定させてから止める
+            let events = self.inner.query(filters).await;
+            self.query_started.notify_one();
+            self.resume_query.notified().await;
+            events
+        }
+
+        async fn delete(&self, event: &VerifiedEvent) -> Result<DeleteResult, StoreError> {
+            self.inner.delete(event).await
+        }
+
+        async fn purge_expired(
+            &self,
+            policies: &[RetentionPolicy],
+            now: u64,
+        ) -> Result<u64, StoreError> {
+            self.inner.purge_expired(policies, now).await
+        }
+    }
+
+    fn cached_store(config: QueryCacheConfig) -> CachedEventStore<InMemoryEventStore> {
+        CachedEventStore::new(InMemoryEventStore::new(), config)
+    }
+
+    fn parse_filters(json: &str) -> Vec<Filter> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query_hits_cache_for_equivalent_filters() {
+        let store = cached_store(QueryCacheConfig {
+            invalidation: CacheInvalidation::TtlOnly,
+            ..Default::default()
+        });
+        let first = create_custom_event(1, 1000, "first", vec![]);
+        store.save(&first.clone().verify().unwrap()).await.unwrap();
+
+        let results = store
+            .query(&parse_filters(r#"[{"kinds": [1, 7]}, {"kinds": [0]}]"#))
+            .await
+            .unwrap();
+        assert_eq!(results, vec![first.clone()]);
+        assert_eq!(store.len(), 1);
+
+        // 内部ストアにだけ追加する（キャッシュは無効化されない）
+        let second = create_custom_event(1, 2000, "second", vec![]);
+        store.inner().save(&second.verify().unwrap()).await.unwrap();
+
+        // フィルタの順序・値の並びが違っても同じキー → キャッシュヒットで古い結果
+        let results = store
+            .query(&parse_filters(r#"[{"kinds": [0]}, {"kinds": [7, 1, 1]}]"#))
+            .await
+            .unwrap();
+        assert_eq!(results, vec![first]);
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_misses_cache_for_different_limit() {
+        let store = cached_store(QueryCacheConfig::default());
+
+        store
+            .query(&parse_filters(r#"[{"kinds": [1], "limit": 10}]"#))
+            .await
+            .unwrap();
+        store
+            .query(&parse_filters(r#"[{"kinds": [1], "limit": 20}]"#))
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_query_cache_expires_after_ttl() {
+        let store = cached_store(QueryCacheConfig {
+            ttl: Duration::from_secs(3),
+            invalidation: CacheInvalidation::TtlOnly,
+            ..Default::default()
+        });
+        let filters = parse_filters(r#"[{"kinds": [1]}]"#);
+        assert!(store.query(&filters).await.unwrap().is_empty());
+
+        let event = create_test_event();
+        store
+            .inner()
+            .save(&event.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert!(store.query(&filters).await.unwrap().is_empty());
+
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert_eq!(store.query(&filters).await.unwrap(), vec![event]);
+    }
+
+    #[tokio::test]
+    async fn test_save_invalidates_matching_entries_on_write() {
+        let store = cached_store(QueryCacheConfig::default());
+        let notes = parse_filters(r#"[{"kinds": [1]}]"#);
+        let reactions = parse_filters(r#"[{"kinds": [7]}]"#);
+        store.query(&notes).await.unwrap();
+        store.query(&reactions).await.unwrap();
+        assert_eq!(store.len(), 2);
+
+        let event = create_test_event();
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+
+        // kind 1 のエントリだけが破棄される
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.query(&notes).await.unwrap(), vec![event]);
+    }
+
+    #[tokio::test]
+    async fn test_query_result_not_cached_when_invalidated_during_query() {
+        let store = CachedEventStore::new(PausedQueryStore::default(), QueryCacheConfig::default());
+        let filters = parse_filters(r#"[{"kinds": [1]}]"#);
+        let event = create_test_event();
+
+        let (stale, ()) = tokio::join!(store.query(&filters), async {
+            store.inner().query_started.notified().await;
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+            store.inner().resume_query.notify_one();
+        });
+
+        // クエリ自体は保存前の結果を返すが、キャッシュには残らない
+        assert!(stale.unwrap().is_empty());
+        assert!(store.is_empty());
+
+        let (fresh, ()) = tokio::join!(store.query(&filters), async {
+            store.inner().resume_query.notify_one();
+        });
+        assert_eq!(fresh.unwrap(), vec![event]);
+    }
+
+    #[tokio::test]
+    async fn test_replace_invalidates_entries_with_old_event() {
+        let store = cached_store(QueryCacheConfig::default());
+        let old_profile = create_custom_event(0, 1000, "old", vec![]);
+        store
+            .save(&old_profile.clone().verify().unwrap())
+            .await
+            .unwrap();
+
+        let by_id = parse_filters(&format!(r#"[{{"ids": ["{}"]}}]"#, old_profile.id));
+        let notes = parse_filters(r#"[{"kinds": [1]}]"#);
+        assert_eq!(
+            store.query(&by_id).await.unwrap(),
+            vec![old_profile.clone()]
+        );
+        store.query(&notes).await.unwrap();
+        assert_eq!(store.len(), 2);
+
+        let new_profile = create_custom_event(0, 2000, "new", vec![]);
+        let result = store.save(&new_profile.verify().unwrap()).await.unwrap();
+        assert_eq!(
+            result,
+            SaveResult::Replaced {
+                old_id: old_profile.id
+            }
+        );
+
+        // 旧イベントを含むエントリだけが破棄される
+        assert_eq!(store.len(), 1);
+        assert!(store.query(&by_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_save_keeps_entries_when_ttl_only() {
+        let store = cached_store(QueryCacheConfig {
+            invalidation: CacheInvalidation::TtlOnly,
+            ..Default::default()
+        });
+        let filters = parse_filters(r#"[{"kinds": [1]}]"#);
+        store.query(&filters).await.unwrap();
+
+        store
+            .save(&create_test_event().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 1);
+        assert!(store.query(&filters).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_clears_cache() {
+        let store = cached_store(QueryCacheConfig {
+            invalidation: CacheInvalidation::TtlOnly,
+            ..Default::default()
+        });
+        let event = create_test_event();
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+        let filters = parse_filters(r#"[{"kinds": [1]}]"#);
+        assert_eq!(store.query(&filters).await.unwrap().len(), 1);
+
+        let deletion = create_custom_event(5, 2000, "", vec![vec!["e", &event.id.to_string()]]);
+        store.delete(&deletion.verify().unwrap()).await.unwrap();
+
+        assert!(store.is_empty());
+        assert!(store.query(&filters).await.unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_entries_evicts_oldest() {
+        let store = cached_store(QueryCacheConfig {
+            max_entries: 2,
+            ..Default::default()
+        });
+        for kind in [1, 2, 3] {
+            store
+                .query(&parse_filters(&format!(r#"[{{"kinds": [{kind}]}}]"#)))
+                .await
+                .unwrap();
+            tokio::time::advance(Duration::from_millis(10)).await;
+        }
+
+        // 最も古い kind 1 のエントリが追い出される
+        assert_eq!(store.len(), 2);
+        let oldest_key = cache_key(&parse_filters(r#"[{"kinds": [1]}]"#));
+        assert!(!store.state().entries.contains_key(&oldest_key));
+    }
+
+    /// kind ごとに `count` 件のイベントを保存する
+    async fn save_events(store: &CachedEventStore<InMemoryEventStore>, kind: u16, count: i64) {
+        for i in 0..count {
+            let event = create_custom_event(kind, 1000 + i, "", vec![]);
+            store.inner().save(&event.verify().unwrap()).await.unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_total_events_evicts_oldest() {
+        let store = cached_store(QueryCacheConfig {
+            max_total_events: 5,
+            ..Default::default()
+        });
+        for kind in [1, 7, 9] {
+            save_events(&store, kind, 2).await;
+        }
+        for kind in [1, 7, 9] {
+            store
+                .query(&parse_filters(&format!(r#"[{{"kinds": [{kind}]}}]"#)))
+                .await
+                .unwrap();
+            tokio::time::advance(Duration::from_millis(10)).await;
+        }
+
+        // 合計 6 件は上限を超えるため、最も古い kind 1 のエントリが追い出される
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.state().total_events, 4);
+        let oldest_key = cache_key(&parse_filters(r#"[{"kinds": [1]}]"#));
+        assert!(!store.state().entries.contains_key(&oldest_key));
+    }
+
+    #[tokio::test]
+    async fn test_large_result_is_not_cached() {
+        let store = cached_store(QueryCacheConfig {
+            max_entry_events: 2,
+            ..Default::default()
+        });
+        save_events(&store, 1, 3).await;
+        save_events(&store, 7, 2).await;
+
+        assert_eq!(
+            store
+                .query(&parse_filters(r#"[{"kinds": [1]}]"#))
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(store.is_empty());
+
+        store
+            .query(&parse_filters(r#"[{"kinds": [7]}]"#))
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_entries_are_removed_on_insert() {
+        let store = cached_store(QueryCacheConfig {
+            ttl: Duration::from_secs(3),
+            ..Default::default()
+        });
+        save_events(&store, 1, 2).await;
+        store
+            .query(&parse_filters(r#"[{"kinds": [1]}]"#))
+            .await
+            .unwrap();
+        tokio::time::advance(Duration::from_secs(3)).await;
+
+        store
+            .query(&parse_filters(r#"[{"kinds": [7]}]"#))
+            .await
+            .unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.state().total_events, 0);
+        assert_eq!(store.state().by_age.len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_cache_config_from_env() {
+        unsafe {
+            env::set_var(ENV_QUERY_CACHE_TTL_MS, "500");
+            env::set_var(ENV_QUERY_CACHE_MAX_ENTRIES, "0");
+            env::set_var(ENV_QUERY_CACHE_MAX_TOTAL_EVENTS, "2000");
+            env::set_var(ENV_QUERY_CACHE_MAX_ENTRY_EVENTS, "100");
+            env::set_var(ENV_QUERY_CACHE_INVALIDATION, "ttl_only");
+        }
+        let config = QueryCacheConfig::from_env();
+        assert_eq!(config.ttl, Duration::from_millis(500));
+        assert_eq!(config.max_entries, 0);
+        assert_eq!(config.max_total_events, 2000);
+        assert_eq!(config.max_entry_events, 100);
+        assert_eq!(config.invalidation, CacheInvalidation::TtlOnly);
+
+        // 不正な値はデフォルト
+        unsafe {
+            env::set_var(ENV_QUERY_CACHE_TTL_MS, "-1");
+            env::set_var(ENV_QUERY_CACHE_MAX_ENTRIES, "many");
+            env::set_var(ENV_QUERY_CACHE_MAX_TOTAL_EVENTS, "-5");
+            env::set_var(ENV_QUERY_CACHE_MAX_ENTRY_EVENTS, "");
+            env::set_var(ENV_QUERY_CACHE_INVALIDATION, "never");
+        }
+        let config = QueryCacheConfig::from_env();
+        let default = QueryCacheConfig::default();
+        assert_eq!(config.ttl, default.ttl);
+        assert_eq!(config.max_entries, default.max_entries);
+        assert_eq!(config.max_total_events, default.max_total_events);
+        assert_eq!(config.max_entry_events, default.max_entry_events);
+        assert_eq!(config.invalidation, default.invalidation);
+
+        unsafe {
+            env::remove_var(ENV_QUERY_CACHE_TTL_MS);
+            env::remove_var(ENV_QUERY_CACHE_MAX_ENTRIES);
+            env::remove_var(ENV_QUERY_CACHE_MAX_TOTAL_EVENTS);
+            env::remove_var(ENV_QUERY_CACHE_MAX_ENTRY_EVENTS);
+            env::remove_var(ENV_QUERY_CACHE_INVALIDATION);
+        }
+        assert_eq!(QueryCacheConfig::from_env().ttl, default.ttl);
+    }
+}
@@ -0,0 +1,182 @@
+//! DynamoDB 書き込みのスロットリング時の指数バックオフ
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tracing::warn;
+
+/// スロットリング時のリトライ設定
+#[derive(Debug, Clone)]
+pub(super) struct BackoffConfig {
+    /// 最大リトライ回数（初回の送信は含まない）
+    pub max_retries: u32,
+    /// 1回目のリトライ前の待機時間の上限
+    pub base_delay: Duration,
+    /// 待機時間の上限（指数的に伸ばしてもこれを超えない）
+    pub max_delay: Duration,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// `retry` 回目（0始まり）のリトライ前の待機時間の上限
+    fn delay_cap(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// `retry` 回目（0始まり）のリトライ前の待機時間（上限までの範囲でランダム）
+    pub(super) fn retry_delay(&self, retry: u32) -> Duration {
+        jittered(self.delay_cap(retry))
+    }
+}
+
+/// 0..=cap の範囲でランダムに待機時間を選ぶ（full jitter）
+///
+/// 同時にスロットリングされた書き込みが一斉に再送しないようにばらつかせる。
+fn jittered(cap: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let cap_ms = u64::try_from(cap.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(random % cap_ms.saturating_add(1))
+}
+
+/// `op` を実行し、`is_throttled` が真のエラーのときだけ指数バックオフで再実行する
+///
+/// それ以外のエラー（ConditionalCheckFailed など）と、リトライ上限に達した後のエラーはそのまま返す。
+pub(super) async fn with_backoff<T, E, F, Fut>(
+    config: &BackoffConfig,
+    is_throttled: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retry = 0;
+    loop {
+        match op().await {
+            Err(e) if retry < config.max_retries && is_throttled(&e) => {
+                let delay = config.retry_delay(retry);
+                retry += 1;
+                warn!(
+                    retry,
+                    max_retries = config.max_retries,
+                    delay_ms = delay.as_millis(),
+                    "DynamoDBのスロットリングのため再送"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Debug, PartialEq)]
+    enum FakeError {
+        Throttled,
+        ConditionalCheckFailed,
+    }
+
+    fn is_throttled(e: &FakeError) -> bool {
+        *e == FakeError::Throttled
+    }
+
+    /// 指定した順にエラーを返し、尽きたら成功するモック操作
+    fn fake_op(
+        errors: Vec<FakeError>,
+        calls: &Cell<usize>,
+    ) -> impl FnMut() -> std::future::Ready<Result<&'static str, FakeError>> {
+        let mut errors = errors.into_iter();
+        move || {
+            calls.set(calls.get() + 1);
+            std::future::ready(match errors.next() {
+                Some(e) => Err(e),
+                None => Ok("written"),
+            })
+        }
+    }
+
+    #[test]
+    fn test_delay_cap_grows_exponentially_up_to_max() {
+        let config = BackoffConfig {
+            max_retries: 10,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_millis(300),
+        };
+        let caps: Vec<u128> = (0..5).map(|i| config.delay_cap(i).as_millis()).collect();
+        assert_eq!(caps, vec![50, 100, 200, 300, 300]);
+        // 極端な回数でもオーバーフローしない
+        assert_eq!(config.delay_cap(u32::MAX), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_jittered_stays_within_cap() {
+        let cap = Duration::from_millis(100);
+        for _ in 0..100 {
+            assert!(jittered(cap) <= cap);
+        }
+        assert_eq!(jittered(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_delay_stays_within_delay_cap() {
+        let config = BackoffConfig::default();
+        for retry in 0..5 {
+            assert!(config.retry_delay(retry) <= config.delay_cap(retry));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_throttled_until_success() {
+        let calls = Cell::new(0);
+        let op = fake_op(vec![FakeError::Throttled, FakeError::Throttled], &calls);
+
+        let result = with_backoff(&BackoffConfig::default(), is_throttled, op).await;
+        assert_eq!(result, Ok("written"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_retries() {
+        let calls = Cell::new(0);
+        let config = BackoffConfig {
+            max_retries: 2,
+            ..Default::default()
+        };
+        let op = fake_op((0..5).map(|_| FakeError::Throttled).collect(), &calls);
+
+        let result = with_backoff(&config, is_throttled, op).await;
+        assert_eq!(result, Err(FakeError::Throttled));
+        // 初回 + リトライ2回
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_does_not_retry_other_errors() {
+        let calls = Cell::new(0);
+        let op = fake_op(
+            vec![FakeError::Throttled, FakeError::ConditionalCheckFailed],
+            &calls,
+        );
+
+        let result = with_backoff(&BackoffConfig::default(), is_throttled, op).await;
+        assert_eq!(result, Err(FakeError::ConditionalCheckFailed));
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+//! DynamoDB 操作の消費キャパシティ（ConsumedCapacity）の記録
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use aws_sdk_dynamodb::types::ConsumedCapacity;
+use tracing::info;
+
+/// 消費キャパシティを記録するログのメトリクス名
+pub(super) const METRIC_NAME: &str = "dynamodb_consumed_capacity";
+
+/// 消費キャパシティを集計する DynamoDB 操作の種別
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Operation {
+    /// 起動時の全件ロード
+    Scan,
+    /// GSI による既存 Replaceable/Addressable の取得
+    Query,
+    /// イベントの保存
+    PutItem,
+    /// イベントの削除
+    DeleteItem,
+    /// Replaceable/Addressable の置換
+    TransactWriteItems,
+}
+
+impl Operation {
+    /// ログの operation ラベル
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::Scan => "scan",
+            Self::Query => "query",
+            Self::PutItem => "put_item",
+            Self::DeleteItem => "delete_item",
+            Self::TransactWriteItems => "transact_write_items",
+        }
+    }
+}
+
+/// 操作ごとの消費キャパシティユニット（RCU/WCU）の累計
+#[derive(Default)]
+pub(super) struct CapacityRecorder {
+    totals: Mutex<HashMap<Operation, f64>>,
+}
+
+impl CapacityRecorder {
+    fn totals(&self) -> MutexGuard<'_, HashMap<Operation, f64>> {
+        // 集計値は可視化用なので、poison されても中身をそのまま使う
+        self.totals.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 1回の操作で返された ConsumedCapacity を記録する
+    ///
+    /// トランザクションのようにテーブルごとに返る場合は合算する。
+    /// ConsumedCapacity が返らなかった（要求していない・DynamoDB Local など）場合は何もしない。
+    ///
+    /// # 戻り値
+    ///
+    /// 今回の操作の消費キャパシティユニット
+    pub(super) fn record<'a>(
+        &self,
+        operation: Operation,
+        capacities: impl IntoIterator<Item = &'a ConsumedCapacity>,
+    ) -> Option<f64> {
+        let capacity_units = capacities
+            .into_iter()
+            .filter_map(ConsumedCapacity::capacity_units)
+            .reduce(|sum, units| sum + units)?;
+
+        let total_capacity_units = {
+            let mut totals = self.totals();
+            let total = totals.entry(operation).or_default();
+            *total += capacity_units;
+            *total
+        };
+        info!(
+            metric = METRIC_NAME,
+            operation = operation.as_str(),
+            capacity_units,
+            total_capacity_units,
+            "DynamoDB消費キャパシティ"
+        );
+        Some(capacity_units)
+    }
+
+    /// 操作ごとの消費キャパシティユニットの累計
+    #[cfg(test)]
+    fn total(&self, operation: Operation) -> f64 {
+        self.totals().get(&operation).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capacity(units: f64) -> ConsumedCapacity {
+        ConsumedCapacity::builder()
+            .table_name("events")
+            .capacity_units(units)
+            .build()
+    }
+
+    #[test]
+    fn test_record_accumulates_per_operation() {
+        let recorder = CapacityRecorder::default();
+
+        assert_eq!(
+            recorder.record(Operation::Scan, [&capacity(128.0)]),
+            Some(128.0)
+        );
+        assert_eq!(
+            recorder.record(Operation::Scan, [&capacity(64.5)]),
+            Some(64.5)
+        );
+        assert_eq!(
+            recorder.record(Operation::PutItem, [&capacity(1.0)]),
+            Some(1.0)
+        );
+
+        assert_eq!(recorder.total(Operation::Scan), 192.5);
+        assert_eq!(recorder.total(Operation::PutItem), 1.0);
+        assert_eq!(recorder.total(Operation::Query), 0.0);
+    }
+
+    #[test]
+    fn test_record_sums_capacities_of_one_transaction() {
+        let recorder = CapacityRecorder::default();
+        let capacities = vec![capacity(2.0), capacity(2.0)];
+
+        assert_eq!(
+            recorder.record(Operation::TransactWriteItems, &capacities),
+            Some(4.0)
+        );
+        assert_eq!(recorder.total(Operation::TransactWriteItems), 4.0);
+    }
+
+    #[test]
+    fn test_record_ignores_missing_capacity() {
+        let recorder = CapacityRecorder::default();
+
+        assert_eq!(recorder.record(Operation::DeleteItem, None), None);
+        let without_units = ConsumedCapacity::builder().table_name("events").build();
+        assert_eq!(
+            recorder.record(Operation::DeleteItem, [&without_units]),
+            None
+        );
+        assert_eq!(recorder.total(Operation::DeleteItem), 0.0);
+    }
+}
@@ -8,11 +8,15 @@ use tracing::{debug, instrument, trace};
 
 use super::{DeleteResult, EventStore, SaveResult, StoreError};
 use crate::models::{Event, EventId, Filter, VerifiedEvent};
+use crate::retention::{self, RetentionPolicy};
+
+mod event_table;
+pub(crate) use event_table::EventTable;
 
 /// インメモリイベントストア（開発・テスト用）
 pub struct InMemoryEventStore {
-    /// イベントID -> イベント
-    pub(crate) events: RwLock<HashMap<EventId, Event>>,
+    /// イベント本体と二次インデックス
+    pub(crate) events: RwLock<EventTable>,
     /// Replaceable: (pubkey_hex, kind) -> EventId
     replaceable_index: RwLock<HashMap<(String, u16), EventId>>,
     /// Addressable: (pubkey_hex, kind, d_tag) -> EventId
@@ -23,7 +27,7 @@ impl InMemoryEventStore {
     /// 新しい空のインメモリストアを作成
     pub fn new() -> Self {
         Self {
-            events: RwLock::new(HashMap::new()),
+            events: RwLock::new(EventTable::new()),
             replaceable_index: RwLock::new(HashMap::new()),
             addressable_index: RwLock::new(HashMap::new()),
         }
@@ -52,8 +56,12 @@ impl InMemoryEventStore {
         let mut events = self.events.write().await;
         let mut replaceable_index = self.replaceable_index.write().await;
 
-        let mut replaced = false;
+        let mut replaced = None;
         if let Some(existing_id) = replaceable_index.get(&key).copied() {
+            // 完全に同じイベント（同じID）の再送は置換ではなく重複
+            if existing_id == event.id {
+                return Ok(SaveResult::Duplicate);
+            }
             if let Some(existing) = events.get(&existing_id)
                 && !Self::is_newer(event, existing)
             {
@@ -62,17 +70,16 @@ impl InMemoryEventStore {
             }
             // 既存イベントを削除
             events.remove(&existing_id);
-            replaced = true;
+            replaced = Some(existing_id);
         }
 
         // 新イベントを保存
-        events.insert(event.id, event.clone());
+        events.insert(event.clone());
         replaceable_index.insert(key, event.id);
 
-        if replaced {
-            Ok(SaveResult::Replaced)
-        } else {
-            Ok(SaveResult::Saved)
+        match replaced {
+            Some(old_id) => Ok(SaveResult::Replaced { old_id }),
+            None => Ok(SaveResult::Saved),
         }
     }
 
@@ -84,8 +91,12 @@ impl InMemoryEventStore {
         let mut events = self.events.write().await;
         let mut addressable_index = self.addressable_index.write().await;
 
-        let mut replaced = false;
+        let mut replaced = None;
         if let Some(existing_id) = addressable_index.get(&key).copied() {
+            // 完全に同じイベント（同じID）の再送は置換ではなく重複
+            if existing_id == event.id {
+                return Ok(SaveResult::Duplicate);
+            }
             if let Some(existing) = events.get(&existing_id)
                 && !Self::is_newer(event, existing)
             {
@@ -94,21 +105,107 @@ impl InMemoryEventStore {
             }
             // 既存イベントを削除
             events.remove(&existing_id);
-            replaced = true;
+            replaced = Some(existing_id);
         }
 
         // 新イベントを保存
-        events.insert(event.id, event.clone());
+        events.insert(event.clone());
         addressable_index.insert(key, event.id);
 
-        if replaced {
-            Ok(SaveResult::Replaced)
-        } else {
-            Ok(SaveResult::Saved)
+        match replaced {
+            Some(old_id) => Ok(SaveResult::Replaced { old_id }),
+            None => Ok(SaveResult::Saved),
+        }
+    }
+
+    /// 保持期間を過ぎたイベントを削除し、削除したイベントを返す
+    pub(crate) async fn remove_expired(
+        &self,
+        policies: &[RetentionPolicy],
+        now: u64,
+    ) -> Vec<Event> {
+        // 最短の保持期間より新しいイベントは期限切れになり得ないので走査しない
+        let Some(min_age) = policies.iter().map(|p| p.max_age_secs).min() else {
+            return Vec::new();
+        };
+        let newest_expirable = now.saturating_sub(min_age);
+        let newest_expirable = i64::try_from(newest_expirable).unwrap_or(i64::MAX);
+
+        let mut events = self.events.write().await;
+        let mut replaceable_index = self.replaceable_index.write().await;
+        let mut addressable_index = self.addressable_index.write().await;
+
+        let expired_ids: Vec<EventId> = events
+            .range(None, Some(newest_expirable))
+            .filter(|e| retention::is_expired(policies, e, now))
+            .map(|e| e.id)
+            .collect();
+
+        let mut removed = Vec::with_capacity(expired_ids.len());
+        for id in expired_ids {
+            if let Some(event) = events.remove(&id) {
+                if event.kind.is_replaceable() {
+                    replaceable_index.remove(&(event.pubkey.to_hex(), event.kind.as_u16()));
+                }
+                if event.kind.is_addressable() {
+                    let key = (
+                        event.pubkey.to_hex(),
+                        event.kind.as_u16(),
+                        event.d_tag_value().to_string(),
+                    );
+                    addressable_index.remove(&key);
+                }
+                removed.push(event);
+            }
         }
+        removed
     }
 }
 
+/// クエリ結果の並び順: created_at 降順、同タイムスタンプは event ID 昇順
+fn newest_first(a: &Event, b: &Event) -> std::cmp::Ordering {
+    b.created_at
+        .as_i64()
+        .cmp(&a.created_at.as_i64())
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+/// addressable_index を直接引くキーの組み合わせ数の上限
+///
+/// authors × kinds × #d の直積がこれを超える場合は通常の候補絞り込みに任せる。
+const ADDRESSABLE_LOOKUP_MAX_KEYS: usize = 256;
+
+/// Addressable イベントを addressable_index から直接引くためのキーを組み立てる
+///
+/// authors・kinds・#d がすべて指定され、kinds がすべて Addressable の場合のみ
+/// (pubkey_hex, kind, d) の直積を返す。直接引けないフィルタでは `None`。
+fn addressable_lookup_keys(filter: &Filter) -> Option<Vec<(String, u16, String)>> {
+    let authors = filter.authors.as_ref()?;
+    let kinds = filter.kinds.as_ref()?;
+    let d_values = filter.tags.get('d')?;
+    if !kinds.iter().all(|k| k.is_addressable()) {
+        return None;
+    }
+    let key_count = authors
+        .len()
+        .checked_mul(kinds.len())?
+        .checked_mul(d_values.len())?;
+    if key_count > ADDRESSABLE_LOOKUP_MAX_KEYS {
+        return None;
+    }
+
+    let mut keys = Vec::with_capacity(key_count);
+    for author in authors {
+        let pubkey = author.to_hex();
+        for kind in kinds {
+            for d in d_values {
+                keys.push((pubkey.clone(), kind.as_u16(), d.clone()));
+            }
+        }
+    }
+    Some(keys)
+}
+
 impl Default for InMemoryEventStore {
     fn default() -> Self {
         Self::new()
@@ -150,7 +247,7 @@ impl EventStore for InMemoryEventStore {
         // Regular イベント：単純に保存
         trace!("regularイベントとして保存");
         let mut events = self.events.write().await;
-        events.insert(inner.id, inner.clone());
+        events.insert(inner.clone());
         Ok(SaveResult::Saved)
     }
 
@@ -159,50 +256,86 @@ impl EventStore for InMemoryEventStore {
         use std::collections::HashSet;
 
         let start = Instant::now();
+        let lookup_keys: Vec<_> = filters.iter().map(addressable_lookup_keys).collect();
         let events = self.events.read().await;
+        // 保存処理と同じく events → addressable_index の順でロックを取る
+        let addressable_index = if lookup_keys.iter().any(Option::is_some) {
+            Some(self.addressable_index.read().await)
+        } else {
+            None
+        };
 
         // 各フィルターごとにマッチ・limit適用し、結果をマージ（NIP-01: フィルター間はOR）
         let mut seen_ids = HashSet::new();
         let mut merged: Vec<Event> = Vec::new();
-
-        for filter in filters {
-            let mut filter_matched: Vec<Event> = events
-                .values()
-                .filter(|e| filter.matches(e))
-                .cloned()
-                .collect();
-
-            // ソート: created_at 降順、同タイムスタンプは event ID 昇順
-            filter_matched.sort_by(|a, b| {
-                match b.created_at.as_i64().cmp(&a.created_at.as_i64()) {
-                    std::cmp::Ordering::Equal => a.id.to_string().cmp(&b.id.to_string()),
-                    other => other,
+        // 条件判定したイベント数（インデックスによる絞り込み効果の確認用）
+        let mut scanned_count = 0usize;
+
+        for (filter, keys) in filters.iter().zip(&lookup_keys) {
+            let limit = filter.result_limit();
+
+            // authors + kinds + #d の Addressable 検索は最新版をアドレスから直接引く。
+            // 先頭以外の d タグでマッチするイベントは multi_d_tagged から補う
+            let candidates = match (keys, &addressable_index) {
+                (Some(keys), Some(index)) => {
+                    let mut candidate_ids = HashSet::new();
+                    Some(
+                        keys.iter()
+                            .filter_map(|key| index.get(key))
+                            .filter_map(|id| events.get(id))
+                            .chain(events.multi_d_tagged())
+                            .filter(|e| candidate_ids.insert(e.id))
+                            .collect(),
+                    )
                 }
-            });
-
-            // フィルターごとのlimit適用
-            if let Some(limit) = filter.limit {
-                filter_matched.truncate(limit as usize);
-            }
+                _ => events.candidates(filter),
+            };
+
+            // limit 件を超えてイベントを複製・保持しないよう、参照のまま上位 limit 件に絞る
+            let filter_matched: Vec<&Event> = match candidates {
+                Some(candidates) => {
+                    scanned_count += candidates.len();
+                    let mut matched: Vec<&Event> = candidates
+                        .into_iter()
+                        .filter(|e| filter.matches(e))
+                        .collect();
+                    // 上位 limit 件だけを選んでからソートする
+                    if matched.len() > limit {
+                        matched.select_nth_unstable_by(limit, |a, b| newest_first(a, b));
+                        matched.truncate(limit);
+                    }
+                    matched.sort_by(|a, b| newest_first(a, b));
+                    matched
+                }
+                // インデックスで候補を絞り込めない場合は、時系列インデックスを
+                // since/until の範囲だけ走査する（結果は既にソート済みなので limit 件で打ち切る）。
+                // since のみ・until のみの片側範囲も、反対側を無制限とした範囲走査になり、
+                // 全件走査になるのは since/until とインデックス対象の条件がどちらもない場合だけ
+                None => events
+                    .range(
+                        filter.since.map(|t| t.as_i64()),
+                        filter.until.map(|t| t.as_i64()),
+                    )
+                    .inspect(|_| scanned_count += 1)
+                    .filter(|e| filter.matches(e))
+                    .take(limit)
+                    .collect(),
+            };
 
             // 重複排除してマージ
             for event in filter_matched {
                 if seen_ids.insert(event.id) {
-                    merged.push(event);
+                    merged.push(event.clone());
                 }
             }
         }
 
         // 最終ソート（マージ後）
-        merged.sort_by(
-            |a, b| match b.created_at.as_i64().cmp(&a.created_at.as_i64()) {
-                std::cmp::Ordering::Equal => a.id.to_string().cmp(&b.id.to_string()),
-                other => other,
-            },
-        );
+        merged.sort_by(newest_first);
 
         debug!(
             total_events = events.len(),
+            scanned_count,
             result_count = merged.len(),
             elapsed_ms = start.elapsed().as_millis(),
             "ストアクエリ完了"
@@ -287,6 +420,17 @@ impl EventStore for InMemoryEventStore {
         debug!(deleted_count, "削除処理完了");
         Ok(DeleteResult { deleted_count })
     }
+
+    #[instrument(skip(self, policies), fields(policy_count = policies.len()))]
+    async fn purge_expired(
+        &self,
+        policies: &[RetentionPolicy],
+        now: u64,
+    ) -> Result<u64, StoreError> {
+        let removed = self.remove_expired(policies, now).await;
+        debug!(purged_count = removed.len(), "期限切れイベント削除完了");
+        Ok(removed.len() as u64)
+    }
 }
 
 #[cfg(test)]
@@ -312,6 +456,159 @@ mod tests {
         assert_eq!(results[0], event);
     }
 
+    /// relay ヒントや marker を含む3要素以上のタグを持つテスト用イベント
+    fn create_event_with_extra_tag_elements() -> Event {
+        create_custom_event(
+            1,
+            1000,
+            "reply with hints",
+            vec![
+                vec!["e", "root-id", "wss://relay.example.com", "root"],
+                vec!["e", "reply-id", "", "reply"],
+                vec!["p", "pubkey1", "wss://relay2.example.com"],
+                vec![
+                    "client",
+                    "app",
+                    "31990:pubkey:handler",
+                    "wss://relay3.example.com",
+                ],
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_query_preserves_extra_tag_elements() {
+        // 検索には2要素目しか使わないが、3要素目以降（relay ヒント・marker）も欠けずに返る
+        let store = InMemoryEventStore::new();
+        let event = create_event_with_extra_tag_elements();
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+
+        let filter: Filter =
+            serde_json::from_value(serde_json::json!({"#e": ["reply-id"]})).unwrap();
+        let results = store.query(&[filter]).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].tags, event.tags);
+        // 完全なタグが復元されているので、取得したイベントの ID・署名もそのまま検証できる
+        assert!(results[0].clone().verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_query_empty_string_tag_value() {
+        let store = InMemoryEventStore::new();
+        let empty = create_custom_event(1, 1000, "empty", vec![vec!["e", ""]]);
+        let valued = create_custom_event(1, 1001, "valued", vec![vec!["e", "root"]]);
+        let untagged = create_custom_event(1, 1002, "untagged", vec![]);
+        for event in [&empty, &valued, &untagged] {
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+        }
+
+        // インデックス経路でも空文字は通常の値として引かれ、全件にはマッチしない
+        let filter: Filter = serde_json::from_str(r##"{"#e": [""]}"##).unwrap();
+        let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+        assert_eq!(results, vec![empty]);
+        assert_eq!(results, scan_query(&store, &filter).await);
+    }
+
+    #[tokio::test]
+    async fn test_query_a_tag_matches_exact_value_only() {
+        // #a は kind:pubkey:d の値全体の完全一致。kind だけ・d なしなどの部分一致ではマッチしない
+        let store = InMemoryEventStore::new();
+        let pubkey = "a".repeat(64);
+        let address = format!("30023:{pubkey}:article");
+        let exact = create_custom_event(1, 1000, "exact", vec![vec!["a", &address]]);
+        let with_relay = create_custom_event(
+            1,
+            1001,
+            "with relay hint",
+            vec![vec!["a", &address, "wss://relay.example.com"]],
+        );
+        let other_d = create_custom_event(
+            1,
+            1002,
+            "other d",
+            vec![vec!["a", &format!("30023:{pubkey}:other")]],
+        );
+        for event in [&exact, &with_relay, &other_d] {
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+        }
+
+        let filter: Filter = serde_json::from_value(serde_json::json!({"#a": [address]})).unwrap();
+        let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+        assert_eq!(results, vec![with_relay, exact]);
+        assert_eq!(results, scan_query(&store, &filter).await);
+
+        for partial in [
+            "30023".to_string(),
+            format!("30023:{pubkey}"),
+            format!("30023:{pubkey}:"),
+            format!("30023:{pubkey}:art"),
+            format!(":{pubkey}:article"),
+        ] {
+            let filter: Filter =
+                serde_json::from_value(serde_json::json!({"#a": [partial]})).unwrap();
+            let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+            assert!(results.is_empty(), "#a={partial} はマッチしないべき");
+            assert!(scan_query(&store, &filter).await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_explicit_empty_lists_return_nothing() {
+        // インデックス経路・全件走査経路のどちらでも、空配列は「マッチなし」になる
+        let store = InMemoryEventStore::new();
+        let event = create_custom_event(1, 1000, "tagged", vec![vec!["e", "root"]]);
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+
+        for json in [
+            serde_json::json!({"ids": []}),
+            serde_json::json!({"authors": []}),
+            serde_json::json!({"kinds": []}),
+            serde_json::json!({"#e": []}),
+            serde_json::json!({"authors": [], "kinds": [1]}),
+        ] {
+            let filter: Filter = serde_json::from_value(json.clone()).unwrap();
+            let results = store.query(&[filter]).await.unwrap();
+            assert!(results.is_empty(), "{json} は空結果になるべき");
+        }
+
+        let filter: Filter = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(store.query(&[filter]).await.unwrap(), vec![event]);
+    }
+
+    #[tokio::test]
+    async fn test_query_by_second_value_of_same_tag() {
+        // 同じタグ名の2つ目以降の値でも検索できる
+        let store = InMemoryEventStore::new();
+        let event = create_custom_event(
+            1,
+            1000,
+            "reply",
+            vec![vec!["e", "root"], vec!["e", "reply"]],
+        );
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+
+        for value in ["root", "reply"] {
+            let filter: Filter =
+                serde_json::from_value(serde_json::json!({"#e": [value]})).unwrap();
+            let results = store.query(&[filter]).await.unwrap();
+            assert_eq!(results, vec![event.clone()], "#e={value}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_undefined_kind_as_regular() {
+        // NIP-01 で範囲が未定義の kind は Regular として保存される
+        let store = InMemoryEventStore::new();
+        for (kind, created_at) in [(45, 1000), (40000, 1001), (40000, 1002)] {
+            let event = create_custom_event(kind, created_at, "future kind", vec![]);
+            let result = store.save(&event.verify().unwrap()).await.unwrap();
+            assert_eq!(result, SaveResult::Saved);
+        }
+
+        let results = store.query(&[Filter::default()]).await.unwrap();
+        assert_eq!(results.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_duplicate_event() {
         let store = InMemoryEventStore::new();
@@ -392,6 +689,7 @@ mod tests {
         let store = InMemoryEventStore::new();
 
         let old_event = create_custom_event(0, 1000, "old profile", vec![]);
+        let old_event_id = old_event.id;
         let verified_old = old_event.verify().unwrap();
         let result1 = store.save(&verified_old).await.unwrap();
         assert_eq!(result1, SaveResult::Saved);
@@ -399,13 +697,79 @@ mod tests {
         let new_event = create_custom_event(0, 2000, "new profile", vec![]);
         let verified_new = new_event.clone().verify().unwrap();
         let result2 = store.save(&verified_new).await.unwrap();
-        assert_eq!(result2, SaveResult::Replaced);
+        assert_eq!(
+            result2,
+            SaveResult::Replaced {
+                old_id: old_event_id
+            }
+        );
 
         let results = store.query(&[Filter::default()]).await.unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].content, "new profile");
     }
 
+    #[tokio::test]
+    async fn test_contact_list_is_replaced_like_other_replaceable_kinds() {
+        // kind:0, 3 の特殊 Replaceable も 10000番台と同じく置換される（Regular として並ばない）
+        for kind in [0, 3, 10002] {
+            let store = InMemoryEventStore::new();
+            let old_event = create_custom_event(kind, 1000, "old", vec![vec!["p", "aa"]]);
+            let new_event = create_custom_event(kind, 2000, "new", vec![vec!["p", "bb"]]);
+
+            let verified_old = old_event.clone().verify().unwrap();
+            assert_eq!(store.save(&verified_old).await.unwrap(), SaveResult::Saved);
+            let verified_new = new_event.clone().verify().unwrap();
+            assert_eq!(
+                store.save(&verified_new).await.unwrap(),
+                SaveResult::Replaced {
+                    old_id: old_event.id
+                },
+                "kind {kind}"
+            );
+
+            // 古い方を後から受け取っても保存されない
+            assert_eq!(
+                store.save(&verified_old).await.unwrap(),
+                SaveResult::Ignored,
+                "kind {kind}"
+            );
+            assert_eq!(
+                store.query(&[Filter::default()]).await.unwrap(),
+                vec![new_event],
+                "kind {kind}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replaceable_and_addressable_exact_resend_is_duplicate() {
+        let store = InMemoryEventStore::new();
+        let replaceable = create_custom_event(0, 1000, "profile", vec![]);
+        let addressable = create_custom_event(30023, 1000, "article", vec![vec!["d", "slug"]]);
+
+        for event in [replaceable, addressable] {
+            let verified = event.clone().verify().unwrap();
+            assert_eq!(store.save(&verified).await.unwrap(), SaveResult::Saved);
+
+            // 同じイベントの再送は Replaced でも Ignored でもなく Duplicate
+            assert_eq!(store.save(&verified).await.unwrap(), SaveResult::Duplicate);
+            // 先頭の重複チェックをすり抜けた（並行保存の）場合も、置換処理の中で Duplicate になる
+            let result = if event.kind.is_replaceable() {
+                store.save_replaceable(&event).await
+            } else {
+                store.save_addressable(&event).await
+            };
+            assert_eq!(result.unwrap(), SaveResult::Duplicate);
+
+            // ストレージは書き換わらない
+            let filter: Filter =
+                serde_json::from_value(serde_json::json!({"kinds": [event.kind.as_u16()]}))
+                    .unwrap();
+            assert_eq!(store.query(&[filter]).await.unwrap(), vec![event]);
+        }
+    }
+
     #[tokio::test]
     async fn test_replaceable_event_older_ignored() {
         let store = InMemoryEventStore::new();
@@ -437,12 +801,13 @@ mod tests {
             (event2, event1)
         };
 
+        let larger_id = larger_id_event.id;
         let verified_larger = larger_id_event.verify().unwrap();
         store.save(&verified_larger).await.unwrap();
 
         let verified_smaller = smaller_id_event.clone().verify().unwrap();
         let result = store.save(&verified_smaller).await.unwrap();
-        assert_eq!(result, SaveResult::Replaced);
+        assert_eq!(result, SaveResult::Replaced { old_id: larger_id });
 
         let results = store.query(&[Filter::default()]).await.unwrap();
         assert_eq!(results.len(), 1);
@@ -457,6 +822,7 @@ mod tests {
 
         let old_event =
             create_custom_event(30000, 1000, "old article", vec![vec!["d", "article1"]]);
+        let old_event_id = old_event.id;
         let verified_old = old_event.verify().unwrap();
         let result1 = store.save(&verified_old).await.unwrap();
         assert_eq!(result1, SaveResult::Saved);
@@ -465,7 +831,12 @@ mod tests {
             create_custom_event(30000, 2000, "new article", vec![vec!["d", "article1"]]);
         let verified_new = new_event.clone().verify().unwrap();
         let result2 = store.save(&verified_new).await.unwrap();
-        assert_eq!(result2, SaveResult::Replaced);
+        assert_eq!(
+            result2,
+            SaveResult::Replaced {
+                old_id: old_event_id
+            }
+        );
 
         let results = store.query(&[Filter::default()]).await.unwrap();
         assert_eq!(results.len(), 1);
@@ -493,19 +864,80 @@ mod tests {
         let store = InMemoryEventStore::new();
 
         let event1 = create_custom_event(30000, 1000, "no d tag 1", vec![]);
+        let event1_id = event1.id;
         let verified1 = event1.verify().unwrap();
         store.save(&verified1).await.unwrap();
 
         let event2 = create_custom_event(30000, 2000, "no d tag 2", vec![]);
         let verified2 = event2.clone().verify().unwrap();
         let result = store.save(&verified2).await.unwrap();
-        assert_eq!(result, SaveResult::Replaced);
+        assert_eq!(result, SaveResult::Replaced { old_id: event1_id });
 
         let results = store.query(&[Filter::default()]).await.unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].content, "no d tag 2");
     }
 
+    #[tokio::test]
+    async fn test_addressable_event_multiple_d_tags_uses_first_d_tag() {
+        let store = InMemoryEventStore::new();
+
+        let multi =
+            create_custom_event(30000, 1000, "multi d", vec![vec!["d", "a"], vec!["d", "b"]]);
+        store.save(&multi.clone().verify().unwrap()).await.unwrap();
+
+        // 2つ目の d タグと同じアドレスのイベントでは置換されない
+        let address_b = create_custom_event(30000, 2000, "b", vec![vec!["d", "b"]]);
+        let result = store
+            .save(&address_b.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Saved);
+
+        // 先頭の d タグと同じアドレスのイベントでだけ置換され、別アドレスのイベントは残る
+        let address_a = create_custom_event(30000, 3000, "a", vec![vec!["d", "a"]]);
+        let result = store
+            .save(&address_a.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Replaced { old_id: multi.id });
+
+        let mut ids: Vec<EventId> = store
+            .query(&[Filter::default()])
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        ids.sort();
+        let mut expected = vec![address_a.id, address_b.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_addressable_event_empty_d_tag_shares_address_with_missing_d_tag() {
+        let store = InMemoryEventStore::new();
+
+        let empty_d = create_custom_event(30000, 1000, "empty d", vec![vec!["d", ""]]);
+        store
+            .save(&empty_d.clone().verify().unwrap())
+            .await
+            .unwrap();
+
+        // 空文字の d タグと d タグなしは同じアドレス（末尾が空）として置換し合う
+        let no_d = create_custom_event(30000, 2000, "no d", vec![]);
+        let result = store.save(&no_d.clone().verify().unwrap()).await.unwrap();
+        assert_eq!(result, SaveResult::Replaced { old_id: empty_d.id });
+
+        let value_less_d = create_custom_event(30000, 3000, "value-less d", vec![vec!["d"]]);
+        let result = store
+            .save(&value_less_d.clone().verify().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result, SaveResult::Replaced { old_id: no_d.id });
+    }
+
     // ========== Ephemeral イベントテスト ==========
 
     #[tokio::test]
@@ -564,6 +996,25 @@ mod tests {
         assert_eq!(results[1].content, "event 2");
     }
 
+    #[tokio::test]
+    async fn test_query_huge_limit_returns_all() {
+        let store = InMemoryEventStore::new();
+        for i in 0..3 {
+            let event =
+                create_custom_event(1, 1000 + i, &format!("event {i}"), vec![vec!["t", "x"]]);
+            store.save(&event.verify().unwrap()).await.unwrap();
+        }
+
+        // 時系列走査経路とインデックス経路のどちらでも、u64::MAX は上限なしとして扱う
+        for json in [
+            r#"{"limit": 18446744073709551615}"#,
+            r##"{"#t": ["x"], "limit": 18446744073709551615}"##,
+        ] {
+            let filter: Filter = serde_json::from_str(json).unwrap();
+            assert_eq!(store.query(&[filter]).await.unwrap().len(), 3);
+        }
+    }
+
     #[tokio::test]
     async fn test_query_multiple_filters_limit_per_filter() {
         let store = InMemoryEventStore::new();
@@ -776,4 +1227,483 @@ mod tests {
         let result = store.delete(&verified_delete).await.unwrap();
         assert_eq!(result.deleted_count, 0);
     }
+
+    // ========== インデックス経路と全件走査の一致 ==========
+
+    /// 全件走査でフィルタを評価した結果（インデックスを使わない基準実装）
+    async fn scan_query(store: &InMemoryEventStore, filter: &Filter) -> Vec<Event> {
+        let events = store.events.read().await;
+        let mut matched: Vec<Event> = events
+            .range(None, None)
+            .filter(|e| filter.matches(e))
+            .cloned()
+            .collect();
+        matched.sort_by(
+            |a, b| match b.created_at.as_i64().cmp(&a.created_at.as_i64()) {
+                std::cmp::Ordering::Equal => a.id.to_string().cmp(&b.id.to_string()),
+                other => other,
+            },
+        );
+        matched.truncate(filter.result_limit());
+        matched
+    }
+
+    #[tokio::test]
+    async fn test_indexed_query_matches_full_scan() {
+        let store = InMemoryEventStore::new();
+        let e_values = ["e1", "e2", "e3"];
+        let p_values = ["p1", "p2"];
+        for i in 0..30i64 {
+            let e = e_values[(i % 3) as usize];
+            let p = p_values[(i % 2) as usize];
+            let kind = if i % 4 == 0 { 7 } else { 1 };
+            let secret = [(i % 3 + 1) as u8; 32];
+            let event = create_custom_event_with_keypair(
+                kind,
+                1000 + i,
+                &format!("event {i}"),
+                vec![vec!["e", e], vec!["p", p]],
+                secret,
+            );
+            store.save(&event.verify().unwrap()).await.unwrap();
+        }
+        let author = create_custom_event_with_keypair(1, 0, "", vec![], [0x02; 32]).pubkey;
+
+        let filters: Vec<Filter> = [
+            r##"{"#e": ["e1", "e3"]}"##,
+            r##"{"#e": ["e1", "e2"], "#p": ["p2"], "limit": 4}"##,
+            r##"{"#p": ["p1"], "kinds": [7]}"##,
+            r##"{"#e": ["unknown"]}"##,
+            r##"{"#e": []}"##,
+            r##"{"#p": ["p1", "p2"], "since": 1010, "until": 1020}"##,
+            r##"{"since": 1010, "until": 1020}"##,
+            r##"{"since": 1025}"##,
+            r##"{"until": 1004, "limit": 2}"##,
+            r##"{"since": 1020, "until": 1010}"##,
+            r##"{"kinds": [7], "since": 1005}"##,
+        ]
+        .iter()
+        .map(|json| serde_json::from_str(json).unwrap())
+        .chain([
+            Filter {
+                authors: Some(vec![author]),
+                ..Default::default()
+            },
+            Filter {
+                authors: Some(vec![author]),
+                limit: Some(3),
+                ..Default::default()
+            },
+        ])
+        .collect();
+
+        for filter in &filters {
+            let indexed = store.query(std::slice::from_ref(filter)).await.unwrap();
+            let scanned = scan_query(&store, filter).await;
+            assert_eq!(indexed, scanned, "filter: {filter:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limit_returns_newest_events_regardless_of_save_order() {
+        // 保存順と created_at の順が一致しなくても、limit は created_at の新しい順に N 件を返す
+        let store = InMemoryEventStore::new();
+        let mut saved = Vec::new();
+        for i in [7i64, 2, 9, 0, 5, 3, 8, 1, 6, 4, 5, 9] {
+            let event = create_custom_event(
+                1,
+                1000 + i,
+                &format!("event {}", saved.len()),
+                vec![vec!["t", "all"]],
+            );
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+            saved.push(event);
+        }
+        let mut newest_first = saved.clone();
+        newest_first.sort_by(|a, b| {
+            b.created_at
+                .as_i64()
+                .cmp(&a.created_at.as_i64())
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        let author = saved[0].pubkey.to_hex();
+        for limit in [1usize, 3, 5, 12, 20] {
+            for json in [
+                serde_json::json!({"limit": limit}),
+                serde_json::json!({"#t": ["all"], "limit": limit}),
+                serde_json::json!({"authors": [author], "limit": limit}),
+                serde_json::json!({"kinds": [1], "limit": limit}),
+            ] {
+                let filter: Filter = serde_json::from_value(json.clone()).unwrap();
+                let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+                let expected: Vec<Event> = newest_first.iter().take(limit).cloned().collect();
+                assert_eq!(results, expected, "{json}");
+                assert_eq!(results, scan_query(&store, &filter).await, "{json}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_one_sided_time_ranges() {
+        let store = InMemoryEventStore::new();
+        for i in 0..10i64 {
+            let tag = if i % 2 == 0 { "even" } else { "odd" };
+            let event =
+                create_custom_event(1, 1000 + i, &format!("event {i}"), vec![vec!["t", tag]]);
+            store.save(&event.verify().unwrap()).await.unwrap();
+        }
+
+        let cases: [(&str, &[i64]); 8] = [
+            // なし
+            (
+                r##"{}"##,
+                &[1009, 1008, 1007, 1006, 1005, 1004, 1003, 1002, 1001, 1000],
+            ),
+            // since のみ（新しいものから since まで）
+            (r##"{"since": 1007}"##, &[1009, 1008, 1007]),
+            (r##"{"since": 1002, "limit": 2}"##, &[1009, 1008]),
+            // until のみ（until から古いものへ）
+            (r##"{"until": 1002}"##, &[1002, 1001, 1000]),
+            (r##"{"until": 1007, "limit": 2}"##, &[1007, 1006]),
+            // 両方
+            (r##"{"since": 1003, "until": 1005}"##, &[1005, 1004, 1003]),
+            // タグインデックス経路でも片側範囲は同じように効く
+            (r##"{"#t": ["even"], "since": 1005}"##, &[1008, 1006]),
+            (r##"{"#t": ["odd"], "until": 1004, "limit": 1}"##, &[1003]),
+        ];
+        for (json, expected) in cases {
+            let filter: Filter = serde_json::from_str(json).unwrap();
+            let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+            let timestamps: Vec<i64> = results.iter().map(|e| e.created_at.as_i64()).collect();
+            assert_eq!(timestamps, expected, "filter: {json}");
+            assert_eq!(results, scan_query(&store, &filter).await, "filter: {json}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_authors_and_p_tag_are_not_confused() {
+        let store = InMemoryEventStore::new();
+        let (x, y) = ([0x02; 32], [0x03; 32]);
+        let pubkey_of = |secret| create_custom_event_with_keypair(1, 0, "", vec![], secret).pubkey;
+        let (x_hex, y_hex) = (pubkey_of(x).to_hex(), pubkey_of(y).to_hex());
+
+        let x_mentions_y =
+            create_custom_event_with_keypair(1, 1000, "", vec![vec!["p", &y_hex]], x);
+        // 作成者とメンション先を入れ替えたイベントを多めに入れ、どちらのインデックスが
+        // 候補に選ばれても結果が変わらないことを確認する
+        let mut others = vec![];
+        for i in 0..5 {
+            others.push(create_custom_event_with_keypair(
+                1,
+                1001 + i,
+                "",
+                vec![vec!["p", &x_hex]],
+                y,
+            ));
+            others.push(create_custom_event_with_keypair(
+                1,
+                1011 + i,
+                "",
+                vec![vec!["p", &x_hex]],
+                x,
+            ));
+            others.push(create_custom_event_with_keypair(
+                1,
+                1021 + i,
+                "",
+                vec![vec!["p", &y_hex]],
+                y,
+            ));
+        }
+        for event in others.iter().chain([&x_mentions_y]) {
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+        }
+
+        let filter: Filter = serde_json::from_value(serde_json::json!({
+            "authors": [x_hex],
+            "#p": [y_hex],
+        }))
+        .unwrap();
+        let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+        assert_eq!(results, vec![x_mentions_y]);
+        assert_eq!(results, scan_query(&store, &filter).await);
+    }
+
+    #[tokio::test]
+    async fn test_query_duplicated_ids_return_each_event_once() {
+        // 正規化していないフィルタの重複IDでも、同じイベントを重ねず limit にも二重に数えない
+        let store = InMemoryEventStore::new();
+        let older = create_custom_event(1, 1000, "older", vec![]);
+        let newer = create_custom_event(1, 2000, "newer", vec![]);
+        for event in [&older, &newer] {
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+        }
+
+        let filter = Filter {
+            ids: Some(vec![newer.id, newer.id, older.id]),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let results = store.query(std::slice::from_ref(&filter)).await.unwrap();
+        assert_eq!(results, vec![newer, older]);
+    }
+
+    #[tokio::test]
+    async fn test_query_uppercase_ids_and_authors_match_stored_events() {
+        // ids / authors はパース時にバイト列へ変換するので、大文字 hex でも保存済みのイベントに当たる
+        let store = InMemoryEventStore::new();
+        let event = create_custom_event(1, 1000, "", vec![]);
+        store.save(&event.clone().verify().unwrap()).await.unwrap();
+
+        let id = event.id.to_string().to_ascii_uppercase();
+        let author = event.pubkey.to_hex().to_ascii_uppercase();
+        for json in [
+            serde_json::json!({"ids": [id]}),
+            serde_json::json!({"authors": [author]}),
+            serde_json::json!({"ids": [id], "authors": [author]}),
+        ] {
+            let filter: Filter = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(
+                store.query(std::slice::from_ref(&filter)).await.unwrap(),
+                vec![event.clone()],
+                "{json}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_limit_keeps_top_events_on_large_data() {
+        // インデックス経路（select_nth で上位 limit 件を選択）と時系列走査経路（limit 件で打ち切り）の
+        // どちらでも、大量のイベントから created_at 降順の上位 limit 件が返る
+        let store = InMemoryEventStore::new();
+        for i in 0..2000i64 {
+            // created_at が挿入順と一致しないよう並べ替える
+            let created_at = (i * 7919) % 2000;
+            let tag = if i % 2 == 0 { "even" } else { "odd" };
+            let event =
+                create_custom_event(1, created_at, &format!("event {i}"), vec![vec!["t", tag]]);
+            store.save(&event.verify().unwrap()).await.unwrap();
+        }
+
+        for json in [
+            r##"{"limit": 5}"##,
+            r##"{"#t": ["even"], "limit": 5}"##,
+            r##"{"#t": ["even", "odd"], "limit": 1}"##,
+            r##"{"#t": ["odd"], "limit": 0}"##,
+            r##"{"kinds": [1], "until": 1000, "limit": 3}"##,
+        ] {
+            let filter: Filter = serde_json::from_str(json).unwrap();
+            let result = store.query(std::slice::from_ref(&filter)).await.unwrap();
+            assert_eq!(result, scan_query(&store, &filter).await, "filter: {json}");
+            assert_eq!(result.len() as u64, filter.limit.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_addressable_lookup_keys_builds_cross_product() {
+        let author = create_custom_event_with_keypair(1, 0, "", vec![], [0x02; 32]).pubkey;
+        let filter: Filter = serde_json::from_value(serde_json::json!({
+            "authors": [author.to_hex()],
+            "kinds": [30000, 30023],
+            "#d": ["a", "b"],
+        }))
+        .unwrap();
+
+        let mut keys = addressable_lookup_keys(&filter).unwrap();
+        keys.sort();
+        let pubkey = author.to_hex();
+        assert_eq!(
+            keys,
+            vec![
+                (pubkey.clone(), 30000, "a".to_string()),
+                (pubkey.clone(), 30000, "b".to_string()),
+                (pubkey.clone(), 30023, "a".to_string()),
+                (pubkey, 30023, "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_addressable_lookup_keys_requires_addressable_address() {
+        let author = create_custom_event_with_keypair(1, 0, "", vec![], [0x02; 32]).pubkey;
+        let lookup = |value: serde_json::Value| {
+            addressable_lookup_keys(&serde_json::from_value(value).unwrap())
+        };
+
+        // authors / kinds / #d のいずれかが欠けると直接引けない
+        assert!(lookup(serde_json::json!({"kinds": [30000], "#d": ["a"]})).is_none());
+        assert!(lookup(serde_json::json!({"authors": [author.to_hex()], "#d": ["a"]})).is_none());
+        assert!(
+            lookup(serde_json::json!({"authors": [author.to_hex()], "kinds": [30000]})).is_none()
+        );
+        // Addressable 以外の kind を含む
+        assert!(
+            lookup(
+                serde_json::json!({"authors": [author.to_hex()], "kinds": [1, 30000], "#d": ["a"]})
+            )
+            .is_none()
+        );
+        // 直積が上限を超える
+        let d_values: Vec<String> = (0..=ADDRESSABLE_LOOKUP_MAX_KEYS)
+            .map(|i| i.to_string())
+            .collect();
+        assert!(
+            lookup(
+                serde_json::json!({"authors": [author.to_hex()], "kinds": [30000], "#d": d_values})
+            )
+            .is_none()
+        );
+        // 空リストはキーなし（何もマッチしない）
+        assert_eq!(
+            lookup(serde_json::json!({"authors": [author.to_hex()], "kinds": [30000], "#d": []})),
+            Some(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_addressable_lookup_query_matches_full_scan() {
+        let store = InMemoryEventStore::new();
+        let secrets = [[0x02; 32], [0x03; 32]];
+        for (i, secret) in secrets.iter().enumerate() {
+            let offset = i as i64 * 100;
+            let events = [
+                // 同じアドレスの古い版は置換される
+                create_custom_event_with_keypair(
+                    30000,
+                    1000 + offset,
+                    "v1",
+                    vec![vec!["d", "a"]],
+                    *secret,
+                ),
+                create_custom_event_with_keypair(
+                    30000,
+                    1001 + offset,
+                    "v2",
+                    vec![vec!["d", "a"]],
+                    *secret,
+                ),
+                create_custom_event_with_keypair(
+                    30000,
+                    1002 + offset,
+                    "",
+                    vec![vec!["d", "b"]],
+                    *secret,
+                ),
+                create_custom_event_with_keypair(
+                    30023,
+                    1003 + offset,
+                    "",
+                    vec![vec!["d", "a"]],
+                    *secret,
+                ),
+                // d タグなし（アドレスは空文字列だが #d: [""] にはマッチしない）
+                create_custom_event_with_keypair(30000, 1004 + offset, "", vec![], *secret),
+                // 先頭以外の d タグでもマッチする
+                create_custom_event_with_keypair(
+                    30001,
+                    1005 + offset,
+                    "",
+                    vec![vec!["d", "x"], vec!["d", "a"]],
+                    *secret,
+                ),
+                // Addressable 以外で d タグを持つイベント
+                create_custom_event_with_keypair(
+                    1,
+                    1006 + offset,
+                    "",
+                    vec![vec!["d", "a"]],
+                    *secret,
+                ),
+            ];
+            for event in events {
+                store.save(&event.verify().unwrap()).await.unwrap();
+            }
+        }
+        let authors: Vec<String> = secrets
+            .iter()
+            .map(|s| {
+                create_custom_event_with_keypair(1, 0, "", vec![], *s)
+                    .pubkey
+                    .to_hex()
+            })
+            .collect();
+
+        let filters: Vec<Filter> = [
+            serde_json::json!({"authors": [authors[0]], "kinds": [30000], "#d": ["a"]}),
+            serde_json::json!({"authors": [authors[0]], "kinds": [30000], "#d": ["a"], "limit": 1}),
+            serde_json::json!({"authors": authors, "kinds": [30000, 30023], "#d": ["a", "b"]}),
+            serde_json::json!({"authors": authors, "kinds": [30000, 30001], "#d": ["a"]}),
+            serde_json::json!({"authors": authors, "kinds": [30000], "#d": [""]}),
+            serde_json::json!({"authors": authors, "kinds": [30000], "#d": ["a"], "until": 1050}),
+            serde_json::json!({"authors": [authors[1]], "kinds": [30000], "#d": ["unknown"]}),
+        ]
+        .into_iter()
+        .map(|value| serde_json::from_value(value).unwrap())
+        .collect();
+
+        for filter in &filters {
+            assert!(addressable_lookup_keys(filter).is_some());
+            let looked_up = store.query(std::slice::from_ref(filter)).await.unwrap();
+            let scanned = scan_query(&store, filter).await;
+            assert_eq!(looked_up, scanned, "filter: {filter:?}");
+        }
+
+        // 単一アドレスの検索は最新版の1件だけを返す
+        let latest = store.query(&filters[0..1]).await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].content, "v2");
+    }
+
+    // ========== 保持期間 ==========
+
+    #[tokio::test]
+    async fn test_purge_expired_removes_only_expired_events() {
+        let store = InMemoryEventStore::new();
+        let old_note = create_custom_event(1, 1000, "old note", vec![]);
+        let new_note = create_custom_event(1, 5000, "new note", vec![]);
+        let old_reaction = create_custom_event(7, 1000, "+", vec![]);
+        for event in [&old_note, &new_note, &old_reaction] {
+            store.save(&event.clone().verify().unwrap()).await.unwrap();
+        }
+
+        let policies = vec![RetentionPolicy {
+            kind_range: 1..=1,
+            max_age_secs: 3000,
+        }];
+        let purged = store.purge_expired(&policies, 5000).await.unwrap();
+        assert_eq!(purged, 1);
+
+        let results = store.query(&[Filter::default()]).await.unwrap();
+        let ids: Vec<EventId> = results.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![new_note.id, old_reaction.id]);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_without_policies_is_noop() {
+        let store = InMemoryEventStore::new();
+        let event = create_custom_event(1, 0, "ancient", vec![]);
+        store.save(&event.verify().unwrap()).await.unwrap();
+
+        assert_eq!(store.purge_expired(&[], u64::MAX).await.unwrap(), 0);
+        assert_eq!(store.query(&[Filter::default()]).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired_cleans_replaceable_index() {
+        let store = InMemoryEventStore::new();
+        let profile = create_custom_event(0, 1000, "profile", vec![]);
+        store.save(&profile.verify().unwrap()).await.unwrap();
+
+        let policies = vec![RetentionPolicy {
+            kind_range: 0..=0,
+            max_age_secs: 10,
+        }];
+        assert_eq!(store.purge_expired(&policies, 2000).await.unwrap(), 1);
+
+        // インデックスが残っていなければ新規保存として扱われる
+        let new_profile = create_custom_event(0, 1500, "new profile", vec![]);
+        let result = store.save(&new_profile.verify().unwrap()).await.unwrap();
+        assert_eq!(result, SaveResult::Saved);
+    }
 }
@@ -0,0 +1,395 @@
+//! インメモリストアのイベント本体と二次インデックス
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::models::{Event, EventId, Filter, Pubkey};
+
+/// イベント本体と、クエリの候補絞り込みに使う二次インデックス
+///
+/// イベントの追加・削除は必ずこの構造体を経由し、インデックスとの整合性を保つ。
+#[derive(Default)]
+pub(crate) struct EventTable {
+    /// イベントID -> イベント
+    events: HashMap<EventId, Event>,
+    /// 時系列インデックス（created_at 降順、同タイムスタンプは ID 昇順）
+    by_time: BTreeSet<(Reverse<i64>, EventId)>,
+    /// 作成者 -> イベントID集合
+    by_author: HashMap<Pubkey, HashSet<EventId>>,
+    /// (単一文字タグ名, タグ値) -> イベントID集合
+    by_tag: HashMap<(char, String), HashSet<EventId>>,
+    /// 異なる値の d タグを複数持つイベント
+    ///
+    /// Addressable の置換インデックスは先頭の d タグだけで引くため、
+    /// 2つ目以降の d タグで #d フィルタにマッチするイベントをここから補う。
+    multi_d_tagged: HashSet<EventId>,
+}
+
+impl EventTable {
+    /// 空のテーブルを作成
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// 保持しているイベント数
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// イベントIDでイベントを取得
+    pub(crate) fn get(&self, id: &EventId) -> Option<&Event> {
+        self.events.get(id)
+    }
+
+    /// イベントIDのイベントを保持しているか
+    pub(crate) fn contains_key(&self, id: &EventId) -> bool {
+        self.events.contains_key(id)
+    }
+
+    /// イベントを追加し、インデックスを更新する
+    pub(crate) fn insert(&mut self, event: Event) {
+        // 同一IDが既にあればインデックスごと置き換える
+        self.remove(&event.id);
+
+        self.by_time
+            .insert((Reverse(event.created_at.as_i64()), event.id));
+        self.by_author
+            .entry(event.pubkey)
+            .or_default()
+            .insert(event.id);
+        let tag_keys = indexed_tag_keys(&event);
+        if tag_keys.iter().filter(|(name, _)| *name == 'd').count() > 1 {
+            self.multi_d_tagged.insert(event.id);
+        }
+        for key in tag_keys {
+            self.by_tag.entry(key).or_default().insert(event.id);
+        }
+        self.events.insert(event.id, event);
+    }
+
+    /// イベントを削除し、インデックスからも取り除く
+    pub(crate) fn remove(&mut self, id: &EventId) -> Option<Event> {
+        let event = self.events.remove(id)?;
+
+        self.by_time
+            .remove(&(Reverse(event.created_at.as_i64()), event.id));
+        if let Some(ids) = self.by_author.get_mut(&event.pubkey) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.by_author.remove(&event.pubkey);
+            }
+        }
+        for key in indexed_tag_keys(&event) {
+            if let Some(ids) = self.by_tag.get_mut(&key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.by_tag.remove(&key);
+                }
+            }
+        }
+        self.multi_d_tagged.remove(id);
+
+        Some(event)
+    }
+
+    /// 異なる値の d タグを複数持つイベントを走査する
+    pub(crate) fn multi_d_tagged(&self) -> impl Iterator<Item = &Event> {
+        self.multi_d_tagged
+            .iter()
+            .filter_map(|id| self.events.get(id))
+    }
+
+    /// created_at が `since..=until` に入るイベントを、クエリ結果と同じ順序
+    /// （created_at 降順、同タイムスタンプは ID 昇順）で走査する
+    pub(crate) fn range(
+        &self,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> impl Iterator<Item = &Event> {
+        let upper = until.unwrap_or(i64::MAX);
+        let lower = since.unwrap_or(i64::MIN);
+        // since > until の場合は空（BTreeSet::range は逆転した範囲で panic する）
+        let bounds = (lower <= upper).then(|| {
+            (
+                (Reverse(upper), EventId::from_bytes([0x00; 32])),
+                (Reverse(lower), EventId::from_bytes([0xff; 32])),
+            )
+        });
+        bounds
+            .into_iter()
+            .flat_map(move |(start, end)| self.by_time.range(start..=end))
+            .filter_map(|(_, id)| self.events.get(id))
+    }
+
+    /// フィルタにマッチし得る候補イベントをインデックスから絞り込む
+    ///
+    /// ids / authors / タグフィルタのうち最も候補が少ないものを使う。
+    /// 使えるインデックスがない場合は `None`（全件走査が必要）を返す。
+    /// 返す候補はフィルタの全条件を満たすとは限らないため、呼び出し側で `matches` を適用する。
+    pub(crate) fn candidates(&self, filter: &Filter) -> Option<Vec<&Event>> {
+        // ids指定はそのまま直接引ける（正規化されていないフィルタの重複IDで同じイベントを重ねない）
+        if let Some(ids) = &filter.ids {
+            let ids: BTreeSet<&EventId> = ids.iter().collect();
+            return Some(
+                ids.into_iter()
+                    .filter_map(|id| self.events.get(id))
+                    .collect(),
+            );
+        }
+
+        let mut best: Option<HashSet<EventId>> = None;
+        let mut consider = |set: HashSet<EventId>| {
+            if best.as_ref().is_none_or(|b| set.len() < b.len()) {
+                best = Some(set);
+            }
+        };
+
+        if let Some(authors) = &filter.authors {
+            consider(union_ids(
+                authors.iter().filter_map(|a| self.by_author.get(a)),
+            ));
+        }
+
+        for (tag_name, values) in filter.tags.iter() {
+            consider(union_ids(
+                values
+                    .iter()
+                    .filter_map(|v| self.by_tag.get(&(*tag_name, v.clone()))),
+            ));
+        }
+
+        best.map(|ids| ids.iter().filter_map(|id| self.events.get(id)).collect())
+    }
+}
+
+/// 複数のID集合の和集合（複数値指定はOR条件）
+fn union_ids<'a>(sets: impl Iterator<Item = &'a HashSet<EventId>>) -> HashSet<EventId> {
+    let mut result = HashSet::new();
+    for set in sets {
+        result.extend(set.iter().copied());
+    }
+    result
+}
+
+/// タグインデックスに登録するキー
+///
+/// `Filter::matches` と同じく、タグ名が単一文字で値（tags[1]）を持つタグのみを対象とする。
+fn indexed_tag_keys(event: &Event) -> HashSet<(char, String)> {
+    event
+        .single_letter_tags()
+        .map(|(name, value)| (name, value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{create_custom_event, create_custom_event_with_keypair};
+
+    fn tag_filter(name: char, values: &[&str]) -> Filter {
+        let mut filter = Filter::default();
+        filter
+            .tags
+            .insert(name, values.iter().map(|v| v.to_string()).collect());
+        filter
+    }
+
+    fn candidate_ids(table: &EventTable, filter: &Filter) -> Option<HashSet<EventId>> {
+        table
+            .candidates(filter)
+            .map(|events| events.iter().map(|e| e.id).collect())
+    }
+
+    #[test]
+    fn test_insert_and_remove_keep_indexes_consistent() {
+        let mut table = EventTable::new();
+        let event = create_custom_event(1, 1000, "hello", vec![vec!["e", "aaa"], vec!["p", "bbb"]]);
+        let id = event.id;
+
+        table.insert(event);
+        assert_eq!(table.len(), 1);
+        assert!(table.by_tag.contains_key(&('e', "aaa".to_string())));
+        assert!(table.by_tag.contains_key(&('p', "bbb".to_string())));
+        assert_eq!(table.by_author.len(), 1);
+
+        let removed = table.remove(&id).unwrap();
+        assert_eq!(removed.id, id);
+        assert_eq!(table.len(), 0);
+        // 空になったインデックスエントリは残さない
+        assert!(table.by_tag.is_empty());
+        assert!(table.by_author.is_empty());
+    }
+
+    #[test]
+    fn test_remove_missing_event_returns_none() {
+        let mut table = EventTable::new();
+        let event = create_custom_event(1, 1000, "hello", vec![]);
+        assert!(table.remove(&event.id).is_none());
+    }
+
+    #[test]
+    fn test_only_single_letter_tags_with_value_are_indexed() {
+        let mut table = EventTable::new();
+        let event = create_custom_event(
+            1,
+            1000,
+            "hello",
+            vec![vec!["e", "aaa"], vec!["title", "x"], vec!["t"]],
+        );
+        table.insert(event);
+
+        assert_eq!(table.by_tag.len(), 1);
+        assert!(table.by_tag.contains_key(&('e', "aaa".to_string())));
+    }
+
+    #[test]
+    fn test_candidates_by_tag_values_are_ored() {
+        let mut table = EventTable::new();
+        let e1 = create_custom_event(1, 1000, "1", vec![vec!["e", "aaa"]]);
+        let e2 = create_custom_event(1, 1001, "2", vec![vec!["e", "bbb"]]);
+        let e3 = create_custom_event(1, 1002, "3", vec![vec!["e", "ccc"]]);
+        let expected: HashSet<EventId> = [e1.id, e2.id].into_iter().collect();
+        table.insert(e1);
+        table.insert(e2);
+        table.insert(e3);
+
+        let filter = tag_filter('e', &["aaa", "bbb", "unknown"]);
+        assert_eq!(candidate_ids(&table, &filter), Some(expected));
+    }
+
+    #[test]
+    fn test_candidates_by_authors() {
+        let mut table = EventTable::new();
+        let alice = create_custom_event_with_keypair(1, 1000, "alice", vec![], [0x01; 32]);
+        let bob = create_custom_event_with_keypair(1, 1000, "bob", vec![], [0x02; 32]);
+        let alice_pubkey = alice.pubkey;
+        let alice_id = alice.id;
+        table.insert(alice);
+        table.insert(bob);
+
+        let filter = Filter {
+            authors: Some(vec![alice_pubkey]),
+            ..Default::default()
+        };
+        assert_eq!(
+            candidate_ids(&table, &filter),
+            Some([alice_id].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_candidates_pick_smallest_index() {
+        let mut table = EventTable::new();
+        // 同じ著者の大量イベントのうち1件だけが #e にマッチ
+        let mut target_id = None;
+        for i in 0..10 {
+            let tags = if i == 0 {
+                vec![vec!["e", "aaa"]]
+            } else {
+                vec![]
+            };
+            let event = create_custom_event(1, 1000 + i, "x", tags);
+            if i == 0 {
+                target_id = Some(event.id);
+            }
+            table.insert(event);
+        }
+        let pubkey = table.range(None, None).next().unwrap().pubkey;
+
+        let mut filter = tag_filter('e', &["aaa"]);
+        filter.authors = Some(vec![pubkey]);
+        assert_eq!(
+            candidate_ids(&table, &filter),
+            Some([target_id.unwrap()].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_candidates_without_index_returns_none() {
+        let mut table = EventTable::new();
+        table.insert(create_custom_event(1, 1000, "x", vec![]));
+
+        let filter: Filter = serde_json::from_str(r#"{"kinds": [1]}"#).unwrap();
+        assert!(table.candidates(&filter).is_none());
+    }
+
+    // ========== 時系列インデックス ==========
+
+    fn range_timestamps(table: &EventTable, since: Option<i64>, until: Option<i64>) -> Vec<i64> {
+        table
+            .range(since, until)
+            .map(|e| e.created_at.as_i64())
+            .collect()
+    }
+
+    fn table_with_timestamps(timestamps: &[i64]) -> EventTable {
+        let mut table = EventTable::new();
+        for ts in timestamps {
+            table.insert(create_custom_event(1, *ts, &format!("at {ts}"), vec![]));
+        }
+        table
+    }
+
+    #[test]
+    fn test_range_is_sorted_desc() {
+        let table = table_with_timestamps(&[1003, 1001, 1004, 1002]);
+        assert_eq!(
+            range_timestamps(&table, None, None),
+            vec![1004, 1003, 1002, 1001]
+        );
+    }
+
+    #[test]
+    fn test_range_bounds_are_inclusive() {
+        let table = table_with_timestamps(&[1000, 1001, 1002, 1003, 1004]);
+        assert_eq!(
+            range_timestamps(&table, Some(1001), Some(1003)),
+            vec![1003, 1002, 1001]
+        );
+        // since のみ・until のみ
+        assert_eq!(range_timestamps(&table, Some(1003), None), vec![1004, 1003]);
+        assert_eq!(range_timestamps(&table, None, Some(1001)), vec![1001, 1000]);
+    }
+
+    #[test]
+    fn test_range_with_inverted_bounds_is_empty() {
+        let table = table_with_timestamps(&[1000, 1001]);
+        assert!(range_timestamps(&table, Some(1001), Some(1000)).is_empty());
+    }
+
+    #[test]
+    fn test_range_same_timestamp_ordered_by_id() {
+        let mut table = EventTable::new();
+        for content in ["a", "b", "c", "d"] {
+            table.insert(create_custom_event(1, 1000, content, vec![]));
+        }
+        let ids: Vec<EventId> = table.range(None, None).map(|e| e.id).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn test_range_reflects_removal() {
+        let mut table = table_with_timestamps(&[1000, 1001]);
+        let id = table.range(None, None).next().unwrap().id;
+        table.remove(&id);
+        assert_eq!(range_timestamps(&table, None, None), vec![1000]);
+    }
+
+    #[test]
+    fn test_multi_d_tagged_tracks_only_distinct_d_values() {
+        let mut table = EventTable::new();
+        let single = create_custom_event(30000, 1000, "", vec![vec!["d", "a"], vec!["d", "a"]]);
+        let multi = create_custom_event(30000, 1000, "", vec![vec!["d", "a"], vec!["d", "b"]]);
+        let multi_id = multi.id;
+        table.insert(single);
+        table.insert(multi);
+
+        let ids: Vec<EventId> = table.multi_d_tagged().map(|e| e.id).collect();
+        assert_eq!(ids, vec![multi_id]);
+
+        table.remove(&multi_id);
+        assert_eq!(table.multi_d_tagged().count(), 0);
+    }
+}
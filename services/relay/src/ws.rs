@@ -1,20 +1,26 @@
 //! WebSocket 処理
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use axum::extract::ws::{Message, WebSocket};
-use futures::{SinkExt, StreamExt};
+use futures::StreamExt;
 use tokio::sync::broadcast::error::RecvError;
-use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, instrument, trace, warn};
 
 use crate::config::LimitationConfig;
-use crate::models::{ClientMessage, Event, Filter, RelayMessage, SubscriptionId};
+use crate::connection_registry::ConnectionGuard;
+use crate::models::{
+    ClientMessage, ClientMessageParseError, ConnectionStats, Event, EventId, Filter,
+    MachineReadablePrefix, RelayMessage, SubscriptionId, SubscriptionIdParseError, Timestamp,
+};
 use crate::owner_priority::OwnerPriority;
 use crate::relay::Relay;
 use crate::store::EventStore;
-use crate::store::SaveResult;
+use crate::store::{SaveResult, StoreError};
+
+mod responder;
+use responder::Responder;
 
 /// contentを50文字に切り詰め
 fn truncate_content(content: &str) -> String {
@@ -34,15 +40,11 @@ fn check_event_tags(event: &Event, limitation: &LimitationConfig) -> Option<Rela
             max = limitation.max_event_tags,
             "タグ数が制限を超過"
         );
-        Some(RelayMessage::Ok {
-            event_id: event.id,
-            success: false,
-            message: format!(
-                "invalid: too many tags ({}, max {})",
-                event.tags.len(),
-                limitation.max_event_tags
-            ),
-        })
+        Some(RelayMessage::ok_too_many_tags(
+            event.id,
+            event.tags.len(),
+            limitation.max_event_tags,
+        ))
     } else {
         None
     }
@@ -58,20 +60,41 @@ fn check_content_length(event: &Event, limitation: &LimitationConfig) -> Option<
             max = limitation.max_content_length,
             "コンテンツ長が制限を超過"
         );
-        Some(RelayMessage::Ok {
-            event_id: event.id,
-            success: false,
-            message: format!(
-                "invalid: content too long ({} chars, max {})",
-                content_chars, limitation.max_content_length
-            ),
-        })
+        Some(RelayMessage::ok_content_too_long(
+            event.id,
+            content_chars,
+            limitation.max_content_length,
+        ))
     } else {
         None
     }
 }
 
+/// 現在時刻（UNIX秒）
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// UNIX秒（u64）を created_at と比較できる i64 に変換する。
+/// `as` キャストだと i64::MAX を超える値が負数に化けるため、上限で飽和させる。
+fn unix_secs_to_i64(secs: u64) -> i64 {
+    i64::try_from(secs).unwrap_or(i64::MAX)
+}
+
+/// created_at とサーバ時刻のずれ（秒）を計算する。
+/// 正なら未来、負なら過去のイベント。極端な値でもオーバーフローしない。
+fn created_at_drift_seconds(event: &Event, now: u64) -> i64 {
+    event
+        .created_at
+        .as_i64()
+        .saturating_sub(unix_secs_to_i64(now))
+}
+
 /// イベントのcreated_atを検証する。範囲外の場合は拒否メッセージを返す。
+/// 負の created_at はオーナーを含め全員拒否する。
 /// オーナー本人のイベントには過去制限（lower_limit）を適用しない。
 /// 未来制限（upper_limit）は全員に適用する。
 fn check_created_at(
@@ -79,66 +102,162 @@ fn check_created_at(
     limitation: &LimitationConfig,
     owner_priority: &OwnerPriority,
 ) -> Option<RelayMessage> {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let now = unix_now();
     let event_ts = event.created_at.as_i64();
 
+    // 負の created_at（UNIX エポック以前）は不正な値として扱う
+    if event_ts < 0 {
+        warn!(
+            event_id = %event.id,
+            created_at = event_ts,
+            "created_atが負の値"
+        );
+        return Some(RelayMessage::ok_created_at_negative(event.id, event_ts));
+    }
+
     // 過去制限（オーナー本人はスキップ）
     if !owner_priority.is_owner(&event.pubkey.to_hex()) {
-        let lower_bound = now.saturating_sub(limitation.created_at_lower_limit);
-        if event_ts < lower_bound as i64 {
+        let lower_bound = unix_secs_to_i64(now.saturating_sub(limitation.created_at_lower_limit));
+        if event_ts < lower_bound {
             warn!(
                 event_id = %event.id,
                 created_at = event_ts,
                 lower_bound = lower_bound,
                 "created_atが古すぎる"
             );
-            return Some(RelayMessage::Ok {
-                event_id: event.id,
-                success: false,
-                message: format!(
-                    "invalid: event is too old (created_at_lower_limit: {}s)",
-                    limitation.created_at_lower_limit
-                ),
-            });
+            return Some(RelayMessage::ok_created_at_too_old(
+                event.id,
+                event_ts,
+                lower_bound,
+            ));
         }
     }
 
     // 未来制限（全員に適用）
-    let upper_bound = now.saturating_add(limitation.created_at_upper_limit);
-    if event_ts > upper_bound as i64 {
+    let upper_bound = unix_secs_to_i64(now.saturating_add(limitation.created_at_upper_limit));
+    if event_ts > upper_bound {
         warn!(
             event_id = %event.id,
             created_at = event_ts,
             upper_bound = upper_bound,
             "created_atが未来すぎる"
         );
-        return Some(RelayMessage::Ok {
-            event_id: event.id,
-            success: false,
-            message: format!(
-                "invalid: event is too far in the future (created_at_upper_limit: {}s)",
-                limitation.created_at_upper_limit
-            ),
-        });
+        return Some(RelayMessage::ok_created_at_too_far_in_future(
+            event.id,
+            event_ts,
+            upper_bound,
+        ));
     }
 
     None
 }
 
+/// 初回クエリ用に、フィルタの limit を max_limit にクランプする
+///
+/// limit 未指定のフィルタには default_limit（未設定なら max_limit）を適用し、
+/// どのフィルタも max_limit 件を超えて返さない。ただし since と until を両方指定した
+/// 範囲クエリは範囲内の全件を返せるよう、default_limit ではなく max_limit を上限にする。
+///
+/// `clamp_until_to_now` が有効なら、`now`（UNIX秒）より未来の until も `now` にクランプする。
+fn apply_limit_constraints(
+    filters: &[Filter],
+    limitation: &LimitationConfig,
+    now: u64,
+) -> Vec<Filter> {
+    let max_limit = u64::from(limitation.max_limit);
+    let default_limit = limitation.default_limit.map(u64::from);
+    let now = Timestamp::from_i64(unix_secs_to_i64(now));
+    filters
+        .iter()
+        .map(|filter| Filter {
+            limit: match filter.limit {
+                Some(limit) => Some(limit.min(max_limit)),
+                None if filter.since.is_some() && filter.until.is_some() => Some(max_limit),
+                None => Some(default_limit.map_or(max_limit, |limit| limit.min(max_limit))),
+            },
+            until: match filter.until {
+                Some(until) if limitation.clamp_until_to_now && until.as_i64() > now.as_i64() => {
+                    Some(now)
+                }
+                until => until,
+            },
+            ..filter.clone()
+        })
+        .collect()
+}
+
+/// パースに失敗したメッセージが REQ であれば、そのサブスクリプションIDを取り出す。
+/// フィルタ（ids/authors の hex 不正など）や ID の文字種だけが不正な REQ に CLOSED で応答するために使う。
+fn rejected_req_subscription_id(text: &str) -> Option<SubscriptionId> {
+    let value: serde_json::Value = serde_json::from_str(text).ok()?;
+    let array = value.as_array()?;
+    if array.first()?.as_str()? != "REQ" {
+        return None;
+    }
+    let raw = array.get(1)?.as_str()?;
+    match raw.parse() {
+        Ok(subscription_id) => Some(subscription_id),
+        // 制御文字を含むIDも CLOSED で拒否する（応答の JSON ではエスケープされる）
+        Err(SubscriptionIdParseError::ControlCharacter(_)) => {
+            Some(SubscriptionId::new_unchecked(raw))
+        }
+        Err(_) => None,
+    }
+}
+
+/// 1つのサブスクリプションの状態
+struct Subscription {
+    filters: Vec<Filter>,
+    /// EOSE を送信済みか。送信するまではリアルタイム配信の対象にしない
+    eose_sent: bool,
+    /// EOSE までに保存済みイベントとして送ったイベントID
+    ///
+    /// クエリ完了前に broadcast チャネルへ入った同じイベントを、
+    /// EOSE 後にリアルタイム配信としてもう一度送らないために使う。
+    sent_before_eose: HashSet<EventId>,
+}
+
+impl Subscription {
+    fn new(filters: Vec<Filter>) -> Self {
+        Self {
+            filters,
+            eose_sent: false,
+            sent_before_eose: HashSet::new(),
+        }
+    }
+}
+
 /// 各接続が保持するサブスクリプション状態
 struct ConnectionState {
-    subscriptions: HashMap<SubscriptionId, Vec<Filter>>,
+    subscriptions: HashMap<SubscriptionId, Subscription>,
+    /// クライアントから受け取った EVENT メッセージ数（STATS 用）
+    events_received: u64,
+    /// クライアントへの送信に回した EVENT メッセージ数（STATS 用）
+    events_sent: u64,
 }
 
 impl ConnectionState {
     fn new() -> Self {
         Self {
             subscriptions: HashMap::new(),
+            events_received: 0,
+            events_sent: 0,
         }
     }
+
+    /// 実質同じフィルタを持つ別IDのサブスクリプションを探す（同じIDは上書きなので対象外）
+    fn find_equivalent_subscription(
+        &self,
+        subscription_id: &SubscriptionId,
+        filters: &[Filter],
+    ) -> Option<&SubscriptionId> {
+        self.subscriptions
+            .iter()
+            .find(|(id, existing)| {
+                *id != subscription_id && Filter::equivalent_sets(&existing.filters, filters)
+            })
+            .map(|(id, _)| id)
+    }
 }
 
 /// サーバーサイドPingの送信間隔（デフォルト: 5分）
@@ -153,39 +272,501 @@ fn ping_interval() -> std::time::Duration {
     std::time::Duration::from_secs(secs)
 }
 
+/// 1接続分のクライアントメッセージ処理
+///
+/// テキストメッセージ1件を受け取り、パース→ディスパッチ→応答の組み立てまでを行う。
+/// 応答の送信は呼び出し側（`handle_socket`）が `Responder` を通して返された順に行う。
+struct MessageHandler<S: EventStore> {
+    relay: Arc<Relay<S>>,
+    limitation: Arc<LimitationConfig>,
+    owner_priority: Arc<OwnerPriority>,
+    state: ConnectionState,
+}
+
+impl<S: EventStore> MessageHandler<S> {
+    fn new(
+        relay: Arc<Relay<S>>,
+        limitation: Arc<LimitationConfig>,
+        owner_priority: Arc<OwnerPriority>,
+    ) -> Self {
+        Self {
+            relay,
+            limitation,
+            owner_priority,
+            state: ConnectionState::new(),
+        }
+    }
+
+    /// テキストメッセージ1件を処理し、クライアントへ送る応答を返す
+    async fn handle_text(&mut self, text: &str) -> Vec<RelayMessage> {
+        // max_message_length チェック
+        let msg_len = text.len();
+        if msg_len > self.limitation.max_message_length as usize {
+            warn!(
+                message_length = msg_len,
+                max = self.limitation.max_message_length,
+                "メッセージ長が制限を超過"
+            );
+            return vec![RelayMessage::Notice(format!(
+                "メッセージが長すぎます: {}バイト（上限: {}バイト）",
+                msg_len, self.limitation.max_message_length
+            ))];
+        }
+
+        // ClientMessage をパース
+        let client_msg: ClientMessage = match serde_json::from_str(text) {
+            Ok(msg) => msg,
+            Err(e) => {
+                // フィルタが不正な REQ は CLOSED で拒否する（同じIDの既存購読も終了）
+                if let Some(subscription_id) = rejected_req_subscription_id(text) {
+                    // ID に制御文字が含まれうるので Debug 形式でログに出す
+                    warn!(subscription_id = ?subscription_id, error = %e, "REQが不正");
+                    self.state.subscriptions.remove(&subscription_id);
+                    return vec![RelayMessage::closed(
+                        subscription_id,
+                        MachineReadablePrefix::Invalid,
+                        &e.to_string(),
+                    )];
+                }
+
+                // それ以外のパースエラー時は NOTICE を送信
+                warn!(error = %e, "メッセージパースエラー");
+                return vec![RelayMessage::Notice(format!("パースエラー: {e}"))];
+            }
+        };
+
+        // メッセージ種別に応じた処理
+        match client_msg {
+            ClientMessage::Event(event) => {
+                self.state.events_received += 1;
+                vec![self.handle_event(event).await]
+            }
+            ClientMessage::Req {
+                subscription_id,
+                filters,
+            } => self.handle_req(subscription_id, filters).await,
+            ClientMessage::Close(subscription_id) => vec![self.handle_close(subscription_id)],
+            ClientMessage::Stats => vec![self.handle_stats()],
+        }
+    }
+
+    /// STATS: この接続の統計情報を返す（NIP 標準外の診断用コマンド）
+    ///
+    /// EVENT の送信数は応答として送信に回した時点で数える（WebSocket への書き込み完了ではない）。
+    fn handle_stats(&self) -> RelayMessage {
+        if !self.limitation.enable_stats_command {
+            let e = ClientMessageParseError::UnknownMessageType("STATS".to_string());
+            warn!(error = %e, "STATSコマンドは無効");
+            return RelayMessage::Notice(format!("パースエラー: {e}"));
+        }
+        RelayMessage::StatsResult(ConnectionStats {
+            events_received: self.state.events_received,
+            events_sent: self.state.events_sent,
+            subscriptions_active: self.state.subscriptions.len(),
+        })
+    }
+
+    /// EVENT: 制限値・署名を検証して保存し、OK を返す
+    ///
+    /// 検証で拒否（OK false）する場合はストアにも broadcast にも触れない。
+    async fn handle_event(&self, event: Event) -> RelayMessage {
+        // 受信から配信（broadcast への送信）完了までの所要時間を計測する
+        let received_at = std::time::Instant::now();
+        let event_id = event.id;
+        let kind = event.kind.as_u16();
+
+        debug!(
+            event_id = %event_id,
+            kind = kind,
+            pubkey = %event.pubkey.to_hex(),
+            content = %truncate_content(&event.content),
+            "EVENTメッセージ受信"
+        );
+
+        // 制限値チェック: タグ数
+        if let Some(reject) = check_event_tags(&event, &self.limitation) {
+            return reject;
+        }
+
+        // 制限値チェック: コンテンツ長
+        if let Some(reject) = check_content_length(&event, &self.limitation) {
+            return reject;
+        }
+
+        // 制限値チェック: created_at（過去・未来）
+        if let Some(reject) = check_created_at(&event, &self.limitation, &self.owner_priority) {
+            return reject;
+        }
+
+        // 署名検証
+        let verify_started_at = std::time::Instant::now();
+        let verified = match event.verify() {
+            Ok(v) => v,
+            Err(e) => {
+                warn!(
+                    event_id = %event_id,
+                    error = %e,
+                    "署名検証失敗"
+                );
+                if !self.limitation.verbose_errors {
+                    return RelayMessage::ok_verification_failed_without_reason(event_id);
+                }
+                return RelayMessage::ok_verification_failed(event_id, &e);
+            }
+        };
+
+        // NIP-70: 保護イベントチェック
+        // `["-"]` タグ付きイベントはNIP-42認証済みの著者のみが投稿可能。
+        // NIP-42未実装のため、保護イベントはすべて拒否する。
+        // TODO(NIP-42): 認証実装時、ここを認証済み+pubkey一致チェックに変更する
+        if verified.is_protected() {
+            warn!(
+                event_id = %event_id,
+                "保護イベントを拒否（NIP-42未実装）"
+            );
+            // NIP-70 は未認証の著者を `auth-required:` で拒否するが、AUTH を受け付けないため
+            // 認証を促さない `restricted:` で返す
+            return RelayMessage::ok_restricted(
+                event_id,
+                "this relay does not accept protected events. NIP-42 authentication is not supported.",
+            );
+        }
+
+        let verify_duration = verify_started_at.elapsed();
+
+        // 許容範囲内の created_at のずれを可視化するため、保存時に記録する
+        let drift = created_at_drift_seconds(&verified, unix_now());
+
+        // 保存 & broadcast
+        let published = if self.limitation.distribute_before_persist {
+            self.relay.publish_distribute_first(verified).await
+        } else {
+            self.relay.publish_timed(verified).await
+        };
+        let (result, timing) = match published {
+            Ok(published) => published,
+            // ストレージの制約で保存できないイベントは、内部エラーではなく不正なイベントとして返す
+            Err(e @ StoreError::EventTooLarge { .. }) => {
+                warn!(
+                    event_id = %event_id,
+                    error = %e,
+                    "ストレージの上限を超えるイベントを拒否"
+                );
+                return RelayMessage::ok_rejected(
+                    event_id,
+                    MachineReadablePrefix::Invalid,
+                    &e.to_string(),
+                );
+            }
+            Err(e) => {
+                error!(
+                    event_id = %event_id,
+                    error = %e,
+                    "イベント保存エラー"
+                );
+                return RelayMessage::ok_store_error(event_id, &e);
+            }
+        };
+        info!(
+            event_id = %event_id,
+            kind = kind,
+            save_result = ?result,
+            event_processing_duration_seconds = received_at.elapsed().as_secs_f64(),
+            save_duration_seconds = timing.save.as_secs_f64(),
+            distribute_duration_seconds = timing.distribute.as_secs_f64(),
+            "EVENT処理時間"
+        );
+
+        let response = match result {
+            SaveResult::Saved => {
+                info!(
+                    event_id = %event_id,
+                    kind = kind,
+                    event_created_at_drift_seconds = drift,
+                    "イベント保存成功"
+                );
+                RelayMessage::ok_accepted(event_id)
+            }
+            SaveResult::Duplicate => {
+                debug!(
+                    event_id = %event_id,
+                    "重複イベント検出"
+                );
+                RelayMessage::ok_duplicate(event_id)
+            }
+            SaveResult::Replaced { old_id } => {
+                info!(
+                    event_id = %event_id,
+                    replaced_event_id = %old_id,
+                    kind = kind,
+                    event_created_at_drift_seconds = drift,
+                    "イベント置換成功"
+                );
+                RelayMessage::ok_replaced(event_id)
+            }
+            SaveResult::Ephemeral => {
+                debug!(
+                    event_id = %event_id,
+                    kind = kind,
+                    "ephemeralイベント配信完了"
+                );
+                RelayMessage::ok_accepted(event_id)
+            }
+            SaveResult::Ignored => {
+                debug!(
+                    event_id = %event_id,
+                    "イベント無視（古いバージョン）"
+                );
+                RelayMessage::ok_ignored(event_id)
+            }
+        };
+        if self.limitation.include_timing_in_ok {
+            response.with_timing(verify_duration, timing.save)
+        } else {
+            response
+        }
+    }
+
+    /// REQ: サブスクリプションを登録し、既存イベントと EOSE を返す
+    ///
+    /// 応答は「EVENT（0件以上）→ EOSE」か「CLOSED のみ」のどちらか一方になる。
+    /// マッチするイベントが0件でも EOSE は必ず返し、制限超過やクエリエラーで
+    /// 購読を受け付けない場合は CLOSED だけを返して EOSE は送らない。
+    /// CLOSED を返すときは同じIDの既存購読も削除するので、同じIDでの再 REQ は新規購読になる。
+    ///
+    /// 保存済みとリアルタイムの境界は created_at ではなくイベントIDで管理する。
+    /// created_at はクライアントが付ける値なので、REQ 受付時刻で区切ると、受付後に届いた
+    /// 過去の created_at のイベント（時刻ずれ・他リレーからの転送）がどちらにも含まれず欠落する。
+    /// クエリで送ったイベントのIDを `sent_before_eose` に記録し、EOSE 後の broadcast から除く。
+    async fn handle_req(
+        &mut self,
+        subscription_id: SubscriptionId,
+        filters: Vec<Filter>,
+    ) -> Vec<RelayMessage> {
+        debug!(
+            subscription_id = %subscription_id,
+            filter_count = filters.len(),
+            "REQメッセージ受信"
+        );
+
+        // 制限値チェック: フィルタ数
+        if filters.len() > self.limitation.max_filters as usize {
+            warn!(
+                subscription_id = %subscription_id,
+                filter_count = filters.len(),
+                max = self.limitation.max_filters,
+                "フィルタ数が制限を超過"
+            );
+            self.state.subscriptions.remove(&subscription_id);
+            return vec![RelayMessage::closed_too_many_filters(
+                subscription_id,
+                filters.len(),
+                self.limitation.max_filters,
+            )];
+        }
+
+        // 条件のないフィルタ（全件ダウンロード）の拒否
+        if self.limitation.require_filter && filters.iter().any(Filter::is_unconditional) {
+            warn!(
+                subscription_id = %subscription_id,
+                "条件のないフィルタを拒否"
+            );
+            self.state.subscriptions.remove(&subscription_id);
+            return vec![RelayMessage::closed_unconditional_filter(subscription_id)];
+        }
+
+        // 制限値チェック: サブスクリプション数
+        // 同じIDの上書きは数に含めない
+        if !self.state.subscriptions.contains_key(&subscription_id)
+            && self.state.subscriptions.len() >= self.limitation.max_subscriptions as usize
+        {
+            warn!(
+                subscription_id = %subscription_id,
+                current = self.state.subscriptions.len(),
+                max = self.limitation.max_subscriptions,
+                "サブスクリプション数が制限を超過"
+            );
+            return vec![RelayMessage::closed_too_many_subscriptions(
+                subscription_id,
+                self.state.subscriptions.len(),
+                self.limitation.max_subscriptions,
+            )];
+        }
+
+        // 各フィルタの値を重複排除・ソートしてから登録・クエリする（マッチ結果は変わらない）
+        let filters: Vec<Filter> = filters.iter().map(Filter::normalized).collect();
+
+        // 重複購読チェック: 同一接続で実質同じフィルタの購読が既にあるか
+        if let Some(existing_id) = self
+            .state
+            .find_equivalent_subscription(&subscription_id, &filters)
+        {
+            warn!(
+                subscription_id = %subscription_id,
+                existing_subscription_id = %existing_id,
+                "同一フィルタの購読が既に存在"
+            );
+            if self.limitation.reject_duplicate_subscriptions {
+                let message = format!("same filters as subscription {existing_id}");
+                self.state.subscriptions.remove(&subscription_id);
+                return vec![RelayMessage::closed(
+                    subscription_id,
+                    MachineReadablePrefix::Duplicate,
+                    &message,
+                )];
+            }
+        }
+
+        // サブスクリプション登録（既存は上書き）。EOSE を送るまではリアルタイム配信しない
+        self.state
+            .subscriptions
+            .insert(subscription_id.clone(), Subscription::new(filters.clone()));
+        info!(
+            subscription_id = %subscription_id,
+            filter_count = filters.len(),
+            "サブスクリプション作成"
+        );
+
+        // 既存イベントをクエリして送信
+        let query_filters = apply_limit_constraints(&filters, &self.limitation, unix_now());
+        let events = match self.relay.query(&query_filters).await {
+            Ok(events) => events,
+            Err(e) => {
+                error!(
+                    subscription_id = %subscription_id,
+                    error = %e,
+                    "クエリエラー"
+                );
+                // エラー時はサブスクリプションを削除
+                self.state.subscriptions.remove(&subscription_id);
+                // NIP-01: REQエラー時はCLOSEDを送信
+                return vec![RelayMessage::closed_subscription_error(subscription_id, &e)];
+            }
+        };
+        debug!(
+            subscription_id = %subscription_id,
+            result_count = events.len(),
+            "クエリ結果送信"
+        );
+
+        // EOSE を境にリアルタイム配信へ切り替える
+        if let Some(subscription) = self.state.subscriptions.get_mut(&subscription_id) {
+            subscription.sent_before_eose = events.iter().map(|event| event.id).collect();
+            subscription.eose_sent = true;
+        }
+
+        let mut responses: Vec<RelayMessage> = events
+            .into_iter()
+            .map(|event| RelayMessage::Event {
+                subscription_id: subscription_id.clone(),
+                event,
+            })
+            .collect();
+        self.state.events_sent += responses.len() as u64;
+
+        // EOSE を送信
+        trace!(subscription_id = %subscription_id, "EOSE送信");
+        responses.push(RelayMessage::Eose(subscription_id));
+        responses
+    }
+
+    /// CLOSE: サブスクリプションを削除し、CLOSED を返す
+    fn handle_close(&mut self, subscription_id: SubscriptionId) -> RelayMessage {
+        debug!(subscription_id = %subscription_id, "CLOSEメッセージ受信");
+
+        // サブスクリプション削除
+        self.state.subscriptions.remove(&subscription_id);
+        info!(subscription_id = %subscription_id, "サブスクリプション削除");
+
+        RelayMessage::closed_by_client(subscription_id)
+    }
+
+    /// broadcast で届いたイベントを、マッチする自分のサブスクリプション宛の EVENT にする
+    ///
+    /// EOSE 前のサブスクリプションと、保存済みイベントとして送信済みのイベントは対象外。
+    fn route_broadcast(&mut self, event: &Event) -> Vec<RelayMessage> {
+        let mut routed = Vec::new();
+        for (sub_id, subscription) in &mut self.state.subscriptions {
+            if !subscription.eose_sent || !subscription.filters.iter().any(|f| f.matches(event)) {
+                continue;
+            }
+            if subscription.sent_before_eose.remove(&event.id) {
+                trace!(
+                    subscription_id = %sub_id,
+                    event_id = %event.id,
+                    "保存済みイベントとして送信済みのためスキップ"
+                );
+                continue;
+            }
+            trace!(
+                subscription_id = %sub_id,
+                event_id = %event.id,
+                "broadcastイベントをクライアントに転送"
+            );
+            routed.push(RelayMessage::Event {
+                subscription_id: sub_id.clone(),
+                event: event.clone(),
+            });
+        }
+        self.state.events_sent += routed.len() as u64;
+        routed
+    }
+
+    /// クエリ前から broadcast チャネルに溜まっていたイベントを処理し終えたときに呼ぶ
+    ///
+    /// 以降に届くイベントはクエリ結果に含まれえないので、送信済みIDの記録を捨てる。
+    fn settle_broadcast_backlog(&mut self) {
+        for subscription in self.state.subscriptions.values_mut() {
+            if !subscription.sent_before_eose.is_empty() {
+                subscription.sent_before_eose = HashSet::new();
+            }
+        }
+    }
+}
+
 /// WebSocket 接続を処理
-#[instrument(skip(socket, relay, limitation, owner_priority, shutdown), fields(connection_id = %conn_id))]
+///
+/// 1接続内のメッセージは受信順に1件ずつ処理する（前のメッセージの応答を送るまで次を読まない）。
+/// そのため同一接続から連続送信された Replaceable イベントの最終状態は送信順で決まる。
+#[instrument(skip(socket, relay, limitation, owner_priority, connection), fields(connection_id = %conn_id))]
 pub async fn handle_socket<S: EventStore + 'static>(
     socket: WebSocket,
     relay: Arc<Relay<S>>,
     conn_id: String,
     limitation: Arc<LimitationConfig>,
     owner_priority: Arc<OwnerPriority>,
-    shutdown: CancellationToken,
+    mut connection: ConnectionGuard,
 ) {
     info!("WebSocket接続を確立");
 
-    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (ws_tx, mut ws_rx) = socket.split();
+    let mut responder = Responder::new(ws_tx);
     let mut event_rx = relay.subscribe();
-    let mut state = ConnectionState::new();
+    let mut handler = MessageHandler::new(relay, limitation, owner_priority);
+    let shutdown = connection.cancellation_token();
     let mut ping_timer = tokio::time::interval(ping_interval());
     // 最初のtickは即座に発火するのでスキップ
     ping_timer.tick().await;
 
     loop {
         tokio::select! {
-            // シャットダウン通知: Closeフレームを送信して接続を終了
+            // シャットダウン通知・強制切断: Closeフレームを送信して接続を終了
             _ = shutdown.cancelled() => {
-                info!("シャットダウン通知受信、Closeフレームを送信");
-                let _ = ws_tx.send(Message::Close(None)).await;
+                info!("シャットダウン・切断通知受信、Closeフレームを送信");
+                responder.close().await;
                 return;
             }
 
+            // 全接続向け NOTICE（メンテナンス告知など）
+            notice = connection.recv_notice() => {
+                if responder.send(&RelayMessage::Notice(notice)).await.is_err() {
+                    return;
+                }
+            }
+
             // サーバーサイドPing送信（CloudFront idle timeout対策）
             _ = ping_timer.tick() => {
-                trace!("サーバーサイドPing送信");
-                if ws_tx.send(Message::Ping(vec![].into())).await.is_err() {
-                    info!("Ping送信失敗、接続を切断");
+                if responder.ping().await.is_err() {
                     return;
                 }
             }
@@ -227,334 +808,12 @@ pub async fn handle_socket<S: EventStore + 'static>(
 
                 trace!(raw_message = %text, "生メッセージ受信");
 
-                // max_message_length チェック
-                let msg_len = text.len();
-                if msg_len > limitation.max_message_length as usize {
-                    warn!(
-                        message_length = msg_len,
-                        max = limitation.max_message_length,
-                        "メッセージ長が制限を超過"
-                    );
-                    let notice = RelayMessage::Notice(
-                        format!("メッセージが長すぎます: {}バイト（上限: {}バイト）", msg_len, limitation.max_message_length)
-                    );
-                    if send_message(&mut ws_tx, &notice).await.is_err() {
-                        return;
-                    }
-                    continue;
+                let responses = handler.handle_text(&text).await;
+                if responder.send_all(responses).await.is_err() {
+                    return;
                 }
-
-                // ClientMessage をパース
-                let client_msg: ClientMessage = match serde_json::from_str(&text) {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        // パースエラー時は NOTICE を送信
-                        warn!(error = %e, "メッセージパースエラー");
-                        let notice = RelayMessage::Notice(format!("パースエラー: {e}"));
-                        if send_message(&mut ws_tx, &notice).await.is_err() {
-                            return;
-                        }
-                        continue;
-                    }
-                };
-
-                // メッセージ種別に応じた処理
-                match client_msg {
-                    ClientMessage::Event(event) => {
-                        let event_id = event.id;
-                        let kind = event.kind.as_u16();
-                        let pubkey = event.pubkey.to_hex();
-                        let content_preview = truncate_content(&event.content);
-
-                        debug!(
-                            event_id = %event_id,
-                            kind = kind,
-                            pubkey = %pubkey,
-                            content = %content_preview,
-                            "EVENTメッセージ受信"
-                        );
-
-                        // 制限値チェック: タグ数
-                        if let Some(reject) = check_event_tags(&event, &limitation) {
-                            if send_message(&mut ws_tx, &reject).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // 制限値チェック: コンテンツ長
-                        if let Some(reject) = check_content_length(&event, &limitation) {
-                            if send_message(&mut ws_tx, &reject).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // 制限値チェック: created_at（過去・未来）
-                        if let Some(reject) = check_created_at(&event, &limitation, &owner_priority) {
-                            if send_message(&mut ws_tx, &reject).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // 署名検証
-                        let verified = match event.verify() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                // 検証失敗
-                                warn!(
-                                    event_id = %event_id,
-                                    error = %e,
-                                    "署名検証失敗"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: false,
-                                    message: format!("invalid: {e}"),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                                continue;
-                            }
-                        };
-
-                        // NIP-70: 保護イベントチェック
-                        // `["-"]` タグ付きイベントはNIP-42認証済みの著者のみが投稿可能。
-                        // NIP-42未実装のため、保護イベントはすべて拒否する。
-                        // TODO(NIP-42): 認証実装時、ここを認証済み+pubkey一致チェックに変更する
-                        if verified.is_protected() {
-                            warn!(
-                                event_id = %event_id,
-                                "保護イベントを拒否（NIP-42未実装）"
-                            );
-                            let ok_msg = RelayMessage::Ok {
-                                event_id,
-                                success: false,
-                                message: "blocked: this relay does not accept protected events. NIP-42 authentication is not supported.".to_string(),
-                            };
-                            if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // 保存 & broadcast
-                        match relay.publish(verified).await {
-                            Ok(SaveResult::Saved) => {
-                                info!(
-                                    event_id = %event_id,
-                                    kind = kind,
-                                    "イベント保存成功"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: true,
-                                    message: String::new(),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                            Ok(SaveResult::Duplicate) => {
-                                debug!(
-                                    event_id = %event_id,
-                                    "重複イベント検出"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: true,
-                                    message: "duplicate: already have this event".to_string(),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                            Ok(SaveResult::Replaced) => {
-                                info!(
-                                    event_id = %event_id,
-                                    kind = kind,
-                                    "イベント置換成功"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: true,
-                                    message: "replaced: updated existing event".to_string(),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                            Ok(SaveResult::Ephemeral) => {
-                                debug!(
-                                    event_id = %event_id,
-                                    kind = kind,
-                                    "ephemeralイベント配信完了"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: true,
-                                    message: String::new(),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                            Ok(SaveResult::Ignored) => {
-                                debug!(
-                                    event_id = %event_id,
-                                    "イベント無視（古いバージョン）"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: true,
-                                    message: "ignored: newer event exists".to_string(),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                            Err(e) => {
-                                error!(
-                                    event_id = %event_id,
-                                    error = %e,
-                                    "イベント保存エラー"
-                                );
-                                let ok_msg = RelayMessage::Ok {
-                                    event_id,
-                                    success: false,
-                                    message: format!("error: {e}"),
-                                };
-                                if send_message(&mut ws_tx, &ok_msg).await.is_err() {
-                                    return;
-                                }
-                            }
-                        }
-                    }
-
-                    ClientMessage::Req { subscription_id, filters } => {
-                        debug!(
-                            subscription_id = %subscription_id,
-                            filter_count = filters.len(),
-                            "REQメッセージ受信"
-                        );
-
-                        // 制限値チェック: フィルタ数
-                        if filters.len() > limitation.max_filters as usize {
-                            warn!(
-                                subscription_id = %subscription_id,
-                                filter_count = filters.len(),
-                                max = limitation.max_filters,
-                                "フィルタ数が制限を超過"
-                            );
-                            let closed = RelayMessage::Closed {
-                                subscription_id,
-                                message: format!(
-                                    "error: too many filters ({}, max {})",
-                                    filters.len(), limitation.max_filters
-                                ),
-                            };
-                            if send_message(&mut ws_tx, &closed).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // 制限値チェック: サブスクリプション数
-                        // 同じIDの上書きは数に含めない
-                        if !state.subscriptions.contains_key(&subscription_id)
-                            && state.subscriptions.len() >= limitation.max_subscriptions as usize
-                        {
-                            warn!(
-                                subscription_id = %subscription_id,
-                                current = state.subscriptions.len(),
-                                max = limitation.max_subscriptions,
-                                "サブスクリプション数が制限を超過"
-                            );
-                            let closed = RelayMessage::Closed {
-                                subscription_id,
-                                message: format!(
-                                    "error: too many subscriptions ({}, max {})",
-                                    state.subscriptions.len(), limitation.max_subscriptions
-                                ),
-                            };
-                            if send_message(&mut ws_tx, &closed).await.is_err() {
-                                return;
-                            }
-                            continue;
-                        }
-
-                        // サブスクリプション登録（既存は上書き）
-                        state.subscriptions.insert(subscription_id.clone(), filters.clone());
-                        info!(
-                            subscription_id = %subscription_id,
-                            filter_count = filters.len(),
-                            "サブスクリプション作成"
-                        );
-
-                        // 既存イベントをクエリして送信
-                        match relay.query(&filters).await {
-                            Ok(events) => {
-                                debug!(
-                                    subscription_id = %subscription_id,
-                                    result_count = events.len(),
-                                    "クエリ結果送信"
-                                );
-                                for event in events {
-                                    let event_msg = RelayMessage::Event {
-                                        subscription_id: subscription_id.clone(),
-                                        event,
-                                    };
-                                    if send_message(&mut ws_tx, &event_msg).await.is_err() {
-                                        return;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!(
-                                    subscription_id = %subscription_id,
-                                    error = %e,
-                                    "クエリエラー"
-                                );
-                                // NIP-01: REQエラー時はCLOSEDを送信
-                                let closed = RelayMessage::Closed {
-                                    subscription_id: subscription_id.clone(),
-                                    message: format!("error: {e}"),
-                                };
-                                if send_message(&mut ws_tx, &closed).await.is_err() {
-                                    return;
-                                }
-                                // エラー時はサブスクリプションを削除
-                                state.subscriptions.remove(&subscription_id);
-                                continue;
-                            }
-                        }
-
-                        // EOSE を送信
-                        trace!(subscription_id = %subscription_id, "EOSE送信");
-                        let eose = RelayMessage::Eose(subscription_id);
-                        if send_message(&mut ws_tx, &eose).await.is_err() {
-                            return;
-                        }
-                    }
-
-                    ClientMessage::Close(subscription_id) => {
-                        debug!(subscription_id = %subscription_id, "CLOSEメッセージ受信");
-
-                        // サブスクリプション削除
-                        state.subscriptions.remove(&subscription_id);
-                        info!(subscription_id = %subscription_id, "サブスクリプション削除");
-
-                        // CLOSED を送信
-                        let closed = RelayMessage::Closed {
-                            subscription_id,
-                            message: String::new(),
-                        };
-                        if send_message(&mut ws_tx, &closed).await.is_err() {
-                            return;
-                        }
-                    }
+                if event_rx.is_empty() {
+                    handler.settle_broadcast_backlog();
                 }
             }
 
@@ -578,43 +837,26 @@ pub async fn handle_socket<S: EventStore + 'static>(
                 };
 
                 // 自分のサブスクリプションとマッチング
-                for (sub_id, filters) in &state.subscriptions {
-                    if filters.iter().any(|f| f.matches(&event)) {
-                        trace!(
-                            subscription_id = %sub_id,
-                            event_id = %event.id,
-                            "broadcastイベントをクライアントに転送"
-                        );
-                        let event_msg = RelayMessage::Event {
-                            subscription_id: sub_id.clone(),
-                            event: event.clone(),
-                        };
-                        if send_message(&mut ws_tx, &event_msg).await.is_err() {
-                            return;
-                        }
-                    }
+                if responder.send_all(handler.route_broadcast(&event)).await.is_err() {
+                    return;
+                }
+                if event_rx.is_empty() {
+                    handler.settle_broadcast_backlog();
                 }
             }
         }
     }
 }
 
-/// RelayMessage を WebSocket で送信するヘルパー
-async fn send_message<S>(ws_tx: &mut S, msg: &RelayMessage) -> Result<(), ()>
-where
-    S: SinkExt<Message> + Unpin,
-    S::Error: std::fmt::Debug,
-{
-    let json = serde_json::to_string(msg).map_err(|_| ())?;
-    ws_tx.send(Message::Text(json.into())).await.map_err(|_| ())
-}
-
 #[cfg(test)]
 mod tests {
     // WebSocket のテストは統合テストで行う
-    // ユニットテストでは ConnectionState のみテスト
+    // ユニットテストでは ConnectionState と MessageHandler の応答組み立てをテスト
 
     use super::*;
+    use crate::models::VerifiedEvent;
+    use crate::retention::RetentionPolicy;
+    use crate::store::DeleteResult;
 
     #[test]
     fn test_connection_state_new() {
@@ -628,7 +870,9 @@ mod tests {
         let sub_id: SubscriptionId = "sub1".parse().unwrap();
         let filters = vec![Filter::default()];
 
-        state.subscriptions.insert(sub_id.clone(), filters);
+        state
+            .subscriptions
+            .insert(sub_id.clone(), Subscription::new(filters));
         assert!(state.subscriptions.contains_key(&sub_id));
     }
 
@@ -638,7 +882,9 @@ mod tests {
         let sub_id: SubscriptionId = "sub1".parse().unwrap();
         let filters = vec![Filter::default()];
 
-        state.subscriptions.insert(sub_id.clone(), filters);
+        state
+            .subscriptions
+            .insert(sub_id.clone(), Subscription::new(filters));
         state.subscriptions.remove(&sub_id);
         assert!(!state.subscriptions.contains_key(&sub_id));
     }
@@ -712,7 +958,9 @@ mod tests {
         let sub_id: SubscriptionId = "sub1".parse().unwrap();
 
         let filters1 = vec![Filter::default()];
-        state.subscriptions.insert(sub_id.clone(), filters1);
+        state
+            .subscriptions
+            .insert(sub_id.clone(), Subscription::new(filters1));
 
         let filters2 = vec![
             Filter {
@@ -721,11 +969,1180 @@ mod tests {
             },
             Filter::default(),
         ];
-        state.subscriptions.insert(sub_id.clone(), filters2);
+        state
+            .subscriptions
+            .insert(sub_id.clone(), Subscription::new(filters2));
 
         // 上書きされている
-        let filters = state.subscriptions.get(&sub_id).unwrap();
+        let filters = &state.subscriptions.get(&sub_id).unwrap().filters;
         assert_eq!(filters.len(), 2);
         assert_eq!(filters[0].limit, Some(10));
     }
+
+    // ========== 重複購読の検出 ==========
+
+    fn parse_filters(json: &str) -> Vec<Filter> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_find_equivalent_subscription_detects_normalized_duplicate() {
+        let mut state = ConnectionState::new();
+        let sub1: SubscriptionId = "sub1".parse().unwrap();
+        state.subscriptions.insert(
+            sub1.clone(),
+            Subscription::new(parse_filters(r#"[{"kinds": [1, 1, 7]}]"#)),
+        );
+
+        let sub2: SubscriptionId = "sub2".parse().unwrap();
+        let found =
+            state.find_equivalent_subscription(&sub2, &parse_filters(r#"[{"kinds": [7, 1]}]"#));
+        assert_eq!(found, Some(&sub1));
+    }
+
+    #[test]
+    fn test_find_equivalent_subscription_ignores_same_id() {
+        // 同じIDでの再REQは上書きなので重複扱いしない
+        let mut state = ConnectionState::new();
+        let sub1: SubscriptionId = "sub1".parse().unwrap();
+        let filters = parse_filters(r#"[{"kinds": [1]}]"#);
+        state
+            .subscriptions
+            .insert(sub1.clone(), Subscription::new(filters.clone()));
+
+        assert_eq!(state.find_equivalent_subscription(&sub1, &filters), None);
+    }
+
+    #[test]
+    fn test_find_equivalent_subscription_different_filters() {
+        let mut state = ConnectionState::new();
+        let sub1: SubscriptionId = "sub1".parse().unwrap();
+        state.subscriptions.insert(
+            sub1,
+            Subscription::new(parse_filters(r#"[{"kinds": [1]}]"#)),
+        );
+
+        let sub2: SubscriptionId = "sub2".parse().unwrap();
+        let found =
+            state.find_equivalent_subscription(&sub2, &parse_filters(r#"[{"kinds": [1, 7]}]"#));
+        assert_eq!(found, None);
+    }
+
+    // ========== created_at drift ==========
+
+    #[test]
+    fn test_created_at_drift_past_event() {
+        let event = crate::test_helpers::create_custom_event(1, 1_000, "past", vec![]);
+        assert_eq!(created_at_drift_seconds(&event, 1_060), -60);
+    }
+
+    #[test]
+    fn test_created_at_drift_future_event() {
+        let event = crate::test_helpers::create_custom_event(1, 1_120, "future", vec![]);
+        assert_eq!(created_at_drift_seconds(&event, 1_000), 120);
+    }
+
+    #[test]
+    fn test_created_at_drift_zero() {
+        let event = crate::test_helpers::create_custom_event(1, 1_000, "now", vec![]);
+        assert_eq!(created_at_drift_seconds(&event, 1_000), 0);
+    }
+
+    #[test]
+    fn test_created_at_drift_saturates_on_extreme_values() {
+        // 極端な過去のcreated_atでもオーバーフローせず飽和する
+        let event = crate::test_helpers::create_custom_event(1, i64::MIN, "extreme", vec![]);
+        assert_eq!(created_at_drift_seconds(&event, 1_000), i64::MIN);
+
+        // i64に収まらない現在時刻でもpanicしない
+        let event = crate::test_helpers::create_custom_event(1, 0, "epoch", vec![]);
+        assert_eq!(created_at_drift_seconds(&event, u64::MAX), -i64::MAX);
+    }
+
+    // ========== 不正なREQのサブスクリプションID抽出 ==========
+
+    #[test]
+    fn test_rejected_req_subscription_id() {
+        let text = r#"["REQ", "sub1", {"ids": ["zz"]}]"#;
+        assert_eq!(
+            rejected_req_subscription_id(text),
+            Some("sub1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_rejected_req_subscription_id_with_control_chars() {
+        let text = r#"["REQ", "sub\nid", {}]"#;
+        let subscription_id = rejected_req_subscription_id(text).unwrap();
+        assert_eq!(subscription_id.as_str(), "sub\nid");
+    }
+
+    #[test]
+    fn test_rejected_req_subscription_id_non_req() {
+        assert_eq!(rejected_req_subscription_id("not json"), None);
+        assert_eq!(rejected_req_subscription_id(r#"["CLOSE", "sub1"]"#), None);
+        assert_eq!(rejected_req_subscription_id(r#"["REQ", 1, {}]"#), None);
+        assert_eq!(rejected_req_subscription_id(r#"["REQ", "", {}]"#), None);
+    }
+
+    // ========== limit のクランプ ==========
+
+    #[test]
+    fn test_apply_limit_constraints() {
+        let limitation = LimitationConfig {
+            max_limit: 100,
+            ..Default::default()
+        };
+        let filters =
+            parse_filters(r#"[{"kinds": [1], "limit": 1000000}, {"limit": 10}, {"kinds": [7]}]"#);
+
+        let constrained = apply_limit_constraints(&filters, &limitation, 1000);
+        let limits: Vec<Option<u64>> = constrained.iter().map(|f| f.limit).collect();
+        // limit 未指定も max_limit で絞る
+        assert_eq!(limits, vec![Some(100), Some(10), Some(100)]);
+        // limit 以外の条件は変わらない
+        assert_eq!(constrained[0].kinds, filters[0].kinds);
+    }
+
+    #[test]
+    fn test_apply_limit_constraints_default_limit_skips_range_queries() {
+        let filters = parse_filters(
+            r#"[{}, {"since": 100}, {"since": 100, "until": 200}, {"limit": 10000}]"#,
+        );
+        let limits = |limitation: &LimitationConfig| -> Vec<Option<u64>> {
+            apply_limit_constraints(&filters, limitation, 1000)
+                .iter()
+                .map(|f| f.limit)
+                .collect()
+        };
+
+        // default_limit 未設定なら limit 未指定は max_limit
+        let limitation = LimitationConfig {
+            max_limit: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            limits(&limitation),
+            vec![Some(100), Some(100), Some(100), Some(100)]
+        );
+
+        // 片側だけの範囲は default_limit、since/until 両方の範囲は max_limit が上限
+        let limitation = LimitationConfig {
+            max_limit: 100,
+            default_limit: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits(&limitation),
+            vec![Some(20), Some(20), Some(100), Some(100)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_req_range_query_returns_all_events_in_range_up_to_max_limit() {
+        let mut handler = MessageHandler::new(
+            Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+            Arc::new(LimitationConfig {
+                created_at_lower_limit: u64::MAX,
+                max_limit: 5,
+                default_limit: Some(2),
+                ..Default::default()
+            }),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        for created_at in 1..=10 {
+            let event = crate::test_helpers::create_custom_event(1, created_at, "", vec![]);
+            handler.handle_text(&event_message(&event)).await;
+        }
+
+        for (req, expected) in [
+            (r#"["REQ", "s", {}]"#, 2),
+            (r#"["REQ", "s", {"since": 3}]"#, 2),
+            // 限定的な範囲は範囲内の全件
+            (r#"["REQ", "s", {"since": 3, "until": 5}]"#, 3),
+            // 広すぎる範囲は max_limit でクランプ
+            (r#"["REQ", "s", {"since": 1, "until": 10}]"#, 5),
+        ] {
+            let responses = handler.handle_text(req).await;
+            let event_count = responses
+                .iter()
+                .filter(|r| matches!(r, RelayMessage::Event { .. }))
+                .count();
+            assert_eq!(event_count, expected, "{req}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_req_without_limit_is_capped_by_max_limit() {
+        // default_limit 未設定でも、条件付きフィルタの limit 未指定は max_limit 件まで
+        let mut handler = MessageHandler::new(
+            Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+            Arc::new(LimitationConfig {
+                created_at_lower_limit: u64::MAX,
+                max_limit: 3,
+                ..Default::default()
+            }),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        for created_at in 1..=10 {
+            let event = crate::test_helpers::create_custom_event(1, created_at, "", vec![]);
+            handler.handle_text(&event_message(&event)).await;
+        }
+
+        for req in [
+            r#"["REQ", "s", {"kinds": [1]}]"#,
+            r#"["REQ", "s", {"since": 1, "until": 10}]"#,
+        ] {
+            let created_ats: Vec<i64> = handler
+                .handle_text(req)
+                .await
+                .iter()
+                .filter_map(|r| match r {
+                    RelayMessage::Event { event, .. } => Some(event.created_at.as_i64()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(created_ats, vec![10, 9, 8], "{req}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_req_unconditional_filter_is_limited_or_rejected() {
+        for require_filter in [false, true] {
+            let mut handler = MessageHandler::new(
+                Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+                Arc::new(LimitationConfig {
+                    created_at_lower_limit: u64::MAX,
+                    max_limit: 5,
+                    require_filter,
+                    ..Default::default()
+                }),
+                Arc::new(OwnerPriority::new(None)),
+            );
+            for created_at in 1..=10 {
+                let event = crate::test_helpers::create_custom_event(1, created_at, "", vec![]);
+                handler.handle_text(&event_message(&event)).await;
+            }
+
+            let responses = handler
+                .handle_text(r#"["REQ", "all", {"kinds": [7]}, {}]"#)
+                .await;
+            if require_filter {
+                assert_eq!(
+                    responses,
+                    vec![RelayMessage::closed_unconditional_filter(
+                        "all".parse().unwrap()
+                    )]
+                );
+                assert!(handler.state.subscriptions.is_empty());
+            } else {
+                // 制限モードでは default_limit 未設定でも最新 max_limit 件に絞る
+                let created_ats: Vec<i64> = responses
+                    .iter()
+                    .filter_map(|r| match r {
+                        RelayMessage::Event { event, .. } => Some(event.created_at.as_i64()),
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(created_ats, vec![10, 9, 8, 7, 6]);
+            }
+
+            // 条件のあるフィルタだけならどちらのモードでも受け付ける
+            let responses = handler
+                .handle_text(r#"["REQ", "notes", {"kinds": [1]}]"#)
+                .await;
+            assert!(matches!(responses.last(), Some(RelayMessage::Eose(_))));
+        }
+    }
+
+    #[test]
+    fn test_apply_limit_constraints_clamps_until_only_when_enabled() {
+        let filters = parse_filters(r#"[{"until": 5000}, {"until": 500}, {"kinds": [1]}]"#);
+        let untils = |limitation: &LimitationConfig| -> Vec<Option<i64>> {
+            apply_limit_constraints(&filters, limitation, 1000)
+                .iter()
+                .map(|f| f.until.map(|until| until.as_i64()))
+                .collect()
+        };
+
+        // デフォルトでは until をそのまま使う
+        assert_eq!(
+            untils(&LimitationConfig::default()),
+            vec![Some(5000), Some(500), None]
+        );
+
+        // 有効時は未来の until だけを現在時刻にクランプし、未指定はそのまま
+        let limitation = LimitationConfig {
+            clamp_until_to_now: true,
+            ..Default::default()
+        };
+        assert_eq!(untils(&limitation), vec![Some(1000), Some(500), None]);
+    }
+
+    // ========== content 長 ==========
+
+    #[test]
+    fn test_check_content_length_counts_unicode_chars() {
+        // バイト数ではなく文字（Unicode スカラー値）数で判定する
+        let limitation = LimitationConfig {
+            max_content_length: 3,
+            ..Default::default()
+        };
+        let within = crate::test_helpers::create_custom_event(1, 1000, "😀漢a", vec![]);
+        assert_eq!(check_content_length(&within, &limitation), None);
+
+        let over = crate::test_helpers::create_custom_event(1, 1000, "😀漢ab", vec![]);
+        assert_eq!(
+            check_content_length(&over, &limitation),
+            Some(RelayMessage::ok_content_too_long(over.id, 4, 3))
+        );
+    }
+
+    // ========== MessageHandler ==========
+
+    fn test_handler() -> MessageHandler<crate::store::InMemoryEventStore> {
+        let relay = Arc::new(Relay::new(crate::store::InMemoryEventStore::new()));
+        let limitation = LimitationConfig {
+            // テストイベントの created_at は固定値なので過去制限を無効化する
+            created_at_lower_limit: u64::MAX,
+            ..Default::default()
+        };
+        MessageHandler::new(
+            relay,
+            Arc::new(limitation),
+            Arc::new(OwnerPriority::new(None)),
+        )
+    }
+
+    fn event_message(event: &Event) -> String {
+        serde_json::json!(["EVENT", event]).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_handle_text_parse_error_returns_notice() {
+        let mut handler = test_handler();
+
+        let responses = handler.handle_text("not json").await;
+        assert_eq!(responses.len(), 1);
+        assert!(
+            matches!(&responses[0], RelayMessage::Notice(msg) if msg.starts_with("パースエラー")),
+            "パース失敗時は NOTICE を返すべき: {responses:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_text_event_returns_ok() {
+        let mut handler = test_handler();
+        let event = crate::test_helpers::create_test_event();
+
+        let responses = handler.handle_text(&event_message(&event)).await;
+        assert_eq!(responses, vec![RelayMessage::ok_accepted(event.id)]);
+
+        // 同じイベントの再送は duplicate
+        let responses = handler.handle_text(&event_message(&event)).await;
+        assert_eq!(responses, vec![RelayMessage::ok_duplicate(event.id)]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_text_req_returns_events_and_eose() {
+        let mut handler = test_handler();
+        let event = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&event)).await;
+
+        let responses = handler
+            .handle_text(r#"["REQ", "sub1", {"kinds": [1]}]"#)
+            .await;
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        assert_eq!(
+            responses,
+            vec![
+                RelayMessage::Event {
+                    subscription_id: sub_id.clone(),
+                    event: event.clone(),
+                },
+                RelayMessage::Eose(sub_id.clone()),
+            ]
+        );
+        assert!(handler.state.subscriptions.contains_key(&sub_id));
+    }
+
+    /// REQ の応答が「EVENT* → EOSE」か「CLOSED のみ」のどちらかであることを確認する
+    fn assert_eose_xor_closed(responses: &[RelayMessage], expect_eose: bool) {
+        let eose_count = responses
+            .iter()
+            .filter(|r| matches!(r, RelayMessage::Eose(_)))
+            .count();
+        let closed_count = responses
+            .iter()
+            .filter(|r| matches!(r, RelayMessage::Closed { .. }))
+            .count();
+        if expect_eose {
+            assert_eq!((eose_count, closed_count), (1, 0), "{responses:?}");
+            assert!(matches!(responses.last(), Some(RelayMessage::Eose(_))));
+        } else {
+            assert_eq!(responses.len(), 1, "{responses:?}");
+            assert_eq!(closed_count, 1, "{responses:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_req_sends_eose_even_when_no_events_match() {
+        let mut handler = test_handler();
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+
+        // ストアが空
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert_eq!(responses, vec![RelayMessage::Eose(sub_id.clone())]);
+
+        let event = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&event)).await;
+
+        // 全フィルタが非マッチ・空リスト・since > until のいずれでも EOSE だけを返す
+        for req in [
+            r#"["REQ", "sub1", {"kinds": [7]}, {"since": 999999999999}]"#,
+            r#"["REQ", "sub1", {"ids": []}]"#,
+            r#"["REQ", "sub1", {"since": 2000, "until": 1000}]"#,
+            r#"["REQ", "sub1", {"kinds": [1], "limit": 0}]"#,
+        ] {
+            let responses = handler.handle_text(req).await;
+            assert_eq!(responses, vec![RelayMessage::Eose(sub_id.clone())], "{req}");
+        }
+        assert!(handler.state.subscriptions[&sub_id].eose_sent);
+    }
+
+    #[tokio::test]
+    async fn test_req_without_filters_is_closed_without_eose() {
+        let mut handler = test_handler();
+
+        let responses = handler.handle_text(r#"["REQ", "sub1"]"#).await;
+        assert_eose_xor_closed(&responses, false);
+        assert!(handler.state.subscriptions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_req_rejected_by_limits_is_closed_without_eose() {
+        let relay = Arc::new(Relay::new(crate::store::InMemoryEventStore::new()));
+        let limitation = LimitationConfig {
+            max_filters: 1,
+            max_subscriptions: 1,
+            ..Default::default()
+        };
+        let mut handler = MessageHandler::new(
+            relay,
+            Arc::new(limitation),
+            Arc::new(OwnerPriority::new(None)),
+        );
+
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}, {}]"#).await;
+        assert_eose_xor_closed(&responses, false);
+
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert_eose_xor_closed(&responses, true);
+        let responses = handler.handle_text(r#"["REQ", "sub2", {}]"#).await;
+        assert_eose_xor_closed(&responses, false);
+    }
+
+    #[tokio::test]
+    async fn test_closed_req_leaves_no_subscription_and_same_id_can_resubscribe() {
+        let relay = Arc::new(Relay::new(crate::store::InMemoryEventStore::new()));
+        let limitation = LimitationConfig {
+            max_filters: 1,
+            max_subscriptions: 2,
+            reject_duplicate_subscriptions: true,
+            ..Default::default()
+        };
+        let mut handler = MessageHandler::new(
+            relay,
+            Arc::new(limitation),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        let sub1: SubscriptionId = "sub1".parse().unwrap();
+        let sub2: SubscriptionId = "sub2".parse().unwrap();
+        let sub3: SubscriptionId = "sub3".parse().unwrap();
+
+        handler
+            .handle_text(r#"["REQ", "sub1", {"kinds": [1]}]"#)
+            .await;
+        handler
+            .handle_text(r#"["REQ", "sub2", {"kinds": [7]}]"#)
+            .await;
+
+        // フィルタ数超過・重複購読・不正なフィルタで CLOSED になった ID の既存購読は残らない
+        for req in [
+            r#"["REQ", "sub2", {}, {}]"#,
+            r#"["REQ", "sub2", {"kinds": [1]}]"#,
+            r#"["REQ", "sub2", {"kinds": "invalid"}]"#,
+        ] {
+            handler
+                .handle_text(r#"["REQ", "sub2", {"kinds": [7]}]"#)
+                .await;
+            let responses = handler.handle_text(req).await;
+            assert_eose_xor_closed(&responses, false);
+            assert!(!handler.state.subscriptions.contains_key(&sub2), "{req}");
+            assert!(handler.state.subscriptions.contains_key(&sub1), "{req}");
+        }
+
+        // 購読数超過で CLOSED になった ID は登録されない
+        handler
+            .handle_text(r#"["REQ", "sub2", {"kinds": [7]}]"#)
+            .await;
+        let responses = handler.handle_text(r#"["REQ", "sub3", {}]"#).await;
+        assert_eose_xor_closed(&responses, false);
+        assert!(!handler.state.subscriptions.contains_key(&sub3));
+
+        // CLOSED の後の同じ ID での再 REQ は新規購読として処理される
+        handler.handle_text(r#"["REQ", "sub2", {}, {}]"#).await;
+        let responses = handler
+            .handle_text(r#"["REQ", "sub2", {"kinds": [30023]}]"#)
+            .await;
+        assert_eq!(responses, vec![RelayMessage::Eose(sub2.clone())]);
+        let subscription = &handler.state.subscriptions[&sub2];
+        assert!(subscription.eose_sent);
+        assert_eq!(
+            subscription.filters[0].kinds.as_ref().unwrap()[0].as_u16(),
+            30023
+        );
+    }
+
+    #[tokio::test]
+    async fn test_req_clamp_until_to_now_excludes_future_events() {
+        let now = unix_secs_to_i64(unix_now());
+        let past = crate::test_helpers::create_custom_event(1, now - 60, "past", vec![]);
+        let future = crate::test_helpers::create_custom_event(1, now + 600, "future", vec![]);
+        let req = serde_json::json!(["REQ", "sub1", {"until": now + 3600}]).to_string();
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+
+        for clamp_until_to_now in [false, true] {
+            let mut handler = MessageHandler::new(
+                Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+                Arc::new(LimitationConfig {
+                    clamp_until_to_now,
+                    ..Default::default()
+                }),
+                Arc::new(OwnerPriority::new(None)),
+            );
+            handler.handle_text(&event_message(&past)).await;
+            handler.handle_text(&event_message(&future)).await;
+
+            let responses = handler.handle_text(&req).await;
+            let mut expected = Vec::new();
+            if !clamp_until_to_now {
+                expected.push(RelayMessage::Event {
+                    subscription_id: sub_id.clone(),
+                    event: future.clone(),
+                });
+            }
+            expected.push(RelayMessage::Event {
+                subscription_id: sub_id.clone(),
+                event: past.clone(),
+            });
+            expected.push(RelayMessage::Eose(sub_id.clone()));
+            assert_eq!(
+                responses, expected,
+                "clamp_until_to_now={clamp_until_to_now}"
+            );
+        }
+    }
+
+    /// クエリが常に失敗するストア
+    struct FailingQueryStore;
+
+    impl EventStore for FailingQueryStore {
+        async fn save(&self, _event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            Ok(SaveResult::Saved)
+        }
+
+        async fn query(&self, _filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            Err(StoreError::Internal("query failed".to_string()))
+        }
+
+        async fn delete(&self, _event: &VerifiedEvent) -> Result<DeleteResult, StoreError> {
+            Ok(DeleteResult { deleted_count: 0 })
+        }
+
+        async fn purge_expired(
+            &self,
+            _policies: &[RetentionPolicy],
+            _now: u64,
+        ) -> Result<u64, StoreError> {
+            Ok(0)
+        }
+    }
+
+    /// 保存が常にサイズ上限超過で失敗するストア
+    struct TooLargeStore;
+
+    impl EventStore for TooLargeStore {
+        async fn save(&self, _event: &VerifiedEvent) -> Result<SaveResult, StoreError> {
+            Err(StoreError::EventTooLarge {
+                size: 409_601,
+                max: 409_600,
+            })
+        }
+
+        async fn query(&self, _filters: &[Filter]) -> Result<Vec<Event>, StoreError> {
+            Ok(vec![])
+        }
+
+        async fn delete(&self, _event: &VerifiedEvent) -> Result<DeleteResult, StoreError> {
+            Ok(DeleteResult { deleted_count: 0 })
+        }
+
+        async fn purge_expired(
+            &self,
+            _policies: &[RetentionPolicy],
+            _now: u64,
+        ) -> Result<u64, StoreError> {
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_too_large_for_storage_is_rejected_as_invalid() {
+        let mut handler = MessageHandler::new(
+            Arc::new(Relay::new(TooLargeStore)),
+            Arc::new(LimitationConfig {
+                created_at_lower_limit: u64::MAX,
+                ..Default::default()
+            }),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        let event = crate::test_helpers::create_test_event();
+
+        let responses = handler.handle_text(&event_message(&event)).await;
+        assert_eq!(
+            responses,
+            vec![RelayMessage::Ok {
+                event_id: event.id,
+                success: false,
+                message: "invalid: event too large for storage (409601 bytes, max 409600)"
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_req_query_error_is_closed_without_eose() {
+        let mut handler = MessageHandler::new(
+            Arc::new(Relay::new(FailingQueryStore)),
+            Arc::new(LimitationConfig::default()),
+            Arc::new(OwnerPriority::new(None)),
+        );
+
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert_eose_xor_closed(&responses, false);
+        // エラーになった購読は残さない
+        assert!(handler.state.subscriptions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_req_stores_normalized_filters() {
+        let mut handler = test_handler();
+        let event = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&event)).await;
+
+        let kinds = vec![1; 1000];
+        let req = serde_json::json!(["REQ", "sub1", {"kinds": kinds, "#t": ["b", "a", "b"]}]);
+        handler.handle_text(&req.to_string()).await;
+        let req = serde_json::json!(["REQ", "sub2", {"kinds": [7, 1, 1, 7]}]);
+        let responses = handler.handle_text(&req.to_string()).await;
+
+        // 重複した値で結果が変わらない
+        let sub2: SubscriptionId = "sub2".parse().unwrap();
+        assert_eq!(
+            responses,
+            vec![
+                RelayMessage::Event {
+                    subscription_id: sub2.clone(),
+                    event,
+                },
+                RelayMessage::Eose(sub2),
+            ]
+        );
+
+        // 購読には正規化済みのフィルタが保存される
+        let sub1: SubscriptionId = "sub1".parse().unwrap();
+        let stored = &handler.state.subscriptions[&sub1].filters[0];
+        assert_eq!(stored.kinds.as_ref().unwrap().len(), 1);
+        assert_eq!(
+            stored.tags.get('t'),
+            Some(&vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_text_close_returns_closed() {
+        let mut handler = test_handler();
+        handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+
+        let responses = handler.handle_text(r#"["CLOSE", "sub1"]"#).await;
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        assert_eq!(
+            responses,
+            vec![RelayMessage::closed_by_client(sub_id.clone())]
+        );
+        assert!(!handler.state.subscriptions.contains_key(&sub_id));
+    }
+
+    #[tokio::test]
+    async fn test_route_broadcast_matches_subscriptions() {
+        let mut handler = test_handler();
+        handler
+            .handle_text(r#"["REQ", "notes", {"kinds": [1]}]"#)
+            .await;
+        handler
+            .handle_text(r#"["REQ", "reactions", {"kinds": [7]}]"#)
+            .await;
+
+        let event = crate::test_helpers::create_test_event();
+        let routed = handler.route_broadcast(&event);
+        assert_eq!(
+            routed,
+            vec![RelayMessage::Event {
+                subscription_id: "notes".parse().unwrap(),
+                event,
+            }]
+        );
+    }
+
+    // ========== created_at の境界値 ==========
+
+    #[test]
+    fn test_unix_secs_to_i64_saturates() {
+        assert_eq!(unix_secs_to_i64(0), 0);
+        assert_eq!(unix_secs_to_i64(i64::MAX as u64), i64::MAX);
+        assert_eq!(unix_secs_to_i64(u64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn test_check_created_at_rejects_negative_even_for_owner() {
+        let owner_priority = OwnerPriority::new(Some(default_test_pubkey()));
+        let limitation = LimitationConfig::default();
+
+        for created_at in [-1, i64::MIN] {
+            let event = crate::test_helpers::create_custom_event(1, created_at, "negative", vec![]);
+            let result = check_created_at(&event, &limitation, &owner_priority);
+            assert_eq!(
+                result,
+                Some(RelayMessage::ok_created_at_negative(event.id, created_at)),
+                "created_at={created_at} は拒否されるべき"
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_created_at_rejects_i64_max() {
+        let owner_priority = OwnerPriority::new(None);
+        let limitation = LimitationConfig::default();
+        let event = crate::test_helpers::create_custom_event(1, i64::MAX, "max", vec![]);
+
+        let result = check_created_at(&event, &limitation, &owner_priority);
+        assert!(
+            matches!(result, Some(RelayMessage::Ok { success: false, ref message, .. }) if message.contains("future")),
+            "i64::MAX は未来すぎるとして拒否されるべき: {result:?}"
+        );
+    }
+
+    #[test]
+    fn test_check_created_at_huge_limits_do_not_wrap() {
+        // 制限値が u64 上限でも境界が負数に化けて全拒否にならない
+        let owner_priority = OwnerPriority::new(None);
+        let limitation = LimitationConfig {
+            created_at_lower_limit: u64::MAX,
+            created_at_upper_limit: u64::MAX,
+            ..Default::default()
+        };
+        for created_at in [0, unix_secs_to_i64(unix_now()), i64::MAX] {
+            let event = crate::test_helpers::create_custom_event(1, created_at, "ok", vec![]);
+            assert_eq!(
+                check_created_at(&event, &limitation, &owner_priority),
+                None,
+                "created_at={created_at} は受理されるべき"
+            );
+        }
+    }
+
+    // ========== EOSE 境界 ==========
+
+    #[tokio::test]
+    async fn test_eose_sent_transitions_on_req() {
+        let mut handler = test_handler();
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        assert!(!Subscription::new(vec![Filter::default()]).eose_sent);
+
+        handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert!(handler.state.subscriptions[&sub_id].eose_sent);
+
+        // 同じIDで上書きしても、EOSE を送り直した後はリアルタイム配信の対象
+        handler
+            .handle_text(r#"["REQ", "sub1", {"kinds": [1]}]"#)
+            .await;
+        assert!(handler.state.subscriptions[&sub_id].eose_sent);
+    }
+
+    #[tokio::test]
+    async fn test_route_broadcast_waits_for_eose() {
+        let mut handler = test_handler();
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        handler
+            .state
+            .subscriptions
+            .insert(sub_id.clone(), Subscription::new(vec![Filter::default()]));
+        let event = crate::test_helpers::create_test_event();
+
+        // EOSE 前は配信しない
+        assert!(handler.route_broadcast(&event).is_empty());
+
+        handler
+            .state
+            .subscriptions
+            .get_mut(&sub_id)
+            .unwrap()
+            .eose_sent = true;
+        assert_eq!(
+            handler.route_broadcast(&event),
+            vec![RelayMessage::Event {
+                subscription_id: sub_id,
+                event,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_broadcast_skips_events_sent_before_eose() {
+        // REQ の前に保存され、broadcast チャネルに残っていたイベントは二重送信しない
+        let mut handler = test_handler();
+        let stored = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&stored)).await;
+
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert_eq!(responses.len(), 2, "保存済みイベント + EOSE: {responses:?}");
+        assert!(handler.route_broadcast(&stored).is_empty());
+
+        // EOSE 後に届いた別のイベントは配信する
+        let realtime = crate::test_helpers::create_test_event_with_content("realtime");
+        assert_eq!(handler.route_broadcast(&realtime).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_req_boundary_is_managed_by_event_id_not_created_at() {
+        let mut handler = test_handler();
+        let accepted_at = unix_secs_to_i64(unix_now());
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        let delivered = |responses: &[RelayMessage]| -> Vec<String> {
+            responses
+                .iter()
+                .filter_map(|r| match r {
+                    RelayMessage::Event { event, .. } => Some(event.content.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // REQ 前に保存された、受付時刻と同じ created_at のイベント
+        let stored = crate::test_helpers::create_custom_event(1, accepted_at, "stored", vec![]);
+        handler.handle_text(&event_message(&stored)).await;
+
+        let responses = handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        assert_eq!(delivered(&responses), vec!["stored"]);
+        assert_eq!(responses.last(), Some(&RelayMessage::Eose(sub_id.clone())));
+
+        // broadcast チャネルに残っていた保存済みイベントは二重送信しない
+        assert!(handler.route_broadcast(&stored).is_empty());
+        handler.settle_broadcast_backlog();
+
+        // REQ 後に届いたイベントは created_at が受付時刻と同じでも、それより前でも配信する
+        let mut realtime = Vec::new();
+        for (created_at, content) in [(accepted_at, "same"), (accepted_at - 3600, "older")] {
+            let event = crate::test_helpers::create_custom_event(1, created_at, content, vec![]);
+            handler.handle_text(&event_message(&event)).await;
+            realtime.extend(handler.route_broadcast(&event));
+        }
+        assert_eq!(delivered(&realtime), vec!["same", "older"]);
+    }
+
+    #[tokio::test]
+    async fn test_settle_broadcast_backlog_forgets_sent_ids() {
+        let mut handler = test_handler();
+        let stored = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&stored)).await;
+        handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+
+        handler.settle_broadcast_backlog();
+
+        let sub_id: SubscriptionId = "sub1".parse().unwrap();
+        assert!(
+            handler.state.subscriptions[&sub_id]
+                .sent_before_eose
+                .is_empty()
+        );
+        assert_eq!(handler.route_broadcast(&stored).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verification_failure_reason_follows_verbose_errors() {
+        let event = crate::test_helpers::create_test_event();
+        let message = tampered_event_message(&event, "content", serde_json::json!("tampered"));
+
+        for verbose_errors in [true, false] {
+            let mut handler = MessageHandler::new(
+                Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+                Arc::new(LimitationConfig {
+                    created_at_lower_limit: u64::MAX,
+                    verbose_errors,
+                    ..Default::default()
+                }),
+                Arc::new(OwnerPriority::new(None)),
+            );
+            let responses = handler.handle_text(&message).await;
+            let [
+                RelayMessage::Ok {
+                    event_id,
+                    success: false,
+                    message,
+                },
+            ] = responses.as_slice()
+            else {
+                panic!("OK false を返すべき: {responses:?}");
+            };
+            assert_eq!(*event_id, event.id);
+            if verbose_errors {
+                assert!(
+                    message.starts_with("invalid: イベントIDが一致しません"),
+                    "{message}"
+                );
+            } else {
+                assert_eq!(message, "invalid:");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ok_message_includes_timing_only_when_enabled() {
+        let event = crate::test_helpers::create_test_event();
+        let message = serde_json::json!(["EVENT", event]).to_string();
+
+        for include_timing_in_ok in [false, true] {
+            let mut handler = MessageHandler::new(
+                Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+                Arc::new(LimitationConfig {
+                    created_at_lower_limit: u64::MAX,
+                    include_timing_in_ok,
+                    ..Default::default()
+                }),
+                Arc::new(OwnerPriority::new(None)),
+            );
+            let mut messages = Vec::new();
+            for _ in 0..2 {
+                match handler.handle_text(&message).await.as_slice() {
+                    [RelayMessage::Ok { message, .. }] => messages.push(message.clone()),
+                    responses => panic!("OK を1件返すべき: {responses:?}"),
+                }
+            }
+
+            if include_timing_in_ok {
+                assert!(messages[0].starts_with("(verify "), "{}", messages[0]);
+                // prefix はそのまま先頭に残り、処理時間は末尾に括弧で付く
+                assert!(
+                    messages[1].starts_with("duplicate: already have this event (verify "),
+                    "{}",
+                    messages[1]
+                );
+                assert!(messages[1].ends_with("ms)"), "{}", messages[1]);
+            } else {
+                assert_eq!(messages, vec!["", "duplicate: already have this event"]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uppercase_hex_event_is_stored_in_lowercase() {
+        let mut handler = test_handler();
+        let event = crate::test_helpers::create_test_event();
+        let mut json = serde_json::to_value(&event).unwrap();
+        for field in ["id", "pubkey", "sig"] {
+            json[field] = json[field].as_str().unwrap().to_ascii_uppercase().into();
+        }
+
+        // id/pubkey/sig はバイト列として保持するので、大文字 hex でも同じイベントになる
+        let responses = handler
+            .handle_text(&serde_json::json!(["EVENT", json]).to_string())
+            .await;
+        assert_eq!(responses, vec![RelayMessage::ok_accepted(event.id)]);
+        let responses = handler.handle_text(&event_message(&event)).await;
+        assert_eq!(responses, vec![RelayMessage::ok_duplicate(event.id)]);
+
+        // 小文字の id で取得でき、応答も小文字 hex になる
+        let req = serde_json::json!(["REQ", "sub1", {"ids": [event.id.to_string()]}]);
+        let responses = handler.handle_text(&req.to_string()).await;
+        let RelayMessage::Event { event: stored, .. } = &responses[0] else {
+            panic!("EVENT を返すべき: {responses:?}");
+        };
+        let stored_json = serde_json::to_value(stored).unwrap();
+        for field in ["id", "pubkey", "sig"] {
+            let value = stored_json[field].as_str().unwrap();
+            assert_eq!(value, value.to_ascii_lowercase(), "{field}");
+        }
+        assert_eq!(stored, &event);
+    }
+
+    // ========== STATS ==========
+
+    #[tokio::test]
+    async fn test_stats_counts_events_and_subscriptions() {
+        let mut handler = MessageHandler::new(
+            Arc::new(Relay::new(crate::store::InMemoryEventStore::new())),
+            Arc::new(LimitationConfig {
+                created_at_lower_limit: u64::MAX,
+                enable_stats_command: true,
+                ..Default::default()
+            }),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        let stats = |handler: &MessageHandler<_>| match handler.handle_stats() {
+            RelayMessage::StatsResult(stats) => stats,
+            other => panic!("STATS-RESULT を返すべき: {other:?}"),
+        };
+        assert_eq!(stats(&handler), ConnectionStats::default());
+
+        // 受け取った EVENT は拒否したものも数える
+        let stored = crate::test_helpers::create_test_event();
+        handler.handle_text(&event_message(&stored)).await;
+        handler
+            .handle_text(&tampered_event_message(
+                &stored,
+                "content",
+                serde_json::json!("tampered"),
+            ))
+            .await;
+
+        // 保存済みイベントの送信とリアルタイム配信の両方を数える
+        handler.handle_text(r#"["REQ", "sub1", {}]"#).await;
+        handler
+            .handle_text(r#"["REQ", "sub2", {"kinds": [1]}]"#)
+            .await;
+        handler.settle_broadcast_backlog();
+        let realtime = crate::test_helpers::create_test_event_with_content("realtime");
+        handler.route_broadcast(&realtime);
+
+        assert_eq!(
+            stats(&handler),
+            ConnectionStats {
+                events_received: 2,
+                events_sent: 4,
+                subscriptions_active: 2,
+            }
+        );
+
+        let responses = handler.handle_text(r#"["STATS"]"#).await;
+        assert_eq!(
+            serde_json::to_value(&responses).unwrap(),
+            serde_json::json!([[
+                "STATS-RESULT",
+                {"events_received": 2, "events_sent": 4, "subscriptions_active": 2}
+            ]])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stats_is_unknown_message_when_disabled() {
+        let mut handler = test_handler();
+
+        let responses = handler.handle_text(r#"["STATS"]"#).await;
+        assert_eq!(
+            responses,
+            vec![RelayMessage::Notice(
+                "パースエラー: 未知のメッセージタイプ: STATS".to_string()
+            )]
+        );
+    }
+
+    // ========== 拒否＝副作用なし ==========
+
+    /// 値を書き換えたイベントの JSON（再署名しないので id/sig は元のまま）
+    fn tampered_event_message(event: &Event, field: &str, value: serde_json::Value) -> String {
+        let mut json = serde_json::to_value(event).unwrap();
+        json[field] = value;
+        serde_json::json!(["EVENT", json]).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_rejected_events_are_not_stored() {
+        let relay = Arc::new(Relay::new(crate::store::InMemoryEventStore::new()));
+        let limitation = LimitationConfig {
+            max_event_tags: 2,
+            max_content_length: 10,
+            created_at_lower_limit: 3600,
+            created_at_upper_limit: 600,
+            ..Default::default()
+        };
+        let mut handler = MessageHandler::new(
+            relay.clone(),
+            Arc::new(limitation),
+            Arc::new(OwnerPriority::new(None)),
+        );
+        let now = unix_secs_to_i64(unix_now());
+        let create = crate::test_helpers::create_custom_event;
+        let valid = create(1, now, "valid", vec![]);
+        let other = create(1, now, "other", vec![]);
+
+        let cases = [
+            (
+                "invalid id",
+                tampered_event_message(&valid, "content", "tampered".into()),
+            ),
+            (
+                "invalid sig",
+                tampered_event_message(&valid, "sig", serde_json::to_value(other.sig).unwrap()),
+            ),
+            (
+                "too many tags",
+                event_message(&create(
+                    1,
+                    now,
+                    "tags",
+                    vec![vec!["t", "a"], vec!["t", "b"], vec!["t", "c"]],
+                )),
+            ),
+            (
+                "content too long",
+                event_message(&create(1, now, "content too long", vec![])),
+            ),
+            (
+                "created_at too old",
+                event_message(&create(1, now - 7200, "old", vec![])),
+            ),
+            (
+                "created_at too far in future",
+                event_message(&create(1, now + 7200, "future", vec![])),
+            ),
+            (
+                "negative created_at",
+                event_message(&create(1, -1, "negative", vec![])),
+            ),
+            (
+                "protected event",
+                event_message(&create(1, now, "protected", vec![vec!["-"]])),
+            ),
+        ];
+
+        for (case, message) in cases {
+            let responses = handler.handle_text(&message).await;
+            assert!(
+                matches!(
+                    responses.as_slice(),
+                    [RelayMessage::Ok { success: false, .. }]
+                ),
+                "{case}: OK false を返すべき: {responses:?}"
+            );
+            let stored = relay.query(&[Filter::default()]).await.unwrap();
+            assert!(stored.is_empty(), "{case}: 拒否したイベントが保存された");
+        }
+
+        // 対照: 有効なイベントは保存される
+        let responses = handler.handle_text(&event_message(&valid)).await;
+        assert_eq!(responses, vec![RelayMessage::ok_accepted(valid.id)]);
+        let stored = relay.query(&[Filter::default()]).await.unwrap();
+        assert_eq!(stored, vec![valid]);
+    }
 }
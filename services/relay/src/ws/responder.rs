@@ -0,0 +1,152 @@
+//! クライアントへの応答送信
+
+use std::fmt::Display;
+
+use axum::extract::ws::Message;
+use futures::{Sink, SinkExt};
+use tracing::{error, info, trace};
+
+use crate::models::RelayMessage;
+
+/// 送信に失敗し、接続が既に使えなくなっていることを表す
+///
+/// 受け取った側は接続処理を終了する（購読状態は `MessageHandler` ごと破棄される）。
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct ConnectionGone;
+
+/// 1接続分の WebSocket 送信を一元化する
+///
+/// OK / CLOSED / EOSE / EVENT / NOTICE の送信と、送信失敗時のログをここに集約する。
+pub(super) struct Responder<T> {
+    ws_tx: T,
+}
+
+impl<T> Responder<T>
+where
+    T: Sink<Message> + Unpin,
+    T::Error: Display,
+{
+    pub(super) fn new(ws_tx: T) -> Self {
+        Self { ws_tx }
+    }
+
+    /// RelayMessage を1件送信する
+    pub(super) async fn send(&mut self, msg: &RelayMessage) -> Result<(), ConnectionGone> {
+        let json = match serde_json::to_string(msg) {
+            Ok(json) => json,
+            Err(e) => {
+                // シリアライズは通常失敗しない。この1件だけ送らずに接続は維持する
+                error!(message_type = message_type(msg), error = %e, "応答のシリアライズに失敗");
+                return Ok(());
+            }
+        };
+        self.send_frame(Message::Text(json.into()), message_type(msg))
+            .await
+    }
+
+    /// 複数の RelayMessage を順に送信する（途中で失敗したら残りは送らない）
+    pub(super) async fn send_all(
+        &mut self,
+        msgs: impl IntoIterator<Item = RelayMessage>,
+    ) -> Result<(), ConnectionGone> {
+        for msg in msgs {
+            self.send(&msg).await?;
+        }
+        Ok(())
+    }
+
+    /// サーバーサイドPingを送信する
+    pub(super) async fn ping(&mut self) -> Result<(), ConnectionGone> {
+        trace!("サーバーサイドPing送信");
+        self.send_frame(Message::Ping(vec![].into()), "PING").await
+    }
+
+    /// Closeフレームを送信する（接続を閉じる直前なので失敗は無視する）
+    pub(super) async fn close(&mut self) {
+        let _ = self.ws_tx.send(Message::Close(None)).await;
+    }
+
+    async fn send_frame(
+        &mut self,
+        frame: Message,
+        message_type: &'static str,
+    ) -> Result<(), ConnectionGone> {
+        self.ws_tx.send(frame).await.map_err(|e| {
+            info!(message_type, error = %e, "送信失敗、接続を切断");
+            ConnectionGone
+        })
+    }
+}
+
+/// ログ用のメッセージ種別
+fn message_type(msg: &RelayMessage) -> &'static str {
+    match msg {
+        RelayMessage::Event { .. } => "EVENT",
+        RelayMessage::Ok { .. } => "OK",
+        RelayMessage::Eose(_) => "EOSE",
+        RelayMessage::Closed { .. } => "CLOSED",
+        RelayMessage::Notice(_) => "NOTICE",
+        RelayMessage::StatsResult(_) => "STATS-RESULT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SubscriptionId;
+    use futures::StreamExt;
+    use futures::channel::mpsc;
+
+    fn text_frames(frames: Vec<Message>) -> Vec<String> {
+        frames
+            .into_iter()
+            .map(|frame| match frame {
+                Message::Text(text) => text.to_string(),
+                other => panic!("テキストフレーム以外: {other:?}"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_send_all_serializes_in_order() {
+        let (tx, rx) = mpsc::unbounded();
+        let mut responder = Responder::new(tx);
+        let subscription_id: SubscriptionId = "sub1".parse().unwrap();
+
+        responder
+            .send_all([
+                RelayMessage::Eose(subscription_id.clone()),
+                RelayMessage::Closed {
+                    subscription_id,
+                    message: "".to_string(),
+                },
+                RelayMessage::Notice("hello".to_string()),
+            ])
+            .await
+            .unwrap();
+        drop(responder);
+
+        let frames: Vec<Message> = rx.collect().await;
+        assert_eq!(
+            text_frames(frames),
+            vec![
+                r#"["EOSE","sub1"]"#,
+                r#"["CLOSED","sub1",""]"#,
+                r#"["NOTICE","hello"]"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_to_closed_connection_returns_connection_gone() {
+        let (tx, rx) = mpsc::unbounded();
+        drop(rx);
+        let mut responder = Responder::new(tx);
+
+        let result = responder
+            .send(&RelayMessage::Notice("hello".to_string()))
+            .await;
+        assert_eq!(result, Err(ConnectionGone));
+        assert_eq!(responder.ping().await, Err(ConnectionGone));
+    }
+}
@@ -5,12 +5,16 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use futures::{SinkExt, StreamExt};
+use relay::connection_registry::{ConnectionInfo, ConnectionRegistry};
 use serde_json::{Value, json};
 use sha2::{Digest, Sha256};
 use tokio::net::TcpListener;
 use tokio::time::timeout;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
+/// テスト用リレーサーバーの管理APIトークン
+const ADMIN_TOKEN: &str = "test-admin-token";
+
 /// テスト用リレーサーバーを起動し、アドレスを返す
 async fn start_relay() -> SocketAddr {
     start_relay_with_config(relay::config::LimitationConfig::default()).await
@@ -18,32 +22,52 @@ async fn start_relay() -> SocketAddr {
 
 /// カスタム制限値設定でテスト用リレーサーバーを起動し、アドレスを返す
 async fn start_relay_with_config(limitation: relay::config::LimitationConfig) -> SocketAddr {
+    start_relay_with_registry(limitation).await.0
+}
+
+/// テスト用リレーサーバー（管理API付き）を起動し、アドレスと接続レジストリを返す
+async fn start_relay_with_registry(
+    limitation: relay::config::LimitationConfig,
+) -> (SocketAddr, Arc<ConnectionRegistry>) {
     let store = relay::store::InMemoryEventStore::new();
     let relay_instance = Arc::new(relay::relay::Relay::new(store));
     let limitation = Arc::new(limitation);
-
-    let app = axum::Router::new().route(
-        "/",
-        axum::routing::get(move |ws: axum::extract::ws::WebSocketUpgrade| {
-            let relay_clone = relay_instance.clone();
-            let lim_clone = limitation.clone();
-            async move {
-                let conn_id = uuid::Uuid::now_v7().to_string();
-                let owner_priority =
-                    std::sync::Arc::new(relay::owner_priority::OwnerPriority::new(None));
-                ws.on_upgrade(move |socket| {
-                    relay::ws::handle_socket(
-                        socket,
-                        relay_clone,
-                        conn_id,
-                        lim_clone,
-                        owner_priority,
-                        tokio_util::sync::CancellationToken::new(),
-                    )
-                })
-            }
-        }),
-    );
+    let registry = Arc::new(ConnectionRegistry::new());
+    let registry_clone = registry.clone();
+    let admin = relay::admin::router(registry.clone(), ADMIN_TOKEN.to_string());
+
+    let app = axum::Router::new()
+        .route(
+            "/",
+            axum::routing::get(move |ws: axum::extract::ws::WebSocketUpgrade| {
+                let relay_clone = relay_instance.clone();
+                let lim_clone = limitation.clone();
+                let registry = registry_clone.clone();
+                async move {
+                    let conn_id = uuid::Uuid::now_v7().to_string();
+                    let owner_priority =
+                        std::sync::Arc::new(relay::owner_priority::OwnerPriority::new(None));
+                    let info = ConnectionInfo::from_request(
+                        conn_id.clone(),
+                        &axum::http::HeaderMap::new(),
+                        "127.0.0.1:0".parse().unwrap(),
+                    );
+                    ws.on_upgrade(move |socket| {
+                        let connection =
+                            registry.register(info, &tokio_util::sync::CancellationToken::new());
+                        relay::ws::handle_socket(
+                            socket,
+                            relay_clone,
+                            conn_id,
+                            lim_clone,
+                            owner_priority,
+                            connection,
+                        )
+                    })
+                }
+            }),
+        )
+        .merge(admin);
 
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
     let addr = listener.local_addr().unwrap();
@@ -52,7 +76,7 @@ async fn start_relay_with_config(limitation: relay::config::LimitationConfig) ->
         axum::serve(listener, app).await.unwrap();
     });
 
-    addr
+    (addr, registry)
 }
 
 /// テスト用の有効なNostrイベントを生成
@@ -236,6 +260,37 @@ async fn test_self_broadcast() {
     assert_eq!(broadcast[2]["id"], event["id"]);
 }
 
+/// 保存直後の REQ で、保存済みイベントとして届いたものがリアルタイム配信で重複しないテスト
+#[tokio::test]
+async fn test_req_right_after_publish_does_not_duplicate() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    // EVENT と REQ を続けて送り、自分宛ての broadcast が未処理のうちに REQ を処理させる
+    let event = make_test_event("publish then subscribe", 1);
+    tx.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+    tx.send(text_msg(&json!(["REQ", "sub1", {"kinds": [1]}])))
+        .await
+        .unwrap();
+
+    let ok = recv_msg(&mut rx, 3000).await.expect("OK応答が来ない");
+    assert_eq!(ok[0], "OK");
+    let stored = recv_msg(&mut rx, 3000)
+        .await
+        .expect("保存済みイベントが来ない");
+    assert_eq!(stored[0], "EVENT");
+    assert_eq!(stored[2]["id"], event["id"]);
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose, json!(["EOSE", "sub1"]));
+
+    // 同じイベントがリアルタイム配信として再送されない
+    let extra = recv_msg(&mut rx, 500).await;
+    assert!(extra.is_none(), "同じイベントが二重に届いた: {extra:?}");
+}
+
 /// 複数フィルターのlimitが独立して適用されるテスト（NIP-01準拠）
 #[tokio::test]
 async fn test_multiple_filters_independent_limit() {
@@ -330,6 +385,65 @@ async fn test_req_overwrite_subscription() {
     assert_eq!(broadcast.unwrap()[2]["content"], "kind2 event");
 }
 
+/// 別接続が同じ subscription_id を使っても互いに影響しないテスト
+/// サブスクリプションは接続ごとに保持されるため、片方の上書き・CLOSE はもう片方に及ばない
+#[tokio::test]
+async fn test_same_subscription_id_isolated_between_connections() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws_a, _) = connect_async(&url).await.expect("A接続失敗");
+    let (mut tx_a, mut rx_a) = ws_a.split();
+    let (ws_b, _) = connect_async(&url).await.expect("B接続失敗");
+    let (mut tx_b, mut rx_b) = ws_b.split();
+    let (ws_c, _) = connect_async(&url).await.expect("C接続失敗");
+    let (mut tx_c, mut rx_c) = ws_c.split();
+
+    // A と B が同じ subid "shared" で購読し、B は kind=2 に上書きする
+    tx_a.send(text_msg(&json!(["REQ", "shared", {"kinds": [1]}])))
+        .await
+        .unwrap();
+    assert_eq!(recv_msg(&mut rx_a, 3000).await.unwrap()[0], "EOSE");
+    tx_b.send(text_msg(&json!(["REQ", "shared", {"kinds": [1]}])))
+        .await
+        .unwrap();
+    assert_eq!(recv_msg(&mut rx_b, 3000).await.unwrap()[0], "EOSE");
+    tx_b.send(text_msg(&json!(["REQ", "shared", {"kinds": [2]}])))
+        .await
+        .unwrap();
+    assert_eq!(recv_msg(&mut rx_b, 3000).await.unwrap()[0], "EOSE");
+
+    // C が kind=1 を送信 → A のみに届き、上書き済みの B には届かない
+    let event1 = make_test_event("kind1 for A", 1);
+    tx_c.send(text_msg(&json!(["EVENT", event1])))
+        .await
+        .unwrap();
+    let _ = recv_msg(&mut rx_c, 3000).await;
+    let msg = recv_msg(&mut rx_a, 3000)
+        .await
+        .expect("Aにbroadcastが届かない");
+    assert_eq!(msg[1], "shared");
+    assert_eq!(msg[2]["id"], event1["id"]);
+    assert!(
+        recv_msg(&mut rx_b, 500).await.is_none(),
+        "Bの上書きが効いていない"
+    );
+
+    // B が CLOSE しても A の購読は続く
+    tx_b.send(text_msg(&json!(["CLOSE", "shared"])))
+        .await
+        .unwrap();
+    assert_eq!(recv_msg(&mut rx_b, 3000).await.unwrap()[0], "CLOSED");
+
+    let event2 = make_test_event("kind1 again", 1);
+    tx_c.send(text_msg(&json!(["EVENT", event2])))
+        .await
+        .unwrap();
+    let _ = recv_msg(&mut rx_c, 3000).await;
+    let msg = recv_msg(&mut rx_a, 3000).await.expect("Aの購読が止まった");
+    assert_eq!(msg[2]["id"], event2["id"]);
+}
+
 /// 不正JSONへのNOTICE応答テスト
 #[tokio::test]
 async fn test_invalid_json_returns_notice() {
@@ -352,6 +466,281 @@ async fn test_invalid_json_returns_notice() {
     assert_eq!(resp2[0], "NOTICE");
 }
 
+/// content に対になっていないサロゲートを含む EVENT は保存されず NOTICE が返るテスト
+#[tokio::test]
+async fn test_event_with_lone_surrogate_content_returns_notice() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    let event = make_test_event("PLACEHOLDER", 1);
+    let raw = json!(["EVENT", event])
+        .to_string()
+        .replace("PLACEHOLDER", "\\ud83d");
+    tx.send(Message::Text(raw.into())).await.unwrap();
+    let resp = recv_msg(&mut rx, 3000).await.expect("NOTICE応答が来ない");
+    assert_eq!(resp[0], "NOTICE");
+
+    // 保存されていない
+    tx.send(text_msg(&json!(["REQ", "sub1", {}])))
+        .await
+        .unwrap();
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose, json!(["EOSE", "sub1"]));
+}
+
+/// ids/authors に不正な hex を含む REQ は CLOSED(invalid:) で拒否されるテスト
+#[tokio::test]
+async fn test_req_with_invalid_hex_filter_returns_closed() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    for (sub_id, filter) in [
+        ("bad-ids", json!({"ids": ["not-hex"]})),
+        ("bad-authors", json!({"authors": ["abc"]})),
+    ] {
+        tx.send(text_msg(&json!(["REQ", sub_id, filter])))
+            .await
+            .unwrap();
+        let closed = recv_msg(&mut rx, 3000).await.expect("CLOSEDが来ない");
+        assert_eq!(closed[0], "CLOSED");
+        assert_eq!(closed[1], sub_id);
+        let msg = closed[2].as_str().unwrap();
+        assert!(msg.starts_with("invalid:"), "invalid prefixがない: {msg}");
+    }
+}
+
+/// 制御文字を含むサブスクリプションIDの REQ は CLOSED(invalid:) で拒否されるテスト
+#[tokio::test]
+async fn test_req_with_control_char_subscription_id_returns_closed() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    for sub_id in ["sub\nid", "sub\u{0}id", "\u{1b}[31mred"] {
+        tx.send(text_msg(&json!(["REQ", sub_id, {}])))
+            .await
+            .unwrap();
+        let closed = recv_msg(&mut rx, 3000).await.expect("CLOSEDが来ない");
+        assert_eq!(closed[0], "CLOSED");
+        assert_eq!(closed[1], sub_id);
+        let msg = closed[2].as_str().unwrap();
+        assert!(msg.starts_with("invalid:"), "invalid prefixがない: {msg}");
+    }
+
+    // 印字可能な文字だけのIDは通常どおり受け付ける
+    tx.send(text_msg(&json!(["REQ", "sub id", {}])))
+        .await
+        .unwrap();
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose, json!(["EOSE", "sub id"]));
+}
+
+/// 全接続への NOTICE 通知テスト
+#[tokio::test]
+async fn test_broadcast_notice_reaches_all_connections() {
+    let (addr, registry) =
+        start_relay_with_registry(relay::config::LimitationConfig::default()).await;
+    let url = format!("ws://{addr}/");
+
+    let (ws_a, _) = connect_async(&url).await.expect("A接続失敗");
+    let (_tx_a, mut rx_a) = ws_a.split();
+    let (ws_b, _) = connect_async(&url).await.expect("B接続失敗");
+    let (_tx_b, mut rx_b) = ws_b.split();
+
+    // 両接続の登録を待つ
+    timeout(Duration::from_secs(3), async {
+        while registry.len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("接続が登録されない");
+
+    assert_eq!(registry.broadcast_notice("maintenance soon"), 2);
+    for rx in [&mut rx_a, &mut rx_b] {
+        let notice = recv_msg(rx, 3000).await.expect("NOTICEが来ない");
+        assert_eq!(notice, json!(["NOTICE", "maintenance soon"]));
+    }
+}
+
+/// 強制切断テスト: 対象の接続のみ閉じられ、切断後はレジストリから外れる
+#[tokio::test]
+async fn test_disconnect_closes_only_target_connection() {
+    let (addr, registry) =
+        start_relay_with_registry(relay::config::LimitationConfig::default()).await;
+    let url = format!("ws://{addr}/");
+
+    let (ws_a, _) = connect_async(&url).await.expect("A接続失敗");
+    let (_tx_a, mut rx_a) = ws_a.split();
+    timeout(Duration::from_secs(3), async {
+        while registry.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("接続が登録されない");
+    let target = registry
+        .find_by_ip("127.0.0.1".parse().unwrap())
+        .pop()
+        .unwrap()
+        .connection_id;
+
+    let (ws_b, _) = connect_async(&url).await.expect("B接続失敗");
+    let (mut tx_b, mut rx_b) = ws_b.split();
+
+    assert!(registry.disconnect(&target));
+    let closed = timeout(Duration::from_secs(3), rx_a.next())
+        .await
+        .expect("Closeが来ない");
+    assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
+
+    // もう一方の接続は生きている
+    tx_b.send(text_msg(&json!(["REQ", "alive", {"kinds": [1]}])))
+        .await
+        .unwrap();
+    let eose = recv_msg(&mut rx_b, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose[0], "EOSE");
+
+    timeout(Duration::from_secs(3), async {
+        while registry.get(&target).is_some() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("切断後もレジストリに残っている");
+}
+
+/// 登録を待って接続IDを返す
+async fn wait_for_connection(registry: &ConnectionRegistry) -> String {
+    timeout(Duration::from_secs(3), async {
+        loop {
+            if let Some(info) = registry.find_by_ip("127.0.0.1".parse().unwrap()).pop() {
+                return info.connection_id;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("接続が登録されない")
+}
+
+/// 管理API: トークンが無い・一致しないリクエストは拒否する
+#[tokio::test]
+async fn test_admin_api_requires_token() {
+    let (addr, _registry) =
+        start_relay_with_registry(relay::config::LimitationConfig::default()).await;
+    let client = reqwest::Client::new();
+    let url = format!("http://{addr}/admin/notice");
+
+    let response = client
+        .post(&url)
+        .json(&json!({"message": "hi"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .post(&url)
+        .bearer_auth("wrong-token")
+        .json(&json!({"message": "hi"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+/// 管理API: NOTICE の送信と接続IDによる強制切断
+#[tokio::test]
+async fn test_admin_api_notice_and_disconnect() {
+    let (addr, registry) =
+        start_relay_with_registry(relay::config::LimitationConfig::default()).await;
+    let client = reqwest::Client::new();
+
+    let (ws, _) = connect_async(format!("ws://{addr}/"))
+        .await
+        .expect("接続失敗");
+    let (_tx, mut rx) = ws.split();
+    let connection_id = wait_for_connection(&registry).await;
+
+    let response = client
+        .post(format!("http://{addr}/admin/notice"))
+        .bearer_auth(ADMIN_TOKEN)
+        .json(&json!({"message": "maintenance soon"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({"notified": 1})
+    );
+    let notice = recv_msg(&mut rx, 3000).await.expect("NOTICEが来ない");
+    assert_eq!(notice, json!(["NOTICE", "maintenance soon"]));
+
+    // 存在しない接続は 404
+    let response = client
+        .post(format!(
+            "http://{addr}/admin/connections/unknown/disconnect"
+        ))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = client
+        .post(format!(
+            "http://{addr}/admin/connections/{connection_id}/disconnect"
+        ))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let closed = timeout(Duration::from_secs(3), rx.next())
+        .await
+        .expect("Closeが来ない");
+    assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
+}
+
+/// 管理API: 送信元IPによる強制切断
+#[tokio::test]
+async fn test_admin_api_disconnect_by_ip() {
+    let (addr, registry) =
+        start_relay_with_registry(relay::config::LimitationConfig::default()).await;
+
+    let (ws, _) = connect_async(format!("ws://{addr}/"))
+        .await
+        .expect("接続失敗");
+    let (_tx, mut rx) = ws.split();
+    wait_for_connection(&registry).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{addr}/admin/ips/127.0.0.1/disconnect"))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        response.json::<Value>().await.unwrap(),
+        json!({"disconnected": 1})
+    );
+    let closed = timeout(Duration::from_secs(3), rx.next())
+        .await
+        .expect("Closeが来ない");
+    assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
+}
+
 /// 重複イベントのOK応答テスト
 #[tokio::test]
 async fn test_duplicate_event_ok_response() {
@@ -451,6 +840,52 @@ async fn test_realtime_broadcast_between_clients() {
     assert_eq!(broadcast[2]["content"], "realtime test");
 }
 
+/// OK は EVENT を送った接続にだけ返り、購読者には EVENT だけが配信されるテスト
+///
+/// 送信者 A は自分でも購読しているので OK と EVENT の両方を受け取り、
+/// 購読者 B は EVENT のみ、購読していない C には何も届かない。
+#[tokio::test]
+async fn test_ok_is_sent_only_to_sender_connection() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws_a, _) = connect_async(&url).await.expect("A接続失敗");
+    let (mut tx_a, mut rx_a) = ws_a.split();
+    let (ws_b, _) = connect_async(&url).await.expect("B接続失敗");
+    let (mut tx_b, mut rx_b) = ws_b.split();
+    let (ws_c, _) = connect_async(&url).await.expect("C接続失敗");
+    let (_tx_c, mut rx_c) = ws_c.split();
+
+    for (tx, rx, sub) in [(&mut tx_a, &mut rx_a, "a"), (&mut tx_b, &mut rx_b, "b")] {
+        tx.send(text_msg(&json!(["REQ", sub, {"kinds": [1]}])))
+            .await
+            .unwrap();
+        let eose = recv_msg(rx, 3000).await.expect("EOSEが来ない");
+        assert_eq!(eose, json!(["EOSE", sub]));
+    }
+
+    let event = make_test_event("ok routing", 1);
+    tx_a.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+
+    // A: 応答の OK が先、続いて自分の購読への EVENT
+    let ok = recv_msg(&mut rx_a, 3000).await.expect("AのOK応答が来ない");
+    assert_eq!(ok, json!(["OK", event["id"], true, ""]));
+    let own = recv_msg(&mut rx_a, 3000).await.expect("AにEVENTが来ない");
+    assert_eq!(own, json!(["EVENT", "a", event]));
+
+    // B: EVENT のみ
+    let delivered = recv_msg(&mut rx_b, 3000).await.expect("BにEVENTが来ない");
+    assert_eq!(delivered, json!(["EVENT", "b", event]));
+
+    // どの接続にもそれ以上は届かない（OK が他接続に漏れない）
+    assert!(
+        recv_msg(&mut rx_a, 300).await.is_none(),
+        "Aに余分なメッセージ"
+    );
+    assert!(recv_msg(&mut rx_b, 300).await.is_none(), "BにOKが漏れた");
+    assert!(recv_msg(&mut rx_c, 300).await.is_none(), "CにOKが漏れた");
+}
+
 /// Replaceable イベント（kind=0）を2回送信し、REQで最新1件のみ返ることを確認
 #[tokio::test]
 async fn test_replaceable_event_returns_latest_only() {
@@ -497,6 +932,206 @@ async fn test_replaceable_event_returns_latest_only() {
     assert_eq!(events[0][2]["content"], "new profile");
 }
 
+/// 同一接続から応答を待たずに連続送信した EVENT が受信順に処理され、
+/// Replaceable の最終状態が最後に送ったイベントになることを確認
+#[tokio::test]
+async fn test_pipelined_replaceable_events_processed_in_order() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let events: Vec<Value> = (0..5)
+        .map(|i| make_test_event_with_timestamp(&format!("profile {i}"), 0, now - 5 + i))
+        .collect();
+
+    // OK を待たずにまとめて送信
+    for event in &events {
+        tx.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+    }
+
+    // OK は送信順に返る
+    for event in &events {
+        let ok = recv_msg(&mut rx, 3000).await.expect("OK応答が来ない");
+        assert_eq!(ok[0], "OK");
+        assert_eq!(ok[1], event["id"]);
+        assert_eq!(ok[2], true);
+    }
+
+    tx.send(text_msg(&json!(["REQ", "profile", {"kinds": [0]}])))
+        .await
+        .unwrap();
+    let latest = recv_msg(&mut rx, 3000).await.expect("EVENTが来ない");
+    assert_eq!(latest[0], "EVENT");
+    assert_eq!(latest[2]["id"], events[4]["id"]);
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose[0], "EOSE");
+}
+
+/// 複合フィルター（kinds + タグ + 時間範囲 + limit）の REQ で、保存済みイベントが
+/// created_at 降順・フィルターごとの limit 内で返り、最後に EOSE が来ることを確認するシナリオテスト
+#[tokio::test]
+async fn test_req_scenario_with_compound_filters() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    // 300件投入: kind は 3件に1件が 7、それ以外は 1。t タグは even/odd を交互に付与
+    let base = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 1000;
+    let events: Vec<Value> = (0..300u64)
+        .map(|i| {
+            let kind = if i % 3 == 0 { 7 } else { 1 };
+            let parity = if i % 2 == 0 { "even" } else { "odd" };
+            make_test_event_full(
+                &format!("scenario {i}"),
+                kind,
+                base + i,
+                vec![vec!["t", parity]],
+            )
+        })
+        .collect();
+    for event in &events {
+        tx.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+    }
+    for _ in &events {
+        let ok = recv_msg(&mut rx, 3000).await.expect("OK応答が来ない");
+        assert_eq!(ok[2], true, "保存に失敗: {ok}");
+    }
+
+    let filters = vec![
+        json!({"kinds": [1], "#t": ["even"], "since": base + 100, "until": base + 200, "limit": 10}),
+        json!({"kinds": [7], "limit": 5}),
+        json!({"kinds": [1], "#t": ["odd"], "until": base + 50}),
+    ];
+
+    // 期待値: フィルターごとにマッチ → created_at 降順 → limit → 重複排除して全体を降順
+    let matches = |event: &Value, filter: &Value| {
+        let created_at = event["created_at"].as_u64().unwrap();
+        filter["kinds"]
+            .as_array()
+            .is_none_or(|kinds| kinds.contains(&event["kind"]))
+            && filter["#t"]
+                .as_array()
+                .is_none_or(|values| values.contains(&event["tags"][0][1]))
+            && filter["since"]
+                .as_u64()
+                .is_none_or(|since| created_at >= since)
+            && filter["until"]
+                .as_u64()
+                .is_none_or(|until| created_at <= until)
+    };
+    let mut expected: Vec<&Value> = Vec::new();
+    for filter in &filters {
+        let mut matched: Vec<&Value> = events.iter().filter(|e| matches(e, filter)).collect();
+        matched.sort_by_key(|e| std::cmp::Reverse(e["created_at"].as_u64().unwrap()));
+        if let Some(limit) = filter["limit"].as_u64() {
+            matched.truncate(limit as usize);
+        }
+        for event in matched {
+            if !expected.iter().any(|e| e["id"] == event["id"]) {
+                expected.push(event);
+            }
+        }
+    }
+    expected.sort_by_key(|e| std::cmp::Reverse(e["created_at"].as_u64().unwrap()));
+    // 10 + 5 + (0..=50 の奇数で kind 1 のもの)
+    assert_eq!(expected.len(), 10 + 5 + 17);
+
+    let mut req = vec![json!("REQ"), json!("scenario")];
+    req.extend(filters);
+    tx.send(text_msg(&Value::Array(req))).await.unwrap();
+
+    for event in &expected {
+        let msg = recv_msg(&mut rx, 3000).await.expect("EVENTが来ない");
+        assert_eq!(msg[0], "EVENT");
+        assert_eq!(msg[1], "scenario");
+        assert_eq!(msg[2], **event);
+    }
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose, json!(["EOSE", "scenario"]));
+    assert!(
+        recv_msg(&mut rx, 300).await.is_none(),
+        "EOSE後に余分なメッセージが来た"
+    );
+}
+
+/// 単一フィルター内の authors / kinds / #t / since / until がすべて AND で評価され、
+/// 一部の条件だけを満たすイベントが混ざらないことを確認する
+#[tokio::test]
+async fn test_req_single_filter_combines_all_conditions_with_and() {
+    let addr = start_relay().await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    let alice = "0000000000000000000000000000000000000000000000000000000000000001";
+    let bob = "0000000000000000000000000000000000000000000000000000000000000002";
+    let base = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 1000;
+    let nostr = || vec![vec!["t", "nostr"]];
+    let other = || vec![vec!["t", "other"]];
+
+    // 全条件を満たすイベント（since / until の境界を含む）
+    let all_match = vec![
+        make_test_event_by(alice, "all until", 1, base + 20, nostr()),
+        make_test_event_by(alice, "all middle", 1, base + 15, nostr()),
+        make_test_event_by(alice, "all since", 1, base + 10, nostr()),
+    ];
+    let non_matching = vec![
+        // 1条件だけ満たす
+        make_test_event_by(alice, "author only", 7, base + 100, other()),
+        make_test_event_by(bob, "kind only", 1, base + 100, other()),
+        make_test_event_by(bob, "tag only", 7, base + 100, nostr()),
+        make_test_event_by(bob, "time only", 7, base + 15, vec![]),
+        // 1条件だけ満たさない
+        make_test_event_by(bob, "all but author", 1, base + 15, nostr()),
+        make_test_event_by(alice, "all but kind", 7, base + 15, nostr()),
+        make_test_event_by(alice, "all but tag", 1, base + 15, other()),
+        make_test_event_by(alice, "all but since", 1, base + 9, nostr()),
+        make_test_event_by(alice, "all but until", 1, base + 21, nostr()),
+        // 全く満たさない
+        make_test_event_by(bob, "none", 7, base + 100, vec![]),
+    ];
+    for event in all_match.iter().chain(&non_matching) {
+        tx.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+        let ok = recv_msg(&mut rx, 3000).await.expect("OK応答が来ない");
+        assert_eq!(ok[2], true, "保存に失敗: {ok}");
+    }
+
+    let filter = json!({
+        "authors": [all_match[0]["pubkey"]],
+        "kinds": [1],
+        "#t": ["nostr"],
+        "since": base + 10,
+        "until": base + 20,
+    });
+    tx.send(text_msg(&json!(["REQ", "and", filter])))
+        .await
+        .unwrap();
+
+    for event in &all_match {
+        let msg = recv_msg(&mut rx, 3000).await.expect("EVENTが来ない");
+        assert_eq!(msg, json!(["EVENT", "and", event]));
+    }
+    let eose = recv_msg(&mut rx, 3000).await.expect("EOSEが来ない");
+    assert_eq!(eose, json!(["EOSE", "and"]));
+}
+
 // ===========================================
 // 制限値 (limitation) E2Eテスト
 // ===========================================
@@ -568,6 +1203,46 @@ fn make_test_event_with_timestamp(content: &str, kind: u64, created_at: u64) ->
     })
 }
 
+/// max_limit: limit が上限を超える REQ は max_limit 件にクランプされるテスト
+#[tokio::test]
+async fn test_limitation_max_limit_clamps_request_limit() {
+    let config = relay::config::LimitationConfig {
+        max_limit: 3,
+        ..Default::default()
+    };
+    let addr = start_relay_with_config(config).await;
+    let url = format!("ws://{addr}/");
+
+    let (ws, _) = connect_async(&url).await.expect("接続失敗");
+    let (mut tx, mut rx) = ws.split();
+
+    let base = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 100;
+    for i in 0..5 {
+        let event = make_test_event_with_timestamp(&format!("note {i}"), 1, base + i);
+        tx.send(text_msg(&json!(["EVENT", event]))).await.unwrap();
+        let _ = recv_msg(&mut rx, 3000).await;
+    }
+
+    tx.send(text_msg(
+        &json!(["REQ", "clamp", {"kinds": [1], "limit": 1000}]),
+    ))
+    .await
+    .unwrap();
+    let mut contents = vec![];
+    loop {
+        let msg = recv_msg(&mut rx, 3000).await.expect("応答が来ない");
+        if msg[0] == "EOSE" {
+            break;
+        }
+        contents.push(msg[2]["content"].as_str().unwrap().to_string());
+    }
+    assert_eq!(contents, vec!["note 4", "note 3", "note 2"]);
+}
+
 /// max_message_length 制限テスト
 #[tokio::test]
 async fn test_limitation_max_message_length() {
@@ -674,7 +1349,7 @@ async fn test_limitation_max_subscriptions() {
         resp3[2]
             .as_str()
             .unwrap()
-            .contains("too many subscriptions")
+            .starts_with("rate-limited: too many subscriptions")
     );
 }
 
@@ -903,8 +1578,24 @@ async fn test_limitation_subscription_overwrite_not_counted() {
 
 /// タグ付き・カスタムcreated_atのテストイベント作成
 fn make_test_event_full(content: &str, kind: u64, created_at: u64, tags: Vec<Vec<&str>>) -> Value {
-    let secret_key_bytes =
-        hex::decode("0000000000000000000000000000000000000000000000000000000000000001").unwrap();
+    make_test_event_by(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        content,
+        kind,
+        created_at,
+        tags,
+    )
+}
+
+/// 秘密鍵（hex）を指定したテストイベント作成
+fn make_test_event_by(
+    secret_key_hex: &str,
+    content: &str,
+    kind: u64,
+    created_at: u64,
+    tags: Vec<Vec<&str>>,
+) -> Value {
+    let secret_key_bytes = hex::decode(secret_key_hex).unwrap();
     let secp = secp256k1::Secp256k1::new();
     let secret_key =
         secp256k1::SecretKey::from_byte_array(secret_key_bytes.try_into().unwrap()).unwrap();
@@ -960,8 +1651,8 @@ async fn test_nip70_protected_event_rejected() {
     assert_eq!(resp[2], false, "保護イベントは拒否されるべき");
     let msg = resp[3].as_str().unwrap();
     assert!(
-        msg.starts_with("blocked:"),
-        "blocked: プレフィックスが必要: {msg}"
+        msg.starts_with("restricted:"),
+        "restricted: プレフィックスが必要: {msg}"
     );
 }
 
@@ -59,14 +59,22 @@ async fn handler(
             let limitation = state.limitation.clone();
             let owner_priority =
                 std::sync::Arc::new(relay::owner_priority::OwnerPriority::new(None));
+            let info = relay::connection_registry::ConnectionInfo::from_request(
+                conn_id.clone(),
+                &headers,
+                "127.0.0.1:0".parse().unwrap(),
+            );
             ws.on_upgrade(move |socket| {
+                let connection =
+                    std::sync::Arc::new(relay::connection_registry::ConnectionRegistry::new())
+                        .register(info, &tokio_util::sync::CancellationToken::new());
                 relay::ws::handle_socket(
                     socket,
                     relay,
                     conn_id,
                     limitation,
                     owner_priority,
-                    tokio_util::sync::CancellationToken::new(),
+                    connection,
                 )
             })
         }
@@ -230,6 +238,9 @@ async fn test_nip11_valid_response() {
         limitation["max_subid_length"],
         relay::config::DEFAULT_MAX_SUBID_LENGTH
     );
+    assert_eq!(limitation["max_limit"], relay::config::DEFAULT_MAX_LIMIT);
+    // default_limit は未設定なら出力しない
+    assert!(limitation.get("default_limit").is_none());
     assert_eq!(
         limitation["max_event_tags"],
         relay::config::DEFAULT_MAX_EVENT_TAGS
@@ -53,6 +53,12 @@ resource "aws_dynamodb_table" "events" {
     write_capacity  = 2
   }
 
+  # retention policy（RELAY_RETENTION_POLICIES）対象イベントの自動削除
+  ttl {
+    attribute_name = "ttl"
+    enabled        = true
+  }
+
   tags = {
     Name = "nostr-relay-events"
   }